@@ -0,0 +1,60 @@
+//! Phases of checking programs generated by `gen-testcase`, in process:
+//! `lex` and `check` on a clean program, and `render` of the diagnostics of a
+//! program with an error in every tenth statement, as text, short lines and JSON.
+use compilation_exp1::{
+    diagnostics::{self, DiagnosticsFormat},
+    lex, pre_grammar, testcase, ErrorRecorder, LexOptions, SourceMap,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+
+const SIZES: [usize; 4] = [1_000, 10_000, 100_000, 1_000_000];
+
+fn phases(c: &mut Criterion) {
+    let options = LexOptions::default();
+    let mut group = c.benchmark_group("check");
+    group.sample_size(10);
+    for size in SIZES {
+        let mut sources = SourceMap::new();
+        let file = sources.add("bench.in", testcase::gen_testcase(size, false, 0));
+        let input = sources.content(file);
+        let (tokens, tables) = lex::lex(input, &options, &mut ErrorRecorder::new(file));
+        group.throughput(Throughput::Elements(tokens.len() as u64));
+        group.bench_with_input(BenchmarkId::new("lex", size), input, |b, input| {
+            b.iter(|| lex::lex(input, &options, &mut ErrorRecorder::new(file)))
+        });
+        group.bench_with_input(BenchmarkId::new("check", size), &tokens, |b, tokens| {
+            b.iter_batched(
+                || (tokens.clone(), tables.clone()),
+                |(tokens, tables)| pre_grammar::check(tokens, tables, ErrorRecorder::new(file)),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn render(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render");
+    group.sample_size(10);
+    for size in SIZES {
+        let mut sources = SourceMap::new();
+        let file = sources.add("bench.in", testcase::gen_testcase(size, true, 0));
+        let result = compilation_exp1::parse(&sources, file, &LexOptions::default());
+        let diagnostics = result.errors.sorted();
+        group.throughput(Throughput::Elements(diagnostics.len() as u64));
+        group.bench_function(BenchmarkId::new("text", size), |b| {
+            b.iter(|| result.errors.display_with(&sources))
+        });
+        for (name, format) in [
+            ("short", DiagnosticsFormat::Short),
+            ("json", DiagnosticsFormat::Json),
+        ] {
+            group.bench_function(BenchmarkId::new(name, size), |b| {
+                b.iter(|| diagnostics::render(format, &sources, &diagnostics))
+            });
+        }
+    }
+    group.finish();
+}
+criterion_group!(benches, phases, render);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+//! Lexing a generated program of 100k tokens, and the keyword lookup alone
+//! on every identifier and keyword of it.
+use compilation_exp1::{
+    lex::{self, keyword},
+    testcase, ErrorRecorder, FileId, LexOptions, TokenEnum,
+};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+const TOKENS: usize = 100_000;
+
+fn lexing(c: &mut Criterion) {
+    let input = testcase::gen_testcase(TOKENS, false, 0);
+    let options = LexOptions::default();
+    let (tokens, _) = lex::lex(&input, &options, &mut ErrorRecorder::new(FileId::default()));
+    let words: Vec<String> = tokens
+        .iter()
+        .filter(|t| t.token == TokenEnum::Identifier || keyword(&t.content).is_some())
+        .map(|t| t.content.clone())
+        .collect();
+
+    let mut group = c.benchmark_group("lex");
+    group.sample_size(20);
+    group.throughput(Throughput::Elements(tokens.len() as u64));
+    group.bench_function("lex", |b| {
+        b.iter(|| lex::lex(&input, &options, &mut ErrorRecorder::new(FileId::default())))
+    });
+    group.throughput(Throughput::Elements(words.len() as u64));
+    group.bench_function("keyword", |b| {
+        b.iter(|| words.iter().filter_map(|w| keyword(w)).count())
+    });
+    group.finish();
+}
+criterion_group!(benches, lexing);
+criterion_main!(benches);
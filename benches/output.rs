@@ -0,0 +1,40 @@
+//! Token output of a generated program of 100k tokens:
+//! writing the pretty JSON against the MessagePack dump, and reading them back.
+use compilation_exp1::{
+    output::{self, OutputWriter},
+    parse,
+    session::TokenDump,
+    testcase, LexOptions, SourceMap,
+};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+const TOKENS: usize = 100_000;
+
+fn token_output(c: &mut Criterion) {
+    let mut sources = SourceMap::new();
+    let file = sources.add("bench.in", testcase::gen_testcase(TOKENS, false, 0));
+    let result = parse(&sources, file, &LexOptions::default());
+    let json = output::Json.write(&result).unwrap();
+    let msgpack = output::MessagePack.write(&result).unwrap();
+    let dump = output::read_msgpack(&msgpack).unwrap();
+    assert_eq!(dump.tokens.len(), result.tokens.len());
+
+    let mut group = c.benchmark_group("output");
+    group.sample_size(20);
+    group.throughput(Throughput::Elements(result.tokens.len() as u64));
+    group.bench_function("write-json", |b| {
+        b.iter(|| output::Json.write(&result).unwrap())
+    });
+    group.bench_function("write-msgpack", |b| {
+        b.iter(|| output::MessagePack.write(&result).unwrap())
+    });
+    group.bench_function("read-json", |b| {
+        b.iter(|| serde_json::from_slice::<TokenDump>(&json).unwrap())
+    });
+    group.bench_function("read-msgpack", |b| {
+        b.iter(|| output::read_msgpack(&msgpack).unwrap())
+    });
+    group.finish();
+}
+criterion_group!(benches, token_output);
+criterion_main!(benches);
@@ -0,0 +1,310 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{lex::TokenEnum, source_map::Span};
+
+/// Program block, the statements between the outermost `begin` and `end`,
+/// with the bodies of the procedures and functions declared before it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Program {
+    pub procedures: Vec<Procedure>,
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+impl Program {
+    /// Number of statements, including nested ones and those of the procedures.
+    pub fn statement_count(&self) -> usize {
+        let procedures = self.procedures.iter().flat_map(|p| &p.statements);
+        procedures
+            .chain(&self.statements)
+            .map(Statement::count)
+            .sum()
+    }
+}
+/// Body of a procedure or function, the signature is in the symbol table.
+#[derive(Debug, Clone, Serialize)]
+pub struct Procedure {
+    /// Lowercase name
+    pub name: String,
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct Statement {
+    #[serde(flatten)]
+    pub kind: StatementKind,
+    pub span: Span,
+}
+impl Statement {
+    /// Number of statements, including this one and the nested ones.
+    /// Empty statements are not counted.
+    fn count(&self) -> usize {
+        1 + match &self.kind {
+            StatementKind::Empty => return 0,
+            StatementKind::If {
+                then_branch,
+                else_branch,
+                ..
+            } => then_branch.count() + else_branch.as_ref().map_or(0, |s| s.count()),
+            StatementKind::While { body, .. } => body.count(),
+            StatementKind::Block { statements } => statements.iter().map(Statement::count).sum(),
+            StatementKind::Case { arms, .. } => arms.iter().map(|arm| arm.body.count()).sum(),
+            _ => 0,
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum StatementKind {
+    /// `v := e`
+    Assign { target: Variable, value: Expr },
+    /// `if e then s else s`, the else branch is optional
+    If {
+        condition: Expr,
+        then_branch: Box<Statement>,
+        else_branch: Option<Box<Statement>>,
+    },
+    /// `while e do s`
+    While {
+        condition: Expr,
+        body: Box<Statement>,
+    },
+    /// `begin s0; s1 end`
+    Block { statements: Vec<Statement> },
+    /// `case e of l0, l1: s0; l2: s1 end`
+    Case { selector: Expr, arms: Vec<CaseArm> },
+    /// `call p` or `call p(e0, e1)`
+    Call { procedure: String, args: Vec<Expr> },
+    /// `read(v0, v1)`, or `? v` in the extended dialect
+    Read { targets: Vec<Variable> },
+    /// `write(e0, e1)`, or `! e` in the extended dialect
+    Write { values: Vec<Expr> },
+    /// Nothing, e.g. the branch of `if c then ;`
+    Empty,
+}
+/// `l0, l1: s` of a case statement.
+/// Labels are parsed as expressions, the type checker requires them to be constant.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseArm {
+    pub labels: Vec<Expr>,
+    pub body: Statement,
+    pub span: Span,
+}
+/// Variable with its selectors, e.g. `r.f[i, j]`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Variable {
+    /// Lowercase name
+    pub name: String,
+    pub selectors: Vec<Selector>,
+    pub span: Span,
+}
+impl Variable {
+    /// Span of the name, without the selectors.
+    pub fn name_span(&self) -> Span {
+        Span::new(self.span.start, self.span.start + self.name.chars().count())
+    }
+}
+impl fmt::Display for Variable {
+    /// Source form, e.g. `r.f[i + 1, j]`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        for selector in &self.selectors {
+            write!(f, "{}", selector)?;
+        }
+        Ok(())
+    }
+}
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum Selector {
+    /// `.f`, lowercase field name, spanning the name
+    Field { name: String, span: Span },
+    /// `[e0, e1]`, spanning the brackets
+    Index { indices: Vec<Expr>, span: Span },
+}
+impl fmt::Display for Selector {
+    /// Source form, e.g. `.f` or `[i + 1, j]`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Selector::Field { name, .. } => write!(f, ".{}", name),
+            Selector::Index { indices, .. } => {
+                let indices: Vec<_> = indices.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", indices.join(", "))
+            }
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct Expr {
+    #[serde(flatten)]
+    pub kind: ExprKind,
+    pub span: Span,
+}
+impl Expr {
+    /// Precedence of the outermost operator, operands bind tighter than any operator.
+    fn precedence(&self) -> u8 {
+        match &self.kind {
+            ExprKind::Binary { op, .. } => op.precedence(),
+            ExprKind::Unary {
+                op: UnaryOp::Odd, ..
+            } => BinaryOp::Eq.precedence(),
+            ExprKind::Unary {
+                op: UnaryOp::Not, ..
+            } => BinaryOp::And.precedence(),
+            _ => u8::MAX,
+        }
+    }
+}
+impl fmt::Display for Expr {
+    /// Source form with the parentheses required by precedence, e.g. `(a + 1) * b`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operand = |e: &Expr, min: u8| {
+            if e.precedence() < min {
+                format!("({})", e)
+            } else {
+                e.to_string()
+            }
+        };
+        match &self.kind {
+            ExprKind::Number { value } => write!(f, "{}", value),
+            // Debug keeps the decimal point of `1.0`
+            ExprKind::Real { value } => write!(f, "{:?}", value),
+            ExprKind::Bool { value } => write!(f, "{}", value),
+            ExprKind::Char { value: '\'' } => write!(f, "''''"),
+            ExprKind::Char { value } => write!(f, "'{}'", value),
+            ExprKind::Variable { variable } => write!(f, "{}", variable),
+            ExprKind::Call { function, args } => {
+                let args: Vec<_> = args.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}({})", function, args.join(", "))
+            }
+            ExprKind::Unary { op, operand: e } => match op {
+                UnaryOp::Neg => write!(f, "-{}", operand(e, u8::MAX)),
+                UnaryOp::Odd => write!(f, "odd {}", operand(e, BinaryOp::Add.precedence())),
+                UnaryOp::Not => write!(f, "not {}", operand(e, BinaryOp::Eq.precedence())),
+            },
+            ExprKind::Binary { op, lhs, rhs } => {
+                let p = op.precedence();
+                write!(f, "{} {} {}", operand(lhs, p), op, operand(rhs, p + 1))
+            }
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum ExprKind {
+    Number {
+        value: i64,
+    },
+    Real {
+        value: f64,
+    },
+    /// `true` or `false`
+    Bool {
+        value: bool,
+    },
+    /// `'a'`
+    Char {
+        value: char,
+    },
+    Variable {
+        variable: Variable,
+    },
+    /// `f(e0, e1)`, a function without parameters is a `Variable`
+    Call {
+        function: String,
+        args: Vec<Expr>,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+}
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum UnaryOp {
+    /// `-e`
+    Neg,
+    /// `odd e`
+    Odd,
+    /// `not e`
+    Not,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Lt,
+    Gt,
+    Ne,
+    Ge,
+    Le,
+    Eq,
+    And,
+    Or,
+}
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Neg => "-",
+            Self::Odd => "odd",
+            Self::Not => "not",
+        })
+    }
+}
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Lt => "<",
+            Self::Gt => ">",
+            Self::Ne => "<>",
+            Self::Ge => ">=",
+            Self::Le => "<=",
+            Self::Eq => "==",
+            Self::And => "and",
+            Self::Or => "or",
+        })
+    }
+}
+impl BinaryOp {
+    pub fn from_token(token: TokenEnum) -> Option<Self> {
+        Some(match token {
+            TokenEnum::Add => Self::Add,
+            TokenEnum::Sub => Self::Sub,
+            TokenEnum::Mul => Self::Mul,
+            TokenEnum::Div => Self::Div,
+            TokenEnum::Lt => Self::Lt,
+            TokenEnum::Gt => Self::Gt,
+            TokenEnum::Ne => Self::Ne,
+            TokenEnum::Ge => Self::Ge,
+            TokenEnum::Le => Self::Le,
+            TokenEnum::Eq => Self::Eq,
+            TokenEnum::And => Self::And,
+            TokenEnum::Or => Self::Or,
+            _ => return None,
+        })
+    }
+    /// Binding power, higher binds tighter.
+    pub fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::Lt | Self::Gt | Self::Ne | Self::Ge | Self::Le | Self::Eq => 3,
+            Self::Add | Self::Sub => 4,
+            Self::Mul | Self::Div => 5,
+        }
+    }
+    pub fn is_relational(self) -> bool {
+        self.precedence() == 3
+    }
+}
@@ -0,0 +1,955 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use anyhow::{anyhow, Error};
+
+/// Stable code of a diagnostic, e.g. `E010`, printed with it and explained by `--explain`.
+/// Errors are numbered by group: lexical from E001, declarations from E010,
+/// syntax from E020, names from E030, types from E040 and grammar files from E090.
+/// Warnings are numbered from W001.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Code {
+    UnexpectedCharacter,
+    UnexpectedEq,
+    MalformedNumber,
+    LeadingDigit,
+    DigitSeparator,
+    MalformedCharLiteral,
+    UnterminatedComment,
+    ExtendedOnly,
+    LimitExceeded,
+    DuplicateIdentifier,
+    DuplicateType,
+    DuplicateEnumerator,
+    DuplicateField,
+    DuplicateParameter,
+    UnknownType,
+    EmptySubrange,
+    NumberOutOfRange,
+    InvalidIndexType,
+    UnexpectedToken,
+    MissingSemicolon,
+    MissingDelimiter,
+    GluedVar,
+    ChainedComparison,
+    UndeclaredIdentifier,
+    UndeclaredProcedure,
+    UnknownField,
+    InvalidSelector,
+    IndexOutOfBounds,
+    ConstantOutOfRange,
+    AssignToConstant,
+    InvalidCall,
+    AssignMismatch,
+    ConditionNotBool,
+    InvalidCaseSelector,
+    CaseLabelNotConstant,
+    CaseLabelMismatch,
+    DuplicateCaseLabel,
+    ArgumentCount,
+    ArgumentMismatch,
+    OperandMismatch,
+    InvalidReadWrite,
+    MalformedGrammar,
+    LeadingZeros,
+    DiscardedComparison,
+}
+impl Code {
+    pub const ALL: [Code; 44] = [
+        Code::UnexpectedCharacter,
+        Code::UnexpectedEq,
+        Code::MalformedNumber,
+        Code::LeadingDigit,
+        Code::DigitSeparator,
+        Code::MalformedCharLiteral,
+        Code::UnterminatedComment,
+        Code::ExtendedOnly,
+        Code::LimitExceeded,
+        Code::DuplicateIdentifier,
+        Code::DuplicateType,
+        Code::DuplicateEnumerator,
+        Code::DuplicateField,
+        Code::DuplicateParameter,
+        Code::UnknownType,
+        Code::EmptySubrange,
+        Code::NumberOutOfRange,
+        Code::InvalidIndexType,
+        Code::UnexpectedToken,
+        Code::MissingSemicolon,
+        Code::MissingDelimiter,
+        Code::GluedVar,
+        Code::ChainedComparison,
+        Code::UndeclaredIdentifier,
+        Code::UndeclaredProcedure,
+        Code::UnknownField,
+        Code::InvalidSelector,
+        Code::IndexOutOfBounds,
+        Code::ConstantOutOfRange,
+        Code::AssignToConstant,
+        Code::InvalidCall,
+        Code::AssignMismatch,
+        Code::ConditionNotBool,
+        Code::InvalidCaseSelector,
+        Code::CaseLabelNotConstant,
+        Code::CaseLabelMismatch,
+        Code::DuplicateCaseLabel,
+        Code::ArgumentCount,
+        Code::ArgumentMismatch,
+        Code::OperandMismatch,
+        Code::InvalidReadWrite,
+        Code::MalformedGrammar,
+        Code::LeadingZeros,
+        Code::DiscardedComparison,
+    ];
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Code::UnexpectedCharacter => "E001",
+            Code::UnexpectedEq => "E002",
+            Code::MalformedNumber => "E003",
+            Code::LeadingDigit => "E004",
+            Code::DigitSeparator => "E005",
+            Code::MalformedCharLiteral => "E006",
+            Code::UnterminatedComment => "E007",
+            Code::ExtendedOnly => "E008",
+            Code::LimitExceeded => "E009",
+            Code::DuplicateIdentifier => "E010",
+            Code::DuplicateType => "E011",
+            Code::DuplicateEnumerator => "E012",
+            Code::DuplicateField => "E013",
+            Code::DuplicateParameter => "E014",
+            Code::UnknownType => "E015",
+            Code::EmptySubrange => "E016",
+            Code::NumberOutOfRange => "E017",
+            Code::InvalidIndexType => "E018",
+            Code::UnexpectedToken => "E020",
+            Code::MissingSemicolon => "E021",
+            Code::MissingDelimiter => "E022",
+            Code::GluedVar => "E023",
+            Code::ChainedComparison => "E024",
+            Code::UndeclaredIdentifier => "E030",
+            Code::UndeclaredProcedure => "E031",
+            Code::UnknownField => "E032",
+            Code::InvalidSelector => "E033",
+            Code::IndexOutOfBounds => "E034",
+            Code::ConstantOutOfRange => "E035",
+            Code::AssignToConstant => "E036",
+            Code::InvalidCall => "E037",
+            Code::AssignMismatch => "E040",
+            Code::ConditionNotBool => "E041",
+            Code::InvalidCaseSelector => "E042",
+            Code::CaseLabelNotConstant => "E043",
+            Code::CaseLabelMismatch => "E044",
+            Code::DuplicateCaseLabel => "E045",
+            Code::ArgumentCount => "E046",
+            Code::ArgumentMismatch => "E047",
+            Code::OperandMismatch => "E048",
+            Code::InvalidReadWrite => "E049",
+            Code::MalformedGrammar => "E090",
+            Code::LeadingZeros => "W001",
+            Code::DiscardedComparison => "W002",
+        }
+    }
+    /// One line description, e.g. `Duplicate identifier`.
+    pub fn is_warning(self) -> bool {
+        self.as_str().starts_with('W')
+    }
+    pub fn summary(self) -> &'static str {
+        match self {
+            Code::UnexpectedCharacter => "Unexpected character",
+            Code::UnexpectedEq => "Unexpected `=`",
+            Code::MalformedNumber => "Malformed number",
+            Code::LeadingDigit => "Identifier starting with a digit",
+            Code::DigitSeparator => "Misplaced digit separator",
+            Code::MalformedCharLiteral => "Malformed character literal",
+            Code::UnterminatedComment => "Unterminated block comment",
+            Code::ExtendedOnly => "Extended dialect only",
+            Code::LimitExceeded => "Limit exceeded",
+            Code::DuplicateIdentifier => "Duplicate identifier",
+            Code::DuplicateType => "Duplicate type alias",
+            Code::DuplicateEnumerator => "Duplicate enumerator",
+            Code::DuplicateField => "Duplicate record field",
+            Code::DuplicateParameter => "Duplicate parameter",
+            Code::UnknownType => "Unknown type",
+            Code::EmptySubrange => "Empty subrange",
+            Code::NumberOutOfRange => "Number out of range",
+            Code::InvalidIndexType => "Invalid array index type",
+            Code::UnexpectedToken => "Unexpected token",
+            Code::MissingSemicolon => "Missing semicolon",
+            Code::MissingDelimiter => "Missing delimiter",
+            Code::GluedVar => "Missing space after `var`",
+            Code::ChainedComparison => "Chained comparison",
+            Code::UndeclaredIdentifier => "Undeclared identifier",
+            Code::UndeclaredProcedure => "Undeclared procedure",
+            Code::UnknownField => "Unknown record field",
+            Code::InvalidSelector => "Invalid selector",
+            Code::IndexOutOfBounds => "Index out of bounds",
+            Code::ConstantOutOfRange => "Constant out of range",
+            Code::AssignToConstant => "Assignment to a constant",
+            Code::InvalidCall => "Invalid call",
+            Code::AssignMismatch => "Mismatched types in assignment",
+            Code::ConditionNotBool => "Condition not of type Bool",
+            Code::InvalidCaseSelector => "Invalid case selector",
+            Code::CaseLabelNotConstant => "Case label not constant",
+            Code::CaseLabelMismatch => "Mismatched case label",
+            Code::DuplicateCaseLabel => "Duplicate case label",
+            Code::ArgumentCount => "Wrong number of arguments",
+            Code::ArgumentMismatch => "Mismatched argument type",
+            Code::OperandMismatch => "Mismatched operand type",
+            Code::InvalidReadWrite => "Unreadable or unwritable type",
+            Code::MalformedGrammar => "Malformed grammar file",
+            Code::LeadingZeros => "Number with leading zeros",
+            Code::DiscardedComparison => "Discarded comparison",
+        }
+    }
+    /// Longer description with an erroneous example and its correction.
+    pub fn explanation(self) -> &'static str {
+        match self {
+            Code::UnexpectedCharacter => {
+                "\
+A character that does not start any token was found.
+
+Erroneous code example:
+
+    x := a # b;
+
+Remove the character, or replace it with an operator of the language:
+
+    x := a + b;
+"
+            }
+            Code::UnexpectedEq => {
+                "\
+A single `=` was found outside of a constant or type declaration.
+Assignment is written `:=` and comparison `==`.
+
+Erroneous code example:
+
+    x = 1;
+
+Assign with `:=`, or compare with `==`:
+
+    x := 1;
+"
+            }
+            Code::MalformedNumber => {
+                "\
+A real literal has more than one decimal point, or no digits after it.
+
+Erroneous code example:
+
+    x := 1.2.3;
+    y := 1.;
+
+Write at most one decimal point, followed by digits:
+
+    x := 1.2;
+    y := 1.0;
+"
+            }
+            Code::LeadingDigit => {
+                "\
+An identifier starts with a digit, which makes it a number followed by letters.
+
+Erroneous code example:
+
+    var 2x: integer;
+
+Start the identifier with a letter:
+
+    var x2: integer;
+"
+            }
+            Code::DigitSeparator => {
+                "\
+A digit separator `_` starts or ends a number, or is doubled.
+Separators are only allowed between digits, with `--dialect extended`.
+
+Erroneous code example:
+
+    x := 1__000_;
+
+Put single separators between digits:
+
+    x := 1_000;
+"
+            }
+            Code::MalformedCharLiteral => {
+                "\
+A character literal is not closed on its line, is empty or holds more than one
+character. Strings are not supported, a quote is written twice.
+
+Erroneous code example:
+
+    c := 'ab';
+    d := '';
+
+Write exactly one character between the quotes:
+
+    c := 'a';
+    d := '''';
+"
+            }
+            Code::UnterminatedComment => {
+                "\
+A block comment `(* ... *)` or `{ ... }` is not closed before the end of input.
+Block comments do not nest.
+
+Erroneous code example:
+
+    (* the counter
+    var i: integer;
+
+Close the comment:
+
+    (* the counter *)
+    var i: integer;
+"
+            }
+            Code::ExtendedOnly => {
+                "\
+The `?` and `!` statements and the digit separator `_` are only part of the
+extended dialect.
+
+Erroneous code example:
+
+    ?x;
+    !x;
+
+Use `read` and `write`, or pass `--dialect extended`:
+
+    read(x);
+    write(x);
+"
+            }
+            Code::LimitExceeded => {
+                "\
+An identifier, a number, a line, the input or the number of tokens exceeds a
+limit set on the command line, e.g. `--max-identifier-length`.
+
+Shorten the input, or raise the limit.
+"
+            }
+            Code::DuplicateIdentifier => {
+                "\
+An identifier is declared twice. Identifiers ignore case, so `x` and `X` are the
+same identifier, and constants, types, variables, enumerators and procedures
+share one namespace.
+
+Erroneous code example:
+
+    var x: integer;
+        X: real;
+
+Rename one of the declarations:
+
+    var x: integer;
+        y: real;
+"
+            }
+            Code::DuplicateType => {
+                "\
+A type alias is declared twice.
+
+Erroneous code example:
+
+    type digit = 0..9;
+         digit = 0..7;
+
+Rename one of the aliases:
+
+    type digit = 0..9;
+         octal = 0..7;
+"
+            }
+            Code::DuplicateEnumerator => {
+                "\
+An enumerator appears twice in an enumeration, or already belongs to another one.
+
+Erroneous code example:
+
+    type color = (red, green, red);
+
+List each enumerator once:
+
+    type color = (red, green, blue);
+"
+            }
+            Code::DuplicateField => {
+                "\
+A record declares a field twice.
+
+Erroneous code example:
+
+    var p: record x, y, x: integer end;
+
+Rename one of the fields:
+
+    var p: record x, y, z: integer end;
+"
+            }
+            Code::DuplicateParameter => {
+                "\
+A procedure or function declares a parameter twice.
+
+Erroneous code example:
+
+    procedure move(x, y, x: integer);
+
+Rename one of the parameters:
+
+    procedure move(x, y, z: integer);
+"
+            }
+            Code::UnknownType => {
+                "\
+A type name is neither a built-in type nor a declared type alias.
+
+Erroneous code example:
+
+    var s: string;
+
+Use a built-in type such as `integer`, `longint`, `real`, `bool` or `char`,
+or declare the alias in a type block first:
+
+    type digit = 0..9;
+    var d: digit;
+"
+            }
+            Code::EmptySubrange => {
+                "\
+The lower bound of a subrange is greater than its upper bound.
+
+Erroneous code example:
+
+    type digit = 9..0;
+
+Write the lower bound first:
+
+    type digit = 0..9;
+"
+            }
+            Code::NumberOutOfRange => {
+                "\
+An integer literal does not fit in 64 bits.
+
+Erroneous code example:
+
+    x := 99999999999999999999;
+
+Use a smaller number:
+
+    x := 999999999;
+"
+            }
+            Code::InvalidIndexType => {
+                "\
+An array index type is not a subrange.
+
+Erroneous code example:
+
+    var a: array [integer] of integer;
+
+Give the bounds of the index:
+
+    var a: array [1..10] of integer;
+"
+            }
+            Code::UnexpectedToken => {
+                "\
+A token does not fit the grammar where it appears, or the input ends too early.
+
+Erroneous code example:
+
+    var x integer;
+
+Insert or remove tokens as the message suggests:
+
+    var x: integer;
+"
+            }
+            Code::MissingSemicolon => {
+                "\
+Declarations and statements are separated by `;`.
+
+Erroneous code example:
+
+    x := 1
+    y := 2
+
+Separate them with a semicolon:
+
+    x := 1;
+    y := 2
+"
+            }
+            Code::MissingDelimiter => {
+                "\
+A `,` between identifiers or a closing `]` of the indices is missing.
+
+Erroneous code example:
+
+    var i j: integer;
+
+Insert the delimiter:
+
+    var i, j: integer;
+"
+            }
+            Code::GluedVar => {
+                "\
+The `var` keyword is glued to the first identifier of the var block, which reads
+as a single identifier.
+
+Erroneous code example:
+
+    vari: integer;
+
+Separate them with a space:
+
+    var i: integer;
+"
+            }
+            Code::ChainedComparison => {
+                "\
+Comparison operators are not associative, so `a < b < c` has no meaning.
+
+Erroneous code example:
+
+    if 0 < x < 10 then x := 0;
+
+Combine the comparisons with `and`:
+
+    if (0 < x) and (x < 10) then x := 0;
+"
+            }
+            Code::UndeclaredIdentifier => {
+                "\
+An identifier is used without being declared.
+
+Erroneous code example:
+
+    var x: integer;
+    begin
+        y := 1
+    end
+
+Declare the variable in the var block:
+
+    var x, y: integer;
+"
+            }
+            Code::UndeclaredProcedure => {
+                "\
+A procedure is called without being declared.
+
+Erroneous code example:
+
+    begin
+        call reset
+    end
+
+Declare the procedure before the program block:
+
+    procedure reset;
+    begin
+    end;
+"
+            }
+            Code::UnknownField => {
+                "\
+A record has no field of the selected name.
+
+Erroneous code example:
+
+    var p: record x, y: integer end;
+    begin
+        p.z := 0
+    end
+
+Select one of the declared fields:
+
+    p.x := 0
+"
+            }
+            Code::InvalidSelector => {
+                "\
+A field is selected on a value that is not a record, an element on a value
+that is not an array, or an element by an index that is not an integer.
+
+Erroneous code example:
+
+    var n: integer;
+    begin
+        n[1] := 0
+    end
+
+Only select fields of records, and elements of arrays by integers.
+"
+            }
+            Code::IndexOutOfBounds => {
+                "\
+A constant index is outside the bounds of the array, or the number of indices
+differs from its number of dimensions.
+
+Erroneous code example:
+
+    var a: array [1..3] of integer;
+    begin
+        a[4] := 0
+    end
+
+Use an index within the bounds:
+
+    a[3] := 0
+"
+            }
+            Code::ConstantOutOfRange => {
+                "\
+A constant assigned to a variable of a subrange type is outside the subrange.
+
+Erroneous code example:
+
+    type digit = 0..9;
+    var d: digit;
+    begin
+        d := 10
+    end
+
+Assign a value within the subrange.
+"
+            }
+            Code::AssignToConstant => {
+                "\
+A constant, an enumerator or a type is assigned to or read into.
+
+Erroneous code example:
+
+    const limit = 10;
+    begin
+        limit := 20
+    end
+
+Declare a variable instead:
+
+    var limit: integer;
+"
+            }
+            Code::InvalidCall => {
+                "\
+A function is called as a procedure, or a procedure or variable is used as a
+function.
+
+Erroneous code example:
+
+    procedure reset;
+    begin
+    end;
+    begin
+        x := reset
+    end
+
+Call procedures with `call`, and use the results of functions in expressions:
+
+    call reset
+"
+            }
+            Code::AssignMismatch => {
+                "\
+The value assigned is not of the type of the target.
+Integers widen to `longint` and `real`, but a `longint` does not narrow to
+`integer` or a subrange. The constants 0 and 1 can be assigned to `bool` as
+`false` and `true`, as in the reference program of the lab assignment.
+
+Erroneous code example:
+
+    var n: integer;
+    begin
+        n := 'a'
+    end
+
+Assign a value of the type of the target:
+
+    n := 1
+"
+            }
+            Code::ConditionNotBool => {
+                "\
+The condition of `if` or `while` is not of type `bool`.
+
+Erroneous code example:
+
+    var x: real;
+    begin
+        while x do x := 0
+    end
+
+Compare the value instead:
+
+    while x > 0 do x := 0
+"
+            }
+            Code::InvalidCaseSelector => {
+                "\
+The selector of `case` is not an integer, a `char` or an enumeration.
+
+Erroneous code example:
+
+    var x: real;
+    begin
+        case x of
+            1: x := 0
+        end
+    end
+
+Select on an integer, a character or an enumeration.
+"
+            }
+            Code::CaseLabelNotConstant => {
+                "\
+A case label is not an integer literal, a constant or an enumerator.
+
+Erroneous code example:
+
+    case n of
+        m: n := 0
+    end
+
+Use a constant value as the label:
+
+    case n of
+        1: n := 0
+    end
+"
+            }
+            Code::CaseLabelMismatch => {
+                "\
+A case label is not of the type of the selector.
+
+Erroneous code example:
+
+    type color = (red, green);
+    var c: color;
+    begin
+        case c of
+            1: c := red
+        end
+    end
+
+Use labels of the selector type:
+
+    case c of
+        green: c := red
+    end
+"
+            }
+            Code::DuplicateCaseLabel => {
+                "\
+A value is handled by more than one arm of `case`.
+
+Erroneous code example:
+
+    case n of
+        1, 2: n := 0;
+        2: n := 1
+    end
+
+Handle each value in one arm only:
+
+    case n of
+        1, 2: n := 0;
+        3: n := 1
+    end
+"
+            }
+            Code::ArgumentCount => {
+                "\
+A procedure or function is called with a number of arguments different from its
+number of parameters.
+
+Erroneous code example:
+
+    procedure add(x: integer);
+    begin
+    end;
+    begin
+        call add(1, 2)
+    end
+
+Pass one argument per parameter:
+
+    call add(1)
+"
+            }
+            Code::ArgumentMismatch => {
+                "\
+An argument is not of the type of its parameter.
+
+Erroneous code example:
+
+    procedure add(x: integer);
+    begin
+    end;
+    begin
+        call add('a')
+    end
+
+Pass an argument of the parameter type:
+
+    call add(1)
+"
+            }
+            Code::OperandMismatch => {
+                "\
+An operand is not of a type the operator accepts, e.g. a character in an
+arithmetic expression, or two values of unrelated types are compared.
+
+Erroneous code example:
+
+    var n: integer;
+        c: char;
+    begin
+        n := n + c
+    end
+
+Use operands of matching types:
+
+    n := n + 1
+"
+            }
+            Code::InvalidReadWrite => {
+                "\
+Records, arrays and enumerations cannot be read or written as a whole, and only
+variables of numeric or character types can be read into.
+
+Erroneous code example:
+
+    var p: record x, y: integer end;
+    begin
+        read(p)
+    end
+
+Read and write the fields one by one:
+
+    read(p.x);
+    read(p.y)
+"
+            }
+            Code::MalformedGrammar => {
+                "\
+A line of a grammar file is not a production `A -> α | β`, or a continuation
+`| γ` of the previous one, or the file has no production.
+
+Erroneous code example:
+
+    S A -> a
+
+Write one symbol on the left-hand side:
+
+    S -> A a
+"
+            }
+            Code::LeadingZeros => {
+                "\
+A number has leading zeros, which are ignored. In other languages they would
+make it octal.
+
+Erroneous code example:
+
+    x := 010;
+
+Remove the leading zeros:
+
+    x := 10;
+"
+            }
+            Code::DiscardedComparison => {
+                "\
+A statement compares a variable with `==` and discards the result, which is
+likely a mistyped assignment.
+
+Erroneous code example:
+
+    x == 1;
+
+Assign with `:=`:
+
+    x := 1;
+"
+            }
+        }
+    }
+}
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+impl FromStr for Code {
+    type Err = Error;
+    /// Parse a code like `E010`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Code::ALL
+            .into_iter()
+            .find(|code| code.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| anyhow!("Unknown diagnostic code: {}", s))
+    }
+}
+/// Warnings selected by `--warn`, `--allow` and `--deny`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    /// All warnings.
+    Warnings,
+    Code(Code),
+}
+impl FromStr for Lint {
+    type Err = Error;
+    /// Parse `warnings` or a warning code like `W001`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("warnings") {
+            return Ok(Lint::Warnings);
+        }
+        let code: Code = s.parse()?;
+        if !code.is_warning() {
+            return Err(anyhow!(
+                "{} is an error, only warnings can be selected",
+                code
+            ));
+        }
+        Ok(Lint::Code(code))
+    }
+}
+/// What to do with a warning, ordered from the least to the most strict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LintLevel {
+    /// Drop the warning.
+    Allow,
+    #[default]
+    Warn,
+    /// Report the warning as an error.
+    Deny,
+}
+/// Level of each warning. A code selected by itself overrides `warnings`,
+/// a lint selected at several levels gets the strictest one.
+#[derive(Debug, Clone, Default)]
+pub struct WarningLevels {
+    /// Level of all warnings, None if `warnings` is not selected.
+    all: Option<LintLevel>,
+    codes: BTreeMap<Code, LintLevel>,
+}
+impl WarningLevels {
+    pub fn set(&mut self, lint: Lint, level: LintLevel) {
+        match lint {
+            Lint::Warnings => self.all = self.all.max(Some(level)),
+            Lint::Code(code) => {
+                let current = self.codes.entry(code).or_insert(level);
+                *current = (*current).max(level);
+            }
+        }
+    }
+    pub fn level(&self, code: Code) -> LintLevel {
+        self.codes
+            .get(&code)
+            .copied()
+            .or(self.all)
+            .unwrap_or_default()
+    }
+}
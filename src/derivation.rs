@@ -0,0 +1,162 @@
+use crate::{
+    grammar::{Grammar, Rule},
+    lex::TokenEnum,
+};
+
+/// Productions of the var block grammar (see `docs/var-block.typ`).
+/// ```plaintext
+/// S  -> Var D | ε
+/// D  -> D1 D'
+/// D' -> D1 D' | ε
+/// D1 -> I : t ;
+/// I  -> i I'
+/// I' -> , i I' | ε
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Production {
+    /// S -> Var D
+    VarBlock,
+    /// S -> ε
+    EmptyVarBlock,
+    /// D -> D1 D'
+    DefList,
+    /// D' -> D1 D'
+    DefListMore,
+    /// D' -> ε
+    DefListEnd,
+    /// D1 -> I : t ;
+    DefLine,
+    /// I -> i I'
+    IdentList,
+    /// I' -> , i I'
+    IdentListMore,
+    /// I' -> ε
+    IdentListEnd,
+}
+pub const START_SYMBOL: &str = "S";
+impl Production {
+    pub const ALL: [Production; 9] = [
+        Production::VarBlock,
+        Production::EmptyVarBlock,
+        Production::DefList,
+        Production::DefListMore,
+        Production::DefListEnd,
+        Production::DefLine,
+        Production::IdentList,
+        Production::IdentListMore,
+        Production::IdentListEnd,
+    ];
+}
+/// The var block grammar, rule `i` corresponds to `Production::ALL[i]`.
+pub fn var_block_grammar() -> Grammar {
+    Grammar::from_rules(
+        Production::ALL
+            .iter()
+            .map(|p| Rule {
+                lhs: p.lhs().to_string(),
+                rhs: p.rhs().iter().map(|s| s.to_string()).collect(),
+            })
+            .collect(),
+    )
+}
+/// Grammar terminal of the token, or the token name if it is not part of the var block.
+pub fn terminal_of(token: TokenEnum) -> String {
+    match token {
+        TokenEnum::Var => "Var",
+        TokenEnum::Identifier => "i",
+        TokenEnum::Colon => ":",
+        TokenEnum::SemiColon => ";",
+        TokenEnum::Comma => ",",
+        TokenEnum::Integer
+        | TokenEnum::Longint
+        | TokenEnum::Bool
+        | TokenEnum::Real
+        | TokenEnum::Char
+        | TokenEnum::Record
+        | TokenEnum::Array => "t",
+        _ => return format!("{:?}", token),
+    }
+    .to_string()
+}
+/// Human readable terminal for diagnostics.
+pub fn describe_terminal(terminal: &str) -> String {
+    match terminal {
+        "i" => "identifier".to_string(),
+        "t" => "type".to_string(),
+        _ => format!("`{}`", terminal),
+    }
+}
+pub fn is_nonterminal(symbol: &str) -> bool {
+    matches!(symbol, "S" | "D" | "D'" | "D1" | "I" | "I'")
+}
+impl Production {
+    pub fn lhs(self) -> &'static str {
+        match self {
+            Production::VarBlock | Production::EmptyVarBlock => "S",
+            Production::DefList => "D",
+            Production::DefListMore | Production::DefListEnd => "D'",
+            Production::DefLine => "D1",
+            Production::IdentList => "I",
+            Production::IdentListMore | Production::IdentListEnd => "I'",
+        }
+    }
+    /// Right-hand side symbols, empty for ε.
+    pub fn rhs(self) -> &'static [&'static str] {
+        match self {
+            Production::VarBlock => &["Var", "D"],
+            Production::DefList | Production::DefListMore => &["D1", "D'"],
+            Production::DefLine => &["I", ":", "t", ";"],
+            Production::IdentList => &["i", "I'"],
+            Production::IdentListMore => &[",", "i", "I'"],
+            Production::EmptyVarBlock | Production::DefListEnd | Production::IdentListEnd => &[],
+        }
+    }
+    /// Display the production, e.g. `I' -> , i I'`.
+    pub fn display(self) -> String {
+        format!("{} -> {}", self.lhs(), symbols_to_string(self.rhs()))
+    }
+}
+fn symbols_to_string(symbols: &[&str]) -> String {
+    if symbols.is_empty() {
+        "ε".to_string()
+    } else {
+        symbols.join(" ")
+    }
+}
+
+/// Render the leftmost derivation as numbered steps.
+/// ```plaintext
+///       S
+///    1  => Var D          S -> Var D
+///    2  => Var D1 D'      D -> D1 D'
+/// ```
+/// Each production is applied to the leftmost nonterminal of the sentential form.
+/// The trace of a failed parse yields an incomplete derivation.
+pub fn to_derivation(trace: &[Production]) -> String {
+    let mut form = vec![START_SYMBOL];
+    let mut steps = Vec::new();
+    for production in trace {
+        let Some(pos) = form.iter().position(|s| is_nonterminal(s)) else {
+            break;
+        };
+        form.splice(pos..pos + 1, production.rhs().iter().copied());
+        steps.push((symbols_to_string(&form), production.display()));
+    }
+    let width = steps
+        .iter()
+        .map(|(f, _)| f.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut result = format!("      {}\n", START_SYMBOL);
+    for (i, (form, production)) in steps.iter().enumerate() {
+        let padding = " ".repeat(width - form.chars().count());
+        result.push_str(&format!(
+            "{:>4}  => {}{}    {}\n",
+            i + 1,
+            form,
+            padding,
+            production
+        ));
+    }
+    result
+}
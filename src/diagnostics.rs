@@ -0,0 +1,391 @@
+use std::collections::BTreeSet;
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    error::{ErrorRecorder, OffsetError},
+    html::escape,
+    source_map::{FileId, SourceMap, Span},
+};
+
+/// Format of the diagnostics printed to stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum DiagnosticsFormat {
+    /// Source line with a caret under the offending position, and a summary line
+    #[default]
+    Text,
+    /// JSON array of the diagnostics of all files
+    Json,
+    /// SARIF 2.1.0 log, e.g. for GitHub code scanning
+    Sarif,
+    /// GCC-style `file:line:col: error[E010]: message` lines, without the source
+    Short,
+    /// GitHub Actions `::error file=...,line=...,col=...,title=E010::message` workflow commands,
+    /// shown as annotations of the pull request
+    Github,
+    /// Vim quickfix `file:line:col:type:message` lines, type `E`, `W` or `I` for notes,
+    /// read by `:set errorformat=%f:%l:%c:%t:%m`
+    Quickfix,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiagnosticNote {
+    /// `note` or `help`
+    pub level: String,
+    pub message: String,
+}
+/// Secondary span of a diagnostic, in the file of the diagnostic.
+#[derive(Debug, Serialize)]
+pub struct DiagnosticLabel {
+    pub message: String,
+    pub offset: usize,
+    /// 1-based
+    pub line: usize,
+    /// 1-based
+    pub col: usize,
+    pub length: usize,
+}
+/// Diagnostic of the machine-readable formats, positions in characters.
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    pub file: String,
+    /// `error` or `warning`
+    pub severity: String,
+    /// Stable code, e.g. `E010`, explained by `--explain`
+    pub code: String,
+    pub message: String,
+    pub offset: usize,
+    /// 1-based
+    pub line: usize,
+    /// 1-based
+    pub col: usize,
+    /// Number of characters covered, 0 for a position
+    pub length: usize,
+    /// Notes and helps, the replacement of a fix as a help
+    pub notes: Vec<DiagnosticNote>,
+    /// Secondary spans, e.g. the original declaration of a duplicate
+    pub labels: Vec<DiagnosticLabel>,
+}
+impl Diagnostic {
+    /// Spans at EOF are clamped to the end of input.
+    pub fn new(level: &str, sources: &SourceMap, e: &OffsetError) -> Self {
+        let span = e.span.clamp_to(sources, e.file);
+        let (line, col) = span.to_line_col(sources, e.file);
+        let fix = e.fix.as_ref().map(|fix| DiagnosticNote {
+            level: "help".to_string(),
+            message: format!("replace with `{}`", fix.replacement),
+        });
+        Self {
+            file: sources.name(e.file).to_string(),
+            severity: level.to_lowercase(),
+            code: e.code.to_string(),
+            message: e.msg.clone(),
+            offset: span.start,
+            line,
+            col,
+            length: span.end.saturating_sub(span.start),
+            notes: e
+                .notes
+                .iter()
+                .map(|note| DiagnosticNote {
+                    level: note.level.as_str().to_lowercase(),
+                    message: note.msg.clone(),
+                })
+                .chain(fix)
+                .collect(),
+            labels: e
+                .labels
+                .iter()
+                .map(|label| {
+                    let span = label.span.clamp_to(sources, e.file);
+                    let (line, col) = span.to_line_col(sources, e.file);
+                    DiagnosticLabel {
+                        message: label.msg.clone(),
+                        offset: span.start,
+                        line,
+                        col,
+                        length: span.end.saturating_sub(span.start),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// SARIF physical location of the span, clamped to the end of input.
+fn sarif_location(sources: &SourceMap, file: FileId, span: Span) -> Value {
+    let span = span.clamp_to(sources, file);
+    let (start_line, start_col) = sources.line_col(file, span.start);
+    let (end_line, end_col) = sources.line_col(file, span.end);
+    json!({
+        "artifactLocation": { "uri": sources.name(file).replace('\\', "/") },
+        "region": {
+            "startLine": start_line,
+            "startColumn": start_col,
+            "endLine": end_line,
+            "endColumn": end_col,
+            "charOffset": span.start,
+            "charLength": span.end - span.start,
+        },
+    })
+}
+/// SARIF result of the diagnostic, with its notes appended to the message,
+/// its labels as related locations and its fix as a replacement.
+fn sarif_result(level: &str, sources: &SourceMap, e: &OffsetError) -> Value {
+    let uri = sources.name(e.file).replace('\\', "/");
+    let mut message = e.msg.clone();
+    for note in &e.notes {
+        message.push_str(&format!("\n{}: {}", note.level.as_str(), note.msg));
+    }
+    let mut result = json!({
+        "ruleId": e.code.as_str(),
+        "level": level.to_lowercase(),
+        "message": { "text": message },
+        "locations": [{
+            "physicalLocation": sarif_location(sources, e.file, e.span),
+        }],
+    });
+    if !e.labels.is_empty() {
+        let related: Vec<_> = e
+            .labels
+            .iter()
+            .enumerate()
+            .map(|(id, label)| {
+                json!({
+                    "id": id,
+                    "message": { "text": label.msg },
+                    "physicalLocation": sarif_location(sources, e.file, label.span),
+                })
+            })
+            .collect();
+        result["relatedLocations"] = json!(related);
+    }
+    if let Some(fix) = &e.fix {
+        result["fixes"] = json!([{
+            "description": { "text": format!("Replace with `{}`", fix.replacement) },
+            "artifactChanges": [{
+                "artifactLocation": { "uri": uri },
+                "replacements": [{
+                    "deletedRegion": {
+                        "charOffset": fix.span.start,
+                        "charLength": fix.span.end - fix.span.start,
+                    },
+                    "insertedContent": { "text": fix.replacement },
+                }],
+            }],
+        }]);
+    }
+    result
+}
+/// SARIF 2.1.0 log with a single run, columns counted in characters.
+/// The codes of the diagnostics are the rules of the tool.
+fn to_sarif(sources: &SourceMap, diagnostics: &[(&str, &OffsetError)]) -> Value {
+    let results: Vec<_> = diagnostics
+        .iter()
+        .map(|(level, e)| sarif_result(level, sources, e))
+        .collect();
+    let codes: BTreeSet<_> = diagnostics.iter().map(|(_, e)| e.code).collect();
+    let rules: Vec<_> = codes
+        .into_iter()
+        .map(|code| {
+            json!({
+                "id": code.as_str(),
+                "shortDescription": { "text": code.summary() },
+                "fullDescription": { "text": code.explanation() },
+            })
+        })
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    })
+}
+
+/// One line per diagnostic and per note, `file:line:col: level[code]: message`.
+/// Labels are notes at their own location.
+fn to_short(sources: &SourceMap, diagnostics: &[(&str, &OffsetError)]) -> String {
+    let mut output = String::new();
+    for d in diagnostics
+        .iter()
+        .map(|(level, e)| Diagnostic::new(level, sources, e))
+    {
+        let location = format!("{}:{}:{}", d.file, d.line, d.col);
+        output.push_str(&format!(
+            "{}: {}[{}]: {}\n",
+            location, d.severity, d.code, d.message
+        ));
+        for label in &d.labels {
+            output.push_str(&format!(
+                "{}:{}:{}: note: {}\n",
+                d.file, label.line, label.col, label.message
+            ));
+        }
+        for note in &d.notes {
+            output.push_str(&format!("{}: {}: {}\n", location, note.level, note.message));
+        }
+    }
+    output
+}
+
+/// Escape data of a workflow command, properties also escape `:` and `,`.
+fn escape_command(text: &str, property: bool) -> String {
+    let text = text
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A");
+    if property {
+        text.replace(':', "%3A").replace(',', "%2C")
+    } else {
+        text
+    }
+}
+/// One GitHub Actions workflow command per diagnostic, titled by its code, with the
+/// labels and notes on further lines of the message.
+fn to_github(sources: &SourceMap, diagnostics: &[(&str, &OffsetError)]) -> String {
+    let mut output = String::new();
+    for d in diagnostics
+        .iter()
+        .map(|(level, e)| Diagnostic::new(level, sources, e))
+    {
+        let mut message = d.message.clone();
+        for label in &d.labels {
+            message.push_str(&format!(
+                "\nnote: {} at line {}, col {}",
+                label.message, label.line, label.col
+            ));
+        }
+        for note in &d.notes {
+            message.push_str(&format!("\n{}: {}", note.level, note.message));
+        }
+        output.push_str(&format!(
+            "::{} file={},line={},col={},title={}::{}\n",
+            d.severity,
+            escape_command(&d.file, true),
+            d.line,
+            d.col,
+            d.code,
+            escape_command(&message, false)
+        ));
+    }
+    output
+}
+
+/// Lines for the Vim quickfix list, `file:line:col:type:message`, the message of
+/// a diagnostic starting with its code, e.g. `E010: ...`.
+/// The type is `E` for errors, `W` for warnings and `I` for their labels and notes.
+fn to_quickfix(sources: &SourceMap, diagnostics: &[(&str, &OffsetError)]) -> String {
+    let mut output = String::new();
+    for d in diagnostics
+        .iter()
+        .map(|(level, e)| Diagnostic::new(level, sources, e))
+    {
+        let location = format!("{}:{}:{}", d.file, d.line, d.col);
+        let kind = if d.severity == "error" { 'E' } else { 'W' };
+        output.push_str(&format!(
+            "{}:{}:{}: {}\n",
+            location, kind, d.code, d.message
+        ));
+        for label in &d.labels {
+            output.push_str(&format!(
+                "{}:{}:{}:I:note: {}\n",
+                d.file, label.line, label.col, label.message
+            ));
+        }
+        for note in &d.notes {
+            output.push_str(&format!(
+                "{}:I:{}: {}\n",
+                location, note.level, note.message
+            ));
+        }
+    }
+    output
+}
+
+/// Render the diagnostics, as (level, error) sorted by file and offset, in a
+/// machine-readable format, ending with a line break.
+/// None for `Text`, which `ErrorRecorder` renders by file.
+pub fn render(
+    format: DiagnosticsFormat,
+    sources: &SourceMap,
+    diagnostics: &[(&str, &OffsetError)],
+) -> Option<String> {
+    let value = match format {
+        DiagnosticsFormat::Text => return None,
+        DiagnosticsFormat::Json => {
+            let diagnostics: Vec<_> = diagnostics
+                .iter()
+                .map(|(level, e)| Diagnostic::new(level, sources, e))
+                .collect();
+            json!(diagnostics)
+        }
+        DiagnosticsFormat::Sarif => to_sarif(sources, diagnostics),
+        DiagnosticsFormat::Short => return Some(to_short(sources, diagnostics)),
+        DiagnosticsFormat::Github => return Some(to_github(sources, diagnostics)),
+        DiagnosticsFormat::Quickfix => return Some(to_quickfix(sources, diagnostics)),
+    };
+    let output = serde_json::to_string_pretty(&value).expect("diagnostics serialize to JSON");
+    Some(output + "\n")
+}
+
+/// JUnit XML report with a test case per file, failing if the file has errors.
+/// ```plaintext
+/// <testsuites tests="2" failures="1">
+///   <testsuite name="compilation-exp1" tests="2" failures="1">
+///     <testcase name="a.in" classname="compilation-exp1"/>
+///     <testcase name="b.in" classname="compilation-exp1">
+///       <failure message="1 error(s), 0 warning(s)" type="error">b.in:1:4: error: ...</failure>
+///     </testcase>
+///   </testsuite>
+/// </testsuites>
+/// ```
+/// The diagnostics are listed in the short format, those of a passing file in its output.
+pub fn to_junit(sources: &SourceMap, files: &[(&str, &ErrorRecorder)]) -> String {
+    let name = env!("CARGO_PKG_NAME");
+    let tests = files.len();
+    let failures = files.iter().filter(|(_, e)| !e.no_error()).count();
+    let mut output = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <testsuites tests=\"{}\" failures=\"{}\">\n  \
+        <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+        tests, failures, name, tests, failures
+    );
+    for (file, errors) in files {
+        let testcase = format!(
+            "    <testcase name=\"{}\" classname=\"{}\"",
+            escape(file),
+            name
+        );
+        let diagnostics = escape(&to_short(sources, &errors.sorted()));
+        if !errors.no_error() {
+            output.push_str(&format!(
+                "{}>\n      <failure message=\"{} error(s), {} warning(s)\" type=\"error\">{}</failure>\n    </testcase>\n",
+                testcase,
+                errors.error_count(),
+                errors.warning_count(),
+                diagnostics
+            ));
+        } else if !diagnostics.is_empty() {
+            output.push_str(&format!(
+                "{}>\n      <system-out>{}</system-out>\n    </testcase>\n",
+                testcase, diagnostics
+            ));
+        } else {
+            output.push_str(&format!("{}/>\n", testcase));
+        }
+    }
+    output.push_str("  </testsuite>\n</testsuites>\n");
+    output
+}
@@ -0,0 +1,86 @@
+use crate::lex::Token;
+
+/// ANSI color of the token category.
+fn category_color(category: &str) -> &'static str {
+    match category {
+        "keyword" => "34",
+        "literal" => "32",
+        "identifier" => "36",
+        _ => "33",
+    }
+}
+/// Text padded to the width, in the ANSI style if `color`.
+fn cell(text: &str, width: usize, style: &str, color: bool) -> String {
+    let padded = format!("{:<width$}", text, width = width);
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", style, padded)
+    } else {
+        padded
+    }
+}
+
+/// Content with control characters escaped, so that each token stays on its row.
+fn lexeme(content: &str) -> String {
+    content
+        .chars()
+        .map(|c| {
+            if c.is_control() {
+                c.escape_default().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Render the tokens as a table for reading on the terminal.
+/// ```plaintext
+/// index  line:col  kind        lexeme
+/// 0      1:1       Var         Var
+/// 1      2:5       Identifier  i
+/// ```
+/// With `color`, the kind is colored by its category, the index and position are dimmed.
+pub fn to_token_table(tokens: &[Token], color: bool) -> String {
+    let rows: Vec<_> = tokens
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            (
+                i.to_string(),
+                format!("{}:{}", t.line, t.col),
+                format!("{:?}", t.token),
+                t,
+            )
+        })
+        .collect();
+    let index_width = rows
+        .iter()
+        .map(|r| r.0.len())
+        .fold("index".len(), usize::max);
+    let pos_width = rows
+        .iter()
+        .map(|r| r.1.len())
+        .fold("line:col".len(), usize::max);
+    let kind_width = rows
+        .iter()
+        .map(|r| r.2.len())
+        .fold("kind".len(), usize::max);
+    let mut result = format!(
+        "{}  {}  {}  {}\n",
+        cell("index", index_width, "1", color),
+        cell("line:col", pos_width, "1", color),
+        cell("kind", kind_width, "1", color),
+        cell("lexeme", 0, "1", color),
+    );
+    for (index, pos, kind, token) in &rows {
+        let style = category_color(token.token.category());
+        result.push_str(&format!(
+            "{}  {}  {}  {}\n",
+            cell(index, index_width, "2", color),
+            cell(pos, pos_width, "2", color),
+            cell(kind, kind_width, style, color),
+            lexeme(&token.content),
+        ));
+    }
+    result
+}
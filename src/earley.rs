@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+
+use crate::grammar::Grammar;
+
+/// Earley item `lhs -> α • β, origin`.
+/// `children` holds the completed item (set, index) for each nonterminal before the dot,
+/// `None` for terminals.
+#[derive(Debug, Clone)]
+struct Item {
+    rule: usize,
+    dot: usize,
+    origin: usize,
+    children: Vec<Option<(usize, usize)>>,
+}
+
+/// Local repair applied when no item can scan the next terminal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Repair {
+    /// The terminal was inserted before the token at the index.
+    Insert(usize, String),
+    /// The token at the index was skipped, with the terminals expected there.
+    Skip(usize, Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct EarleyResult {
+    /// Rule indices in leftmost derivation order.
+    /// If the input could not be completed, this is the derivation of the
+    /// most advanced partial parse, with the remaining nonterminals unexpanded.
+    pub trace: Vec<usize>,
+    /// Whether the start symbol was completed over the whole (repaired) input.
+    pub complete: bool,
+    pub repairs: Vec<Repair>,
+    /// Terminals expected at the end of the input, if not complete.
+    pub expected_at_end: Vec<String>,
+}
+
+/// Error-tolerant Earley parser.
+/// When the next terminal cannot be scanned, a single expected terminal is inserted
+/// if that lets the terminal be scanned, otherwise the terminal is skipped.
+pub struct Earley<'a> {
+    grammar: &'a Grammar,
+    sets: Vec<Vec<Item>>,
+    keys: Vec<HashSet<(usize, usize, usize)>>,
+}
+impl<'a> Earley<'a> {
+    pub fn new(grammar: &'a Grammar) -> Self {
+        Self {
+            grammar,
+            sets: Vec::new(),
+            keys: Vec::new(),
+        }
+    }
+    fn next_symbol(&self, item: &Item) -> Option<&'a str> {
+        self.grammar.rules[item.rule]
+            .rhs
+            .get(item.dot)
+            .map(|s| s.as_str())
+    }
+    fn add(&mut self, set: usize, item: Item) {
+        if self.keys[set].insert((item.rule, item.dot, item.origin)) {
+            self.sets[set].push(item);
+        }
+    }
+    fn advance(item: &Item, child: Option<(usize, usize)>) -> Item {
+        let mut children = item.children.clone();
+        children.push(child);
+        Item {
+            rule: item.rule,
+            dot: item.dot + 1,
+            origin: item.origin,
+            children,
+        }
+    }
+    /// Start a new set with the given kernel items, then predict and complete.
+    fn push_set(&mut self, kernel: Vec<Item>) {
+        let set = self.sets.len();
+        self.sets.push(Vec::new());
+        self.keys.push(HashSet::new());
+        for item in kernel {
+            self.add(set, item);
+        }
+        let mut i = 0;
+        while i < self.sets[set].len() {
+            let item = self.sets[set][i].clone();
+            match self.next_symbol(&item) {
+                None => {
+                    let lhs = &self.grammar.rules[item.rule].lhs;
+                    let parents: Vec<_> = self.sets[item.origin]
+                        .iter()
+                        .filter(|p| self.next_symbol(p) == Some(lhs))
+                        .map(|p| Self::advance(p, Some((set, i))))
+                        .collect();
+                    for parent in parents {
+                        self.add(set, parent);
+                    }
+                }
+                Some(symbol) if self.grammar.is_nonterminal(symbol) => {
+                    for (rule, r) in self.grammar.rules.iter().enumerate() {
+                        if r.lhs == symbol {
+                            let predicted = Item {
+                                rule,
+                                dot: 0,
+                                origin: set,
+                                children: Vec::new(),
+                            };
+                            self.add(set, predicted);
+                        }
+                    }
+                    // Nullable nonterminals completed earlier in this set
+                    let completed: Vec<_> = (0..self.sets[set].len())
+                        .filter(|j| {
+                            let c = &self.sets[set][*j];
+                            c.origin == set
+                                && self.next_symbol(c).is_none()
+                                && self.grammar.rules[c.rule].lhs == symbol
+                        })
+                        .map(|j| Self::advance(&item, Some((set, j))))
+                        .collect();
+                    for item in completed {
+                        self.add(set, item);
+                    }
+                }
+                Some(_) => {}
+            }
+            i += 1;
+        }
+    }
+    fn scan(&self, set: usize, terminal: &str) -> Vec<Item> {
+        self.sets[set]
+            .iter()
+            .filter(|item| self.next_symbol(item) == Some(terminal))
+            .map(|item| Self::advance(item, None))
+            .collect()
+    }
+    fn expected(&self, set: usize) -> Vec<String> {
+        let mut expected: Vec<String> = Vec::new();
+        for item in &self.sets[set] {
+            if let Some(symbol) = self.next_symbol(item) {
+                if !self.grammar.is_nonterminal(symbol) && !expected.iter().any(|e| e == symbol) {
+                    expected.push(symbol.to_string());
+                }
+            }
+        }
+        expected
+    }
+    /// Preorder rule indices of the subtree rooted at the item.
+    fn subtree(&self, set: usize, index: usize, trace: &mut Vec<usize>) {
+        let item = &self.sets[set][index];
+        trace.push(item.rule);
+        for (s, i) in item.children.iter().flatten() {
+            self.subtree(*s, *i, trace);
+        }
+    }
+    /// Derivation of the item and the chain of items that predicted it, up to the start symbol.
+    fn partial(&self, set: usize, index: usize, depth: usize, trace: &mut Vec<usize>) {
+        let item = &self.sets[set][index];
+        let lhs = &self.grammar.rules[item.rule].lhs;
+        if !(item.origin == 0 && *lhs == self.grammar.start) && depth < self.sets.len() + 16 {
+            let parent = self.sets[item.origin]
+                .iter()
+                .position(|p| p.dot > 0 && self.next_symbol(p) == Some(lhs))
+                .or_else(|| {
+                    self.sets[item.origin]
+                        .iter()
+                        .position(|p| self.next_symbol(p) == Some(lhs))
+                });
+            if let Some(parent) = parent {
+                self.partial(item.origin, parent, depth + 1, trace);
+            }
+        }
+        self.subtree(set, index, trace);
+    }
+    pub fn parse(mut self, input: &[String]) -> EarleyResult {
+        let grammar = self.grammar;
+        let kernel = grammar
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.lhs == grammar.start)
+            .map(|(rule, _)| Item {
+                rule,
+                dot: 0,
+                origin: 0,
+                children: Vec::new(),
+            })
+            .collect();
+        self.push_set(kernel);
+        let mut repairs = Vec::new();
+        for (i, terminal) in input.iter().enumerate() {
+            let current = self.sets.len() - 1;
+            let scanned = self.scan(current, terminal);
+            if !scanned.is_empty() {
+                self.push_set(scanned);
+                continue;
+            }
+            let expected = self.expected(current);
+            let inserted = expected.iter().find_map(|e| {
+                let mut trial = Earley {
+                    grammar,
+                    sets: self.sets.clone(),
+                    keys: self.keys.clone(),
+                };
+                trial.push_set(trial.scan(current, e));
+                let scanned = trial.scan(current + 1, terminal);
+                (!scanned.is_empty()).then(|| (e.clone(), trial, scanned))
+            });
+            match inserted {
+                Some((e, trial, scanned)) => {
+                    self.sets = trial.sets;
+                    self.keys = trial.keys;
+                    self.push_set(scanned);
+                    repairs.push(Repair::Insert(i, e));
+                }
+                None => repairs.push(Repair::Skip(i, expected)),
+            }
+        }
+        let last = self.sets.len() - 1;
+        let complete = self.sets[last].iter().position(|item| {
+            item.origin == 0
+                && self.next_symbol(item).is_none()
+                && grammar.rules[item.rule].lhs == grammar.start
+        });
+        let mut trace = Vec::new();
+        match complete {
+            Some(index) => self.subtree(last, index, &mut trace),
+            None => {
+                let best = (0..self.sets[last].len()).max_by_key(|i| {
+                    let item = &self.sets[last][*i];
+                    (item.dot > 0, item.dot, *i)
+                });
+                if let Some(best) = best {
+                    self.partial(last, best, 0, &mut trace);
+                }
+            }
+        }
+        EarleyResult {
+            trace,
+            complete: complete.is_some(),
+            repairs,
+            expected_at_end: if complete.is_some() {
+                Vec::new()
+            } else {
+                self.expected(last)
+            },
+        }
+    }
+}
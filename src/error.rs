@@ -1,126 +1,442 @@
 use anyhow::{anyhow, Result};
-#[derive(Debug, Clone)]
-pub struct LinePos {
-    content: Vec<char>,
-    start_offset: Vec<usize>,
-}
 
-impl LinePos {
-    pub fn new(content: &str) -> Self {
-        let mut content: Vec<_> = content.chars().collect();
-        if content.last() != Some(&'\n') {
-            content.push('\n');
-        }
-        let mut start_offset = vec![0];
-        for (i, c) in content.iter().enumerate() {
-            if *c == '\n' {
-                start_offset.push(i + 1);
-            }
+use crate::{
+    codes::{Code, LintLevel, WarningLevels},
+    diagnostics::{self, DiagnosticsFormat},
+    source_map::{FileId, SourceMap, Span},
+};
+
+/// How diagnostics are reported and rendered.
+#[derive(Debug, Clone, Default)]
+pub struct DisplayOptions {
+    /// Warnings to drop or to report as errors.
+    pub warnings: WarningLevels,
+    /// Maximum line width, None for unlimited.
+    /// Longer messages are wrapped, longer source lines are truncated around the caret.
+    pub width: Option<usize>,
+    pub format: DiagnosticsFormat,
+    /// Whether to use ANSI colors: the level in red or yellow, the caret in bold.
+    pub color: bool,
+}
+/// Text in the ANSI style, e.g. `1;31` for bold red, unchanged without color.
+fn paint(text: &str, style: &str, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", style, text)
+    } else {
+        text.to_string()
+    }
+}
+/// ANSI style of the level.
+fn level_style(level: &str) -> &'static str {
+    if level == "Error" {
+        "1;31"
+    } else {
+        "1;33"
+    }
+}
+/// Narrowest width honored, so that wrapping stays readable.
+const MIN_WIDTH: usize = 20;
+/// Greedy word wrap, continuation lines are indented by 4 spaces.
+fn wrap(text: &str, width: usize) -> String {
+    let mut result = String::new();
+    let mut line_len = 0;
+    for word in text.split(' ') {
+        let word_len = word.chars().count();
+        if line_len > 4 && line_len + 1 + word_len > width {
+            result.push_str("\n    ");
+            line_len = 4;
+        } else if !result.is_empty() {
+            result.push(' ');
+            line_len += 1;
         }
-        LinePos {
-            content,
-            start_offset,
+        result.push_str(word);
+        line_len += word_len;
+    }
+    result
+}
+/// Truncate the line to `width` characters around the 1-based column,
+/// marking the cut ends with `…`.
+/// Returns the truncated line and the column within it.
+fn truncate(line: &str, col: usize, width: usize) -> (String, usize) {
+    let chars: Vec<char> = line.chars().collect();
+    if chars.len() <= width {
+        return (line.to_string(), col);
+    }
+    let start = (col - 1).saturating_sub(width / 2).min(chars.len() - width);
+    let end = start + width;
+    let mut window: Vec<char> = chars[start..end].to_vec();
+    if start > 0 {
+        window[0] = '…';
+    }
+    if end < chars.len() {
+        window[width - 1] = '…';
+    }
+    (window.into_iter().collect(), col - start)
+}
+/// Machine-applicable fix: replace the characters of the span.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub span: Span,
+    pub replacement: String,
+}
+/// Apply non-overlapping fixes to the content, overlapping ones are skipped.
+pub fn apply_fixes(content: &str, fixes: &[&Fix]) -> String {
+    let mut fixes = fixes.to_vec();
+    fixes.sort_by_key(|f| f.span);
+    let chars: Vec<char> = content.chars().collect();
+    let mut result = String::new();
+    let mut pos = 0;
+    for fix in fixes {
+        if fix.span.start < pos || fix.span.end > chars.len() {
+            continue;
         }
+        result.extend(&chars[pos..fix.span.start]);
+        result.push_str(&fix.replacement);
+        pos = fix.span.end;
     }
-    /// Get the line and column number from the offset.
-    /// The line and column number are 1-based.
-    pub fn line_col(&self, offset: usize) -> (usize, usize) {
-        if offset > self.content.len() {
-            return (self.start_offset.len(), 1);
+    result.extend(&chars[pos..]);
+    result
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteLevel {
+    Note,
+    Help,
+}
+impl NoteLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NoteLevel::Note => "Note",
+            NoteLevel::Help => "Help",
         }
-        let line = self
-            .start_offset
-            .binary_search(&offset)
-            .map(|x| x + 1)
-            .unwrap_or_else(|x| x);
-        let col = offset - self.start_offset[line - 1] + 1;
-        (line, col)
-    }
-    /// Get the line content from the line number.
-    /// The line number is 1-based.
-    pub fn get_line(&self, line: usize) -> Option<String> {
-        let start = self.start_offset.get(line - 1)?;
-        let end = self.start_offset.get(line)?;
-        Some(self.content[*start..*end].iter().collect::<String>())
     }
 }
+/// Note or help attached to a diagnostic, rendered beneath it.
+#[derive(Debug, Clone)]
+pub struct Note {
+    pub level: NoteLevel,
+    pub msg: String,
+}
+/// Secondary span of a diagnostic in the same file, e.g. the original declaration
+/// of a duplicate, rendered as a snippet of its own.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub msg: String,
+}
 #[derive(Debug, Clone)]
 pub struct OffsetError {
-    pub offset: usize,
+    pub file: FileId,
+    pub span: Span,
+    pub code: Code,
     pub msg: String,
+    pub fix: Option<Fix>,
+    pub notes: Vec<Note>,
+    pub labels: Vec<Label>,
 }
 impl OffsetError {
+    /// Source line of the span, truncated to the width, with the column and
+    /// the number of characters of the span within it.
+    fn snippet(
+        &self,
+        sources: &SourceMap,
+        span: Span,
+        options: &DisplayOptions,
+    ) -> (String, usize, usize) {
+        let (line, col) = span.to_line_col(sources, self.file);
+        let width = span.width_on_line(sources, self.file);
+        let content = sources.line(self.file, line).unwrap_or("".to_string());
+        let content = content.trim_end();
+        let (content, col) = match options.width {
+            Some(width) => truncate(content, col, width.max(MIN_WIDTH) - 4),
+            None => (content.to_string(), col),
+        };
+        // The span may run past the truncated end of the line
+        let available = (content.chars().count() + 1).saturating_sub(col);
+        (content, col, width.min(available).max(1))
+    }
     /// Display the error message with the line and column number.
     /// ```plaintext
-    /// [file_name:line:col] Error/Warning: msg
+    /// [file_name:line:col] Error/Warning[code]: msg
     ///    line_content
-    ///    ^
+    ///    ^^^^
+    ///    --> file_name:line:col
+    ///    label_line_content
+    ///    ---- label
+    ///    Note/Help: note
     /// ```
     pub fn display_with(
         level: &str,
-        line_pos: &LinePos,
-        file_name: &str,
+        sources: &SourceMap,
         e: &OffsetError,
+        options: &DisplayOptions,
     ) -> String {
-        let (line, col) = line_pos.line_col(e.offset);
-        let mut result = format!("[{}:{}:{}] {}: {}\n", file_name, line, col, level, e.msg);
-        result.push_str(&format!(
-            "    {}\n",
-            line_pos.get_line(line).unwrap_or("".to_string()).trim_end()
-        ));
-        result.push_str(&format!("    {}^\n", " ".repeat(col - 1)));
+        let (line, col) = e.span.to_line_col(sources, e.file);
+        let title = format!("{}[{}]", level, e.code);
+        let header = format!(
+            "[{}:{}:{}] {}: {}",
+            sources.name(e.file),
+            line,
+            col,
+            title,
+            e.msg
+        );
+        let (content, col, span_width) = e.snippet(sources, e.span, options);
+        let header = match options.width {
+            Some(width) => wrap(&header, width.max(MIN_WIDTH)),
+            None => header,
+        };
+        // Colored after wrapping, which counts the characters
+        let header = header.replacen(
+            &format!("] {}:", title),
+            &format!("] {}:", paint(&title, level_style(level), options.color)),
+            1,
+        );
+        let mut result = format!("{}\n", header);
+        result.push_str(&format!("    {}\n", content));
+        let caret = paint(&"^".repeat(span_width), "1", options.color);
+        result.push_str(&format!("    {}{}\n", " ".repeat(col - 1), caret));
+        for label in &e.labels {
+            let (line, col) = label.span.to_line_col(sources, e.file);
+            let (content, snippet_col, span_width) = e.snippet(sources, label.span, options);
+            let mark = format!(
+                "{}{} {}",
+                " ".repeat(snippet_col - 1),
+                paint(&"-".repeat(span_width), "1", options.color),
+                label.msg
+            );
+            result.push_str(&format!(
+                "    --> {}:{}:{}\n",
+                sources.name(e.file),
+                line,
+                col
+            ));
+            result.push_str(&format!("    {}\n", content));
+            result.push_str(&format!("    {}\n", mark));
+        }
+        let fix = e.fix.as_ref().map(|fix| Note {
+            level: NoteLevel::Help,
+            msg: format!("replace with `{}`", fix.replacement),
+        });
+        for note in e.notes.iter().chain(&fix) {
+            let note = format!("    {}: {}", note.level.as_str(), note.msg);
+            let note = match options.width {
+                Some(width) => wrap(&note, width.max(MIN_WIDTH)),
+                None => note,
+            };
+            result.push_str(&format!("{}\n", note));
+        }
         result
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct ErrorRecorder {
+    /// File of the diagnostics recorded from now on.
+    file: FileId,
     errors: Vec<OffsetError>,
     warnings: Vec<OffsetError>,
+    /// Whether the last recorded diagnostic is an error, for attaching notes.
+    last_is_error: Option<bool>,
+    options: DisplayOptions,
 }
 impl ErrorRecorder {
-    pub fn new() -> Self {
+    pub fn new(file: FileId) -> Self {
         Self {
+            file,
             errors: Vec::new(),
             warnings: Vec::new(),
+            last_is_error: None,
+            options: DisplayOptions::default(),
+        }
+    }
+    /// Set the options, applying the warning levels to the warnings recorded so far.
+    pub fn set_options(&mut self, options: DisplayOptions) {
+        self.options = options;
+        for e in std::mem::take(&mut self.warnings) {
+            match self.options.warnings.level(e.code) {
+                LintLevel::Allow => {}
+                LintLevel::Warn => self.warnings.push(e),
+                LintLevel::Deny => self.errors.push(e),
+            }
         }
+        self.last_is_error = None;
     }
-    pub fn hard<T>(&mut self, offset: usize, msg: impl AsRef<str>) -> Result<T> {
+    pub fn with_options(file: FileId, options: DisplayOptions) -> Self {
+        Self {
+            options,
+            ..Self::new(file)
+        }
+    }
+    /// Record an error and abort with it.
+    pub fn hard<T>(
+        &mut self,
+        span: impl Into<Span>,
+        code: Code,
+        msg: impl AsRef<str>,
+    ) -> Result<T> {
         let msg = msg.as_ref().to_string();
-        self.error(offset, msg.clone());
+        self.error(span, code, msg.clone());
         Err(anyhow!(msg))
     }
-    pub fn error(&mut self, offset: usize, msg: impl AsRef<str>) {
-        let msg = msg.as_ref().to_string();
-        self.errors.push(OffsetError { offset, msg });
+    fn push(
+        &mut self,
+        is_error: bool,
+        span: Span,
+        code: Code,
+        msg: impl AsRef<str>,
+        fix: Option<Fix>,
+    ) {
+        let level = if is_error {
+            LintLevel::Deny
+        } else {
+            self.options.warnings.level(code)
+        };
+        let e = OffsetError {
+            file: self.file,
+            span,
+            code,
+            msg: msg.as_ref().to_string(),
+            fix,
+            notes: Vec::new(),
+            labels: Vec::new(),
+        };
+        // Notes attached to an allowed warning are dropped with it
+        self.last_is_error = match level {
+            LintLevel::Allow => None,
+            LintLevel::Warn => {
+                self.warnings.push(e);
+                Some(false)
+            }
+            LintLevel::Deny => {
+                self.errors.push(e);
+                Some(true)
+            }
+        };
     }
-    pub fn warning(&mut self, offset: usize, msg: impl AsRef<str>) {
-        let msg = msg.as_ref().to_string();
-        self.warnings.push(OffsetError { offset, msg });
+    pub fn error(&mut self, span: impl Into<Span>, code: Code, msg: impl AsRef<str>) {
+        self.push(true, span.into(), code, msg, None);
+    }
+    pub fn warning(&mut self, span: impl Into<Span>, code: Code, msg: impl AsRef<str>) {
+        self.push(false, span.into(), code, msg, None);
+    }
+    /// Error with a machine-applicable fix, located at the fix.
+    pub fn error_with_fix(&mut self, fix: Fix, code: Code, msg: impl AsRef<str>) {
+        self.push(true, fix.span, code, msg, Some(fix));
+    }
+    /// Warning with a machine-applicable fix, located at the fix.
+    pub fn warning_with_fix(&mut self, fix: Fix, code: Code, msg: impl AsRef<str>) {
+        self.push(false, fix.span, code, msg, Some(fix));
+    }
+    /// The last recorded error or warning.
+    fn last_mut(&mut self) -> Option<&mut OffsetError> {
+        match self.last_is_error {
+            Some(true) => self.errors.last_mut(),
+            Some(false) => self.warnings.last_mut(),
+            None => None,
+        }
+    }
+    fn attach(&mut self, level: NoteLevel, msg: impl AsRef<str>) {
+        if let Some(e) = self.last_mut() {
+            e.notes.push(Note {
+                level,
+                msg: msg.as_ref().to_string(),
+            });
+        }
+    }
+    /// Attach a note to the last recorded error or warning.
+    pub fn note(&mut self, msg: impl AsRef<str>) {
+        self.attach(NoteLevel::Note, msg);
+    }
+    /// Attach a help to the last recorded error or warning.
+    pub fn help(&mut self, msg: impl AsRef<str>) {
+        self.attach(NoteLevel::Help, msg);
+    }
+    /// Attach a secondary span with its message to the last recorded error or warning.
+    pub fn label(&mut self, span: impl Into<Span>, msg: impl AsRef<str>) {
+        let span = span.into();
+        if let Some(e) = self.last_mut() {
+            e.labels.push(Label {
+                span,
+                msg: msg.as_ref().to_string(),
+            });
+        }
+    }
+    pub fn fixes(&self) -> Vec<&Fix> {
+        self.errors
+            .iter()
+            .chain(&self.warnings)
+            .filter_map(|e| e.fix.as_ref())
+            .collect()
+    }
+    /// Whether an error of the current file covers the offset.
+    pub fn has_error_at(&self, offset: usize) -> bool {
+        self.errors
+            .iter()
+            .any(|e| e.file == self.file && e.span.contains(offset))
+    }
+    /// Whether an error of the current file starts within the span.
+    pub fn has_error_in(&self, span: Span) -> bool {
+        self.errors
+            .iter()
+            .any(|e| e.file == self.file && span.contains(e.span.start))
     }
     pub fn no_error(&self) -> bool {
         self.errors.is_empty()
     }
-    pub fn display_with(&self, file_name: &str, content: &str) -> String {
-        let line_pos = LinePos::new(content);
-        let errors = self.errors.iter().map(|e| {
-            (
-                e.offset,
-                OffsetError::display_with("Error", &line_pos, file_name, e),
-            )
-        });
-        let warnings = self.warnings.iter().map(|e| {
-            (
-                e.offset,
-                OffsetError::display_with("Warning", &line_pos, file_name, e),
-            )
-        });
+    pub fn error_count(&self) -> usize {
+        self.errors.len()
+    }
+    pub fn warning_count(&self) -> usize {
+        self.warnings.len()
+    }
+    /// Final summary line, e.g.
+    /// `Error: aborting due to 3 previous errors; 2 warnings emitted`.
+    /// None if there is no diagnostic.
+    pub fn summary(&self) -> Option<String> {
+        fn plural(n: usize, word: &str) -> String {
+            format!("{} {}{}", n, word, if n == 1 { "" } else { "s" })
+        }
+        let warnings = plural(self.warning_count(), "warning");
+        match (self.error_count(), self.warning_count()) {
+            (0, 0) => None,
+            (0, _) => Some(format!("Warning: {} emitted", warnings)),
+            (e, 0) => Some(format!(
+                "Error: aborting due to {}",
+                plural(e, "previous error")
+            )),
+            (e, _) => Some(format!(
+                "Error: aborting due to {}; {} emitted",
+                plural(e, "previous error"),
+                warnings
+            )),
+        }
+    }
+    /// All diagnostics as (level, error), sorted by file and offset.
+    pub fn sorted(&self) -> Vec<(&'static str, &OffsetError)> {
+        let errors = self.errors.iter().map(|e| ("Error", e));
+        let warnings = self.warnings.iter().map(|e| ("Warning", e));
         let mut all: Vec<_> = errors.chain(warnings).collect();
-        all.sort_by_key(|x| x.0);
-        all.into_iter().map(|(_, s)| s).collect()
+        all.sort_by_key(|(_, e)| (e.file, e.span.start));
+        all
     }
-    /// Display the error message with the line and column number.
-    pub fn print_with(&self, file_name: &str, content: &str) {
-        eprintln!("{}", self.display_with(file_name, content));
+    pub fn display_with(&self, sources: &SourceMap) -> String {
+        self.sorted()
+            .into_iter()
+            .map(|(level, e)| OffsetError::display_with(level, sources, e, &self.options))
+            .collect()
+    }
+    /// Display the error message with the line and column number,
+    /// followed by the summary line, or the diagnostics in the selected format.
+    pub fn print_with(&self, sources: &SourceMap) {
+        if let Some(output) = diagnostics::render(self.options.format, sources, &self.sorted()) {
+            eprint!("{}", output);
+            return;
+        }
+        eprintln!("{}", self.display_with(sources));
+        if let Some(summary) = self.summary() {
+            let (level, rest) = summary.split_once(':').unwrap_or_default();
+            let level = paint(level, level_style(level), self.options.color);
+            eprintln!("{}:{}", level, rest);
+        }
     }
 }
@@ -0,0 +1,433 @@
+use crate::{
+    error::ErrorRecorder,
+    lex::{lex, LexOptions, Token, TokenEnum},
+    source_map::FileId,
+};
+
+const INDENT: &str = "    ";
+
+/// Reprint a program free of errors with canonical layout.
+/// ```plaintext
+/// var
+///     i, j: integer;
+/// begin
+///     while i < 10 do
+///     begin
+///         i := i + 1
+///     end
+/// end.
+/// ```
+/// Each declaration and statement is on a line of its own, indented by the `begin`,
+/// `case` and `var` blocks around it. A statement after `then`, `else` or `do` is indented
+/// on the next line, a block there starts on the next line at the same indentation.
+/// Operators are surrounded by single spaces. Comments and single blank lines are kept,
+/// a comment after a token on its line stays after it.
+pub fn to_formatted(source: &str, tokens: &[Token]) -> String {
+    let source: Vec<_> = source.chars().collect();
+    let mut printer = Printer {
+        source: &source,
+        tokens,
+        index: 0,
+        pos: 0,
+        output: String::new(),
+        indent: 0,
+        prev: None,
+        prev_unary: false,
+        after_comment: false,
+    };
+    printer.program();
+    printer.output
+}
+
+/// Tokens of the program separated by a space only where they would otherwise be lexed
+/// differently, e.g. `var i:integer;begin i:=i+1 end.`
+/// Each token is lexed joined to the two before it, and separated from them by a space,
+/// so the output is lexed into the same tokens as the input. Comments and line breaks are dropped.
+pub fn to_minified(source: &str, tokens: &[Token], options: &LexOptions) -> String {
+    let source: Vec<_> = source.chars().collect();
+    let mut output = String::new();
+    // Start offsets in the output of the last two tokens
+    let mut starts: Vec<usize> = Vec::new();
+    for token in tokens {
+        let text = token_text(&source, token);
+        let window = &output[*starts.first().unwrap_or(&0)..];
+        let joined = format!("{}{}", window, text);
+        let separated = format!("{} {}", window, text);
+        if !lexed_alike(&joined, &separated, options) {
+            output.push(' ');
+        }
+        if starts.len() == 2 {
+            starts.remove(0);
+        }
+        starts.push(output.len());
+        output.push_str(&text);
+    }
+    output.push('\n');
+    output
+}
+/// Whether the texts are lexed into tokens of the same kinds and contents, with as many errors.
+/// Errors are compared rather than ruled out, as `=` is only valid in a declaration.
+fn lexed_alike(a: &str, b: &str, options: &LexOptions) -> bool {
+    let lex_text = |text: &str| {
+        let mut errors = ErrorRecorder::new(FileId::default());
+        let (tokens, _) = lex(text, options, &mut errors);
+        let tokens: Vec<_> = tokens.into_iter().map(|t| (t.token, t.content)).collect();
+        (tokens, errors.error_count())
+    };
+    lex_text(a) == lex_text(b)
+}
+/// Source text of the token, numbers normalized, e.g. leading zeros removed.
+fn token_text(source: &[char], token: &Token) -> String {
+    match token.token {
+        TokenEnum::IntLiteral | TokenEnum::RealLiteral => token.content.clone(),
+        _ => source[token.offset..token.end].iter().collect(),
+    }
+}
+
+/// Comment, or other text skipped by the lexer, between two tokens.
+pub struct Comment {
+    pub start: usize,
+    pub end: usize,
+    /// Line breaks between the previous token or comment and this one
+    pub newlines: usize,
+}
+impl Comment {
+    fn is_line_comment(&self, source: &[char]) -> bool {
+        source[self.start..].starts_with(&['/', '/'])
+    }
+    /// Whether this is a comment rather than other text, e.g. an unexpected character.
+    pub fn is_comment(&self, source: &[char]) -> bool {
+        let text = &source[self.start..];
+        ["//", "(*", "{"]
+            .iter()
+            .any(|open| text.starts_with(&open.chars().collect::<Vec<_>>()))
+    }
+}
+/// Comments in `source[start..end]`, the gap between two tokens.
+pub fn comments(source: &[char], start: usize, end: usize) -> Vec<Comment> {
+    let mut result = Vec::new();
+    let (mut i, mut newlines) = (start, 0);
+    while i < end {
+        let rest = &source[i..end];
+        let len = if rest[0] == '\n' {
+            newlines += 1;
+            i += 1;
+            continue;
+        } else if rest[0].is_whitespace() {
+            i += 1;
+            continue;
+        } else if rest.starts_with(&['/', '/']) {
+            rest.iter().position(|&c| c == '\n').unwrap_or(rest.len())
+        } else if let Some((open, close)) = [("(*", "*)"), ("{", "}")]
+            .into_iter()
+            .find(|(open, _)| rest.starts_with(&open.chars().collect::<Vec<_>>()))
+        {
+            let close: Vec<_> = close.chars().collect();
+            let skip = open.len();
+            rest[skip..]
+                .windows(close.len())
+                .position(|w| w == close)
+                .map_or(rest.len(), |p| skip + p + close.len())
+        } else {
+            rest.iter()
+                .position(|c| c.is_whitespace())
+                .unwrap_or(rest.len())
+        };
+        result.push(Comment {
+            start: i,
+            end: i + len,
+            newlines,
+        });
+        i += len;
+        newlines = 0;
+    }
+    result
+}
+/// Whether a token of this kind ends an operand, so that a following `-` is binary.
+fn ends_operand(token: TokenEnum) -> bool {
+    use TokenEnum::*;
+    matches!(
+        token,
+        Identifier | IntLiteral | RealLiteral | CharLiteral | True | False | RParen | RBracket
+    )
+}
+/// Whether a space separates the tokens on a line, the previous one not being a unary sign.
+fn space_between(prev: TokenEnum, next: TokenEnum) -> bool {
+    use TokenEnum::*;
+    match (prev, next) {
+        // `-1: ;`, an empty case arm
+        (Colon, SemiColon) => true,
+        (_, RParen | RBracket | Comma | SemiColon | Colon | Dot | DotDot) => false,
+        (LParen | LBracket | Dot | DotDot, _) => false,
+        // `f(x)`, `a[i]`, `read(x)`
+        (Identifier | Read | Write | RBracket, LBracket) => false,
+        (Identifier | Read | Write, LParen) => false,
+        _ => true,
+    }
+}
+
+struct Printer<'a> {
+    source: &'a [char],
+    tokens: &'a [Token],
+    /// Index of the next token
+    index: usize,
+    /// Offset up to which the source is printed, comments included
+    pos: usize,
+    output: String,
+    indent: usize,
+    /// Last token printed
+    prev: Option<TokenEnum>,
+    /// Whether the last token printed is a unary sign
+    prev_unary: bool,
+    /// Whether a comment is printed after the last token
+    after_comment: bool,
+}
+impl Printer<'_> {
+    fn peek(&self) -> Option<TokenEnum> {
+        self.tokens.get(self.index).map(|t| t.token)
+    }
+    /// Offset of the next token, the end of the source after the last one.
+    fn next_offset(&self) -> usize {
+        self.tokens
+            .get(self.index)
+            .map_or(self.source.len(), |t| t.offset)
+    }
+    fn at_line_start(&self) -> bool {
+        self.output.is_empty() || self.output.ends_with('\n')
+    }
+    fn newline(&mut self) {
+        if !self.at_line_start() {
+            self.output.truncate(self.output.trim_end().len());
+            self.output.push('\n');
+        }
+    }
+    /// Blank line at the start of a line, unless at the start of the output or after one.
+    fn blank_line(&mut self) {
+        if !self.output.is_empty() && !self.output.ends_with("\n\n") {
+            self.output.push('\n');
+        }
+    }
+    /// Write the text, indented at the start of a line, otherwise after a space if `space`.
+    fn write(&mut self, text: &str, space: bool) {
+        if self.at_line_start() {
+            for _ in 0..self.indent {
+                self.output.push_str(INDENT);
+            }
+        } else if space {
+            self.output.push(' ');
+        }
+        self.output.push_str(text);
+    }
+    fn write_comment(&mut self, comment: &Comment) {
+        let text: String = self.source[comment.start..comment.end].iter().collect();
+        self.write(text.trim_end(), true);
+        self.after_comment = true;
+        self.pos = comment.end;
+        if comment.is_line_comment(self.source) {
+            self.newline();
+        }
+    }
+    /// Print the comments before the next token, those after a line break on lines of their own.
+    fn leading_comments(&mut self) {
+        let end = self.next_offset();
+        for comment in comments(self.source, self.pos, end) {
+            let own_line = comment.newlines > 0 || self.at_line_start();
+            if own_line {
+                self.newline();
+                if comment.newlines > 1 {
+                    self.blank_line();
+                }
+            }
+            self.write_comment(&comment);
+            if own_line {
+                self.newline();
+            }
+        }
+        let newlines = self.source[self.pos..end]
+            .iter()
+            .filter(|&&c| c == '\n')
+            .count();
+        if newlines > 1 && self.at_line_start() {
+            self.blank_line();
+        }
+        self.pos = end;
+    }
+    /// Print the comments on the line of the last token, after it.
+    fn trailing_comments(&mut self) {
+        let end = self.next_offset();
+        for comment in comments(self.source, self.pos, end) {
+            if comment.newlines > 0 {
+                return;
+            }
+            self.write_comment(&comment);
+        }
+    }
+    /// Print the next token with its comments.
+    fn token(&mut self) {
+        let Some(token) = self.tokens.get(self.index) else {
+            return;
+        };
+        self.leading_comments();
+        let text = token_text(self.source, token);
+        let space = match self.prev {
+            // Spaced as after an operand, e.g. `m (* c *): integer`
+            Some(_) if self.after_comment => space_between(TokenEnum::Identifier, token.token),
+            Some(prev) => !self.prev_unary && space_between(prev, token.token),
+            None => false,
+        };
+        self.write(&text, space);
+        self.prev_unary = matches!(token.token, TokenEnum::Add | TokenEnum::Sub)
+            && !self.prev.is_some_and(ends_operand);
+        self.prev = Some(token.token);
+        self.after_comment = false;
+        self.pos = token.end;
+        self.index += 1;
+        self.trailing_comments();
+    }
+    fn eat(&mut self, token: TokenEnum) {
+        if self.peek() == Some(token) {
+            self.token();
+        }
+    }
+    /// Print the tokens on the line up to one matching `stop` outside of brackets and records.
+    fn until(&mut self, stop: impl Fn(TokenEnum) -> bool) {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            if depth == 0 && stop(token) {
+                return;
+            }
+            match token {
+                TokenEnum::LParen | TokenEnum::LBracket | TokenEnum::Record => depth += 1,
+                TokenEnum::RParen | TokenEnum::RBracket | TokenEnum::End => depth -= 1,
+                _ => {}
+            }
+            self.token();
+        }
+    }
+    fn program(&mut self) {
+        if self.peek() == Some(TokenEnum::Program) {
+            self.until(|t| t == TokenEnum::SemiColon);
+            self.eat(TokenEnum::SemiColon);
+        }
+        self.declarations();
+        self.newline();
+        self.statement();
+        self.eat(TokenEnum::Dot);
+        while self.peek().is_some() {
+            self.token();
+        }
+        self.leading_comments();
+        self.newline();
+    }
+    /// Declaration blocks, and procedures with their own declarations and body.
+    fn declarations(&mut self) {
+        use TokenEnum::*;
+        loop {
+            match self.peek() {
+                Some(Const | Type | Var) => {
+                    self.newline();
+                    self.token();
+                    self.indent += 1;
+                    while !matches!(
+                        self.peek(),
+                        None | Some(Const | Type | Var | Procedure | Function | Begin)
+                    ) {
+                        self.newline();
+                        self.until(|t| t == SemiColon);
+                        self.eat(SemiColon);
+                    }
+                    self.indent -= 1;
+                }
+                Some(Procedure | Function) => {
+                    self.newline();
+                    self.until(|t| t == SemiColon);
+                    self.eat(SemiColon);
+                    self.declarations();
+                    self.newline();
+                    self.statement();
+                    self.eat(SemiColon);
+                }
+                _ => return,
+            }
+        }
+    }
+    fn statement(&mut self) {
+        use TokenEnum::*;
+        match self.peek() {
+            Some(Begin) => {
+                self.token();
+                self.indent += 1;
+                while !matches!(self.peek(), None | Some(End)) {
+                    self.newline();
+                    if self.peek() != Some(SemiColon) {
+                        self.statement();
+                    }
+                    self.eat(SemiColon);
+                }
+                self.indent -= 1;
+                self.newline();
+                self.eat(End);
+            }
+            Some(If) => {
+                self.token();
+                self.until(|t| t == Then);
+                self.eat(Then);
+                self.branch();
+                if self.peek() == Some(Else) {
+                    self.newline();
+                    self.token();
+                    // `else if` stays on one line
+                    if self.peek() == Some(If) {
+                        self.statement();
+                    } else {
+                        self.branch();
+                    }
+                }
+            }
+            Some(While) => {
+                self.token();
+                self.until(|t| t == Do);
+                self.eat(Do);
+                self.branch();
+            }
+            Some(Case) => {
+                self.token();
+                self.until(|t| t == Of);
+                self.eat(Of);
+                self.indent += 1;
+                while !matches!(self.peek(), None | Some(End)) {
+                    self.newline();
+                    if self.peek() != Some(SemiColon) {
+                        self.until(|t| t == Colon);
+                        self.eat(Colon);
+                        // The body follows the labels on their line
+                        if !matches!(self.peek(), Some(SemiColon | End)) {
+                            self.statement();
+                        }
+                    }
+                    self.eat(SemiColon);
+                }
+                self.indent -= 1;
+                self.newline();
+                self.eat(End);
+            }
+            _ => self.until(|t| matches!(t, SemiColon | End | Else)),
+        }
+    }
+    /// Statement after `then`, `else` or `do`.
+    fn branch(&mut self) {
+        match self.peek() {
+            None | Some(TokenEnum::SemiColon | TokenEnum::End | TokenEnum::Else) => {}
+            Some(TokenEnum::Begin) => {
+                self.newline();
+                self.statement();
+            }
+            Some(_) => {
+                self.indent += 1;
+                self.newline();
+                self.statement();
+                self.indent -= 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+/// Expand a pattern with `*` and `?` wildcards into the matching paths, sorted.
+/// Wildcards match within a path component, and hidden files only if the component
+/// starts with `.` too. A pattern without wildcards is returned as is, even if it does not exist,
+/// so that shells which already expanded the pattern work the same.
+pub fn expand(pattern: &str) -> Result<Vec<PathBuf>> {
+    if !pattern.contains(['*', '?']) {
+        return Ok(vec![PathBuf::from(pattern)]);
+    }
+    let mut paths = vec![PathBuf::new()];
+    for component in Path::new(pattern).components() {
+        let component = component.as_os_str().to_string_lossy();
+        if !component.contains(['*', '?']) {
+            for path in &mut paths {
+                path.push(&*component);
+            }
+            continue;
+        }
+        let component: Vec<_> = component.chars().collect();
+        let mut matched = Vec::new();
+        for dir in &paths {
+            let read_dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            // Non-directories and unreadable directories match nothing
+            let Ok(entries) = std::fs::read_dir(read_dir) else {
+                continue;
+            };
+            for entry in entries {
+                let name = entry?.file_name().to_string_lossy().to_string();
+                let name_chars: Vec<_> = name.chars().collect();
+                let hidden = name.starts_with('.') && component.first() != Some(&'.');
+                if !hidden && matches(&component, &name_chars) {
+                    matched.push(dir.join(name));
+                }
+            }
+        }
+        paths = matched;
+    }
+    // Literal components after the last wildcard, e.g. `x.in` in `data/*/x.in`
+    paths.retain(|path| path.exists());
+    if paths.is_empty() {
+        return Err(anyhow!("No file matches {}", pattern));
+    }
+    paths.sort();
+    Ok(paths)
+}
+/// Whether the name matches the pattern, `*` matches any run of characters and `?` one.
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) => p == n && matches(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
@@ -0,0 +1,484 @@
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{codes::Code, error::ErrorRecorder};
+
+/// End of input marker used in follow sets.
+pub const END_MARKER: &str = "#";
+/// A context-free production `lhs -> rhs`, empty rhs for ε.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub lhs: String,
+    pub rhs: Vec<String>,
+}
+impl Rule {
+    pub fn display(&self) -> String {
+        format!("{} -> {}", self.lhs, display_symbols(&self.rhs))
+    }
+}
+fn display_symbols(symbols: &[String]) -> String {
+    if symbols.is_empty() {
+        "ε".to_string()
+    } else {
+        symbols.join(" ")
+    }
+}
+fn display_set(set: &BTreeSet<String>, nullable: bool) -> String {
+    let mut items: Vec<_> = set.iter().map(|s| s.as_str()).collect();
+    if nullable {
+        items.push("ε");
+    }
+    format!("{{{}}}", items.join(", "))
+}
+
+/// Grammar file:
+/// ```plaintext
+/// // comment
+/// S -> Var D | ε
+/// D' -> D1 D'
+///     | epsilon
+/// ```
+/// Symbols are separated by whitespace, symbols appearing on a left-hand side are nonterminals.
+/// The first left-hand side is the start symbol.
+#[derive(Debug, Clone)]
+pub struct Grammar {
+    pub start: String,
+    /// Nonterminals in order of first definition.
+    pub nonterminals: Vec<String>,
+    pub rules: Vec<Rule>,
+}
+impl Grammar {
+    pub fn parse(content: &str, errors: &mut ErrorRecorder) -> Result<Self> {
+        let mut rules = Vec::new();
+        let mut nonterminals: Vec<String> = Vec::new();
+        let mut last_lhs: Option<String> = None;
+        let mut offset = 0;
+        for line in content.split_inclusive('\n') {
+            let line_offset = offset;
+            offset += line.chars().count();
+            let line = line.split("//").next().unwrap_or("").trim_end();
+            if line.trim().is_empty() {
+                continue;
+            }
+            let indent = line.chars().take_while(|c| c.is_whitespace()).count();
+            let line = line.trim_start();
+            let (lhs, body) = if let Some(body) = line.strip_prefix('|') {
+                match &last_lhs {
+                    Some(lhs) => (lhs.clone(), body),
+                    None => {
+                        errors.error(
+                            line_offset + indent,
+                            Code::MalformedGrammar,
+                            "Alternative without a production",
+                        );
+                        continue;
+                    }
+                }
+            } else {
+                let Some((lhs, body)) = line.split_once("->") else {
+                    errors.error(
+                        line_offset + indent,
+                        Code::MalformedGrammar,
+                        "Expected `->`",
+                    );
+                    continue;
+                };
+                let lhs: Vec<_> = lhs.split_whitespace().collect();
+                if lhs.len() != 1 {
+                    errors.error(
+                        line_offset + indent,
+                        Code::MalformedGrammar,
+                        "Left-hand side should be exactly one symbol",
+                    );
+                    continue;
+                }
+                let lhs = lhs[0].to_string();
+                if !nonterminals.contains(&lhs) {
+                    nonterminals.push(lhs.clone());
+                }
+                last_lhs = Some(lhs.clone());
+                (lhs, body)
+            };
+            for alternative in body.split('|') {
+                let rhs = alternative
+                    .split_whitespace()
+                    .filter(|s| *s != "ε" && *s != "epsilon")
+                    .map(|s| s.to_string())
+                    .collect();
+                rules.push(Rule {
+                    lhs: lhs.clone(),
+                    rhs,
+                });
+            }
+        }
+        if !errors.no_error() {
+            return Err(anyhow!("Invalid grammar"));
+        }
+        let start = match nonterminals.first() {
+            Some(start) => start.clone(),
+            None => return errors.hard(0, Code::MalformedGrammar, "Empty grammar"),
+        };
+        Ok(Self {
+            start,
+            nonterminals,
+            rules,
+        })
+    }
+    /// The first left-hand side is the start symbol.
+    pub fn from_rules(rules: Vec<Rule>) -> Self {
+        let mut nonterminals: Vec<String> = Vec::new();
+        for rule in &rules {
+            if !nonterminals.contains(&rule.lhs) {
+                nonterminals.push(rule.lhs.clone());
+            }
+        }
+        Self {
+            start: nonterminals.first().cloned().unwrap_or_default(),
+            nonterminals,
+            rules,
+        }
+    }
+    pub fn is_nonterminal(&self, symbol: &str) -> bool {
+        self.nonterminals.iter().any(|n| n == symbol)
+    }
+    pub fn terminals(&self) -> BTreeSet<String> {
+        self.rules
+            .iter()
+            .flat_map(|r| r.rhs.iter())
+            .filter(|s| !self.is_nonterminal(s))
+            .cloned()
+            .collect()
+    }
+    pub fn rules_of<'a>(&'a self, lhs: &'a str) -> impl Iterator<Item = &'a Rule> + 'a {
+        self.rules.iter().filter(move |r| r.lhs == lhs)
+    }
+    /// A fresh nonterminal name based on `base`, e.g. `A'`, `A''`.
+    fn fresh_name(&self, base: &str) -> String {
+        let mut name = format!("{}'", base);
+        while self.is_nonterminal(&name) {
+            name.push('\'');
+        }
+        name
+    }
+}
+
+/// First, follow and select sets of a grammar.
+#[derive(Debug, Clone)]
+pub struct Sets {
+    pub nullable: BTreeSet<String>,
+    /// First sets of nonterminals, without ε (see `nullable`).
+    pub first: BTreeMap<String, BTreeSet<String>>,
+    pub follow: BTreeMap<String, BTreeSet<String>>,
+}
+impl Sets {
+    pub fn new(grammar: &Grammar) -> Self {
+        let mut sets = Self {
+            nullable: BTreeSet::new(),
+            first: grammar
+                .nonterminals
+                .iter()
+                .map(|n| (n.clone(), BTreeSet::new()))
+                .collect(),
+            follow: grammar
+                .nonterminals
+                .iter()
+                .map(|n| (n.clone(), BTreeSet::new()))
+                .collect(),
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in &grammar.rules {
+                let (first, nullable) = sets.first_of(grammar, &rule.rhs);
+                let entry = sets.first.get_mut(&rule.lhs).unwrap();
+                let len = entry.len();
+                entry.extend(first);
+                changed |= entry.len() != len;
+                if nullable {
+                    changed |= sets.nullable.insert(rule.lhs.clone());
+                }
+            }
+        }
+        sets.follow
+            .get_mut(&grammar.start)
+            .unwrap()
+            .insert(END_MARKER.to_string());
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in &grammar.rules {
+                for (i, symbol) in rule.rhs.iter().enumerate() {
+                    if !grammar.is_nonterminal(symbol) {
+                        continue;
+                    }
+                    let (mut follow, nullable) = sets.first_of(grammar, &rule.rhs[i + 1..]);
+                    if nullable {
+                        follow.extend(sets.follow[&rule.lhs].iter().cloned());
+                    }
+                    let entry = sets.follow.get_mut(symbol).unwrap();
+                    let len = entry.len();
+                    entry.extend(follow);
+                    changed |= entry.len() != len;
+                }
+            }
+        }
+        sets
+    }
+    /// First set of a sequence of symbols.
+    /// Returns (first set without ε, whether the sequence is nullable)
+    pub fn first_of(&self, grammar: &Grammar, symbols: &[String]) -> (BTreeSet<String>, bool) {
+        let mut result = BTreeSet::new();
+        for symbol in symbols {
+            if !grammar.is_nonterminal(symbol) {
+                result.insert(symbol.clone());
+                return (result, false);
+            }
+            result.extend(self.first[symbol].iter().cloned());
+            if !self.nullable.contains(symbol) {
+                return (result, false);
+            }
+        }
+        (result, true)
+    }
+    pub fn select(&self, grammar: &Grammar, rule: &Rule) -> BTreeSet<String> {
+        let (mut select, nullable) = self.first_of(grammar, &rule.rhs);
+        if nullable {
+            select.extend(self.follow[&rule.lhs].iter().cloned());
+        }
+        select
+    }
+}
+
+/// Nonterminals reachable from `lhs` at the leftmost position of a sentential form.
+fn left_edges(grammar: &Grammar, sets: &Sets, lhs: &str) -> BTreeSet<String> {
+    let mut result = BTreeSet::new();
+    for rule in grammar.rules_of(lhs) {
+        for symbol in &rule.rhs {
+            if !grammar.is_nonterminal(symbol) {
+                break;
+            }
+            result.insert(symbol.clone());
+            if !sets.nullable.contains(symbol) {
+                break;
+            }
+        }
+    }
+    result
+}
+/// Find a path `from => ... => to` in the left edge graph.
+fn left_path(grammar: &Grammar, sets: &Sets, from: &str, to: &str) -> Option<Vec<String>> {
+    let mut visited = BTreeSet::new();
+    let mut stack = vec![vec![from.to_string()]];
+    while let Some(path) = stack.pop() {
+        let last = path.last().unwrap();
+        for next in left_edges(grammar, sets, last) {
+            let mut path = path.clone();
+            path.push(next.clone());
+            if next == to {
+                return Some(path);
+            }
+            if visited.insert(next) {
+                stack.push(path);
+            }
+        }
+    }
+    None
+}
+
+fn left_recursion(grammar: &Grammar, sets: &Sets) -> Vec<String> {
+    let mut report = Vec::new();
+    for lhs in &grammar.nonterminals {
+        let (recursive, others): (Vec<_>, Vec<_>) = grammar
+            .rules_of(lhs)
+            .partition(|r| r.rhs.first() == Some(lhs));
+        if !recursive.is_empty() {
+            let mut item = format!("Direct left recursion on {}:\n", lhs);
+            for rule in &recursive {
+                item.push_str(&format!("            {}\n", rule.display()));
+            }
+            let tail = grammar.fresh_name(lhs);
+            if others.is_empty() {
+                item.push_str(&format!(
+                    "        {} has no non-recursive alternative and derives no terminal string\n",
+                    lhs
+                ));
+            } else {
+                item.push_str("        Suggested:\n");
+                let betas: Vec<_> = others
+                    .iter()
+                    .map(|r| display_symbols(&[r.rhs.clone(), vec![tail.clone()]].concat()))
+                    .collect();
+                item.push_str(&format!("            {} -> {}\n", lhs, betas.join(" | ")));
+                let mut alphas: Vec<_> = recursive
+                    .iter()
+                    .map(|r| display_symbols(&[r.rhs[1..].to_vec(), vec![tail.clone()]].concat()))
+                    .collect();
+                alphas.push("ε".to_string());
+                item.push_str(&format!("            {} -> {}\n", tail, alphas.join(" | ")));
+            }
+            report.push(item);
+        } else if let Some(path) = left_path(grammar, sets, lhs, lhs) {
+            let through = &path[1..path.len() - 1];
+            let (kind, suggestion) = if through.is_empty() {
+                (
+                    "Hidden",
+                    "expand the nullable prefix, then eliminate the direct left recursion"
+                        .to_string(),
+                )
+            } else {
+                (
+                    "Indirect",
+                    format!(
+                    "substitute the productions of {} into {}, then eliminate the direct left recursion",
+                    through.join(", "),
+                    lhs
+                ),
+                )
+            };
+            report.push(format!(
+                "{} left recursion on {}: {}\n        Suggested: {}\n",
+                kind,
+                lhs,
+                path.join(" => "),
+                suggestion
+            ));
+        }
+    }
+    report
+}
+
+fn left_factoring(grammar: &Grammar) -> Vec<String> {
+    let mut report = Vec::new();
+    for lhs in &grammar.nonterminals {
+        let rules: Vec<_> = grammar.rules_of(lhs).collect();
+        let mut groups: Vec<(&String, Vec<&Rule>)> = Vec::new();
+        for rule in &rules {
+            let Some(first) = rule.rhs.first() else {
+                continue;
+            };
+            match groups.iter_mut().find(|(f, _)| *f == first) {
+                Some((_, group)) => group.push(rule),
+                None => groups.push((first, vec![rule])),
+            }
+        }
+        for (_, group) in groups.into_iter().filter(|(_, g)| g.len() > 1) {
+            let mut prefix_len = group[0].rhs.len();
+            for rule in &group[1..] {
+                prefix_len = prefix_len.min(
+                    group[0]
+                        .rhs
+                        .iter()
+                        .zip(&rule.rhs)
+                        .take_while(|(a, b)| a == b)
+                        .count(),
+                );
+            }
+            let prefix = &group[0].rhs[..prefix_len];
+            let tail = grammar.fresh_name(lhs);
+            let mut item = format!("Common prefix `{}` in {}:\n", display_symbols(prefix), lhs);
+            for rule in &group {
+                item.push_str(&format!("            {}\n", rule.display()));
+            }
+            let mut alternatives = vec![display_symbols(
+                &[prefix.to_vec(), vec![tail.clone()]].concat(),
+            )];
+            alternatives.extend(
+                rules
+                    .iter()
+                    .filter(|r| !group.contains(r))
+                    .map(|r| display_symbols(&r.rhs)),
+            );
+            let suffixes: Vec<_> = group
+                .iter()
+                .map(|r| display_symbols(&r.rhs[prefix_len..]))
+                .collect();
+            item.push_str("        Suggested:\n");
+            item.push_str(&format!(
+                "            {} -> {}\n",
+                lhs,
+                alternatives.join(" | ")
+            ));
+            item.push_str(&format!(
+                "            {} -> {}\n",
+                tail,
+                suffixes.join(" | ")
+            ));
+            report.push(item);
+        }
+    }
+    report
+}
+
+fn ll1_conflicts(grammar: &Grammar, sets: &Sets) -> Vec<String> {
+    let mut report = Vec::new();
+    for lhs in &grammar.nonterminals {
+        let rules: Vec<_> = grammar
+            .rules_of(lhs)
+            .map(|r| (r, sets.select(grammar, r)))
+            .collect();
+        for (i, (a, select_a)) in rules.iter().enumerate() {
+            for (b, select_b) in &rules[i + 1..] {
+                let common: BTreeSet<_> = select_a.intersection(select_b).cloned().collect();
+                if !common.is_empty() {
+                    report.push(format!(
+                        "`{}` and `{}` both select {}\n",
+                        a.display(),
+                        b.display(),
+                        display_set(&common, false)
+                    ));
+                }
+            }
+        }
+    }
+    report
+}
+
+/// Analyze the grammar and render a report with first/follow/select sets,
+/// left recursion, common prefixes and LL(1) conflicts, with suggested productions.
+pub fn analyze(grammar: &Grammar) -> String {
+    let sets = Sets::new(grammar);
+    let mut result = format!(
+        "Grammar: {} productions, {} nonterminals, {} terminals\nStart symbol: {}\n",
+        grammar.rules.len(),
+        grammar.nonterminals.len(),
+        grammar.terminals().len(),
+        grammar.start
+    );
+    result.push_str("\nFirst sets:\n");
+    for n in &grammar.nonterminals {
+        let set = display_set(&sets.first[n], sets.nullable.contains(n));
+        result.push_str(&format!("    First({}) = {}\n", n, set));
+    }
+    result.push_str("\nFollow sets:\n");
+    for n in &grammar.nonterminals {
+        let set = display_set(&sets.follow[n], false);
+        result.push_str(&format!("    Follow({}) = {}\n", n, set));
+    }
+    result.push_str("\nSelect sets:\n");
+    for rule in &grammar.rules {
+        let set = display_set(&sets.select(grammar, rule), false);
+        result.push_str(&format!("    Select({}) = {}\n", rule.display(), set));
+    }
+    let sections = [
+        ("Left recursion", left_recursion(grammar, &sets)),
+        ("Left factoring", left_factoring(grammar)),
+        ("LL(1) conflicts", ll1_conflicts(grammar, &sets)),
+    ];
+    // A left-recursive grammar is never LL(1), even without a select set conflict
+    let ll1 = sections[0].1.is_empty() && sections[2].1.is_empty();
+    for (title, items) in sections {
+        result.push_str(&format!("\n{}:\n", title));
+        if items.is_empty() {
+            result.push_str("    none\n");
+        }
+        for item in items {
+            result.push_str(&format!("    {}", item));
+        }
+    }
+    if ll1 {
+        result.push_str("\nThe grammar is LL(1).\n");
+    } else {
+        result.push_str("\nThe grammar is not LL(1).\n");
+    }
+    result
+}
@@ -0,0 +1,256 @@
+use crate::{
+    error::OffsetError,
+    format,
+    pre_grammar::ParseResult,
+    source_map::{FileId, SourceMap},
+};
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; font-family: monospace; white-space: pre; }
+td { padding: 0 0.5em; vertical-align: top; }
+td.ln { color: #999; text-align: right; user-select: none; }
+.keyword { color: #00f; font-weight: bold; }
+.literal { color: #098658; }
+.identifier { color: #001080; }
+.operator { color: #795e26; }
+.comment { color: #008000; font-style: italic; }
+.mark-error { text-decoration: wavy underline red; }
+.mark-warning { text-decoration: wavy underline orange; }
+tr.error td, p.error, li.error { color: #c00; }
+tr.warning td, p.warning, li.warning { color: #b60; }
+li a { color: inherit; }
+";
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+/// Source lines of the file as HTML, without line breaks.
+/// Tokens are in spans classed by their category, comments by `comment`,
+/// characters in the span of a diagnostic are underlined by its level.
+pub fn highlight(sources: &SourceMap, file: FileId, result: &ParseResult) -> Vec<String> {
+    let content: Vec<_> = sources.content(file).chars().collect();
+    let mut classes = vec![""; content.len()];
+    let mut gap_start = 0;
+    for token in &result.tokens {
+        for comment in format::comments(&content, gap_start, token.offset.max(gap_start)) {
+            if comment.is_comment(&content) {
+                classes[comment.start..comment.end].fill("comment");
+            }
+        }
+        classes[token.offset..token.end].fill(token.token.category());
+        gap_start = token.end;
+    }
+    for comment in format::comments(&content, gap_start, content.len()) {
+        if comment.is_comment(&content) {
+            classes[comment.start..comment.end].fill("comment");
+        }
+    }
+    let mut marks = vec![""; content.len() + 1];
+    // Warnings first, so that errors win where they overlap
+    let mut diagnostics = result.errors.sorted();
+    diagnostics.sort_by_key(|(level, _)| *level == "Error");
+    for (level, e) in diagnostics.into_iter().filter(|(_, e)| e.file == file) {
+        let mark = if level == "Error" {
+            "mark-error"
+        } else {
+            "mark-warning"
+        };
+        // An empty span marks the character at it, a span at EOF the end of input
+        let start = e.span.start.min(content.len());
+        let end = e.span.end.max(start.saturating_add(1)).min(marks.len());
+        for m in marks.iter_mut().take(end).skip(start) {
+            *m = mark;
+        }
+    }
+    let mut lines = vec![String::new()];
+    let mut run = String::new();
+    let mut style = ("", "");
+    let flush = |run: &mut String, style: (&str, &str), line: &mut String| {
+        if run.is_empty() {
+            return;
+        }
+        let text = escape(run);
+        let text = match style.0 {
+            "" => text,
+            class => format!("<span class=\"{}\">{}</span>", class, text),
+        };
+        match style.1 {
+            "" => line.push_str(&text),
+            mark => line.push_str(&format!("<span class=\"{}\">{}</span>", mark, text)),
+        }
+        run.clear();
+    };
+    for (i, &c) in content.iter().enumerate() {
+        let line = lines.last_mut().unwrap();
+        if c == '\n' {
+            flush(&mut run, style, line);
+            lines.push(String::new());
+            style = ("", "");
+            continue;
+        }
+        // Spaces continue the run before them on the line
+        let class = if c.is_whitespace() {
+            style.0
+        } else {
+            classes[i]
+        };
+        if (class, marks[i]) != style {
+            flush(&mut run, style, line);
+            style = (class, marks[i]);
+        }
+        run.push(c);
+    }
+    flush(&mut run, style, lines.last_mut().unwrap());
+    // A diagnostic at the end of input marks the position after the last character
+    if !marks[content.len()].is_empty() {
+        let mark = format!("<span class=\"{}\"> </span>", marks[content.len()]);
+        lines.last_mut().unwrap().push_str(&mark);
+    }
+    if content.last() == Some(&'\n') && lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+/// Start of a page with the stylesheet, up to the opening `<body>`.
+fn page_start(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n",
+        title, STYLE
+    )
+}
+/// `Level[code]: message` followed by the notes on lines of their own.
+fn message(level: &str, e: &OffsetError) -> String {
+    let mut message = format!("{}[{}]: {}", level, e.code, escape(&e.msg));
+    for note in &e.notes {
+        message.push_str(&format!("\n{}: {}", note.level.as_str(), escape(&note.msg)));
+    }
+    message
+}
+
+/// Render a standalone HTML page of the source with syntax highlighting,
+/// the diagnostics under the offending lines as in the listing.
+pub fn to_html(sources: &SourceMap, file: FileId, result: &ParseResult) -> String {
+    let errors = &result.errors;
+    let diagnostics: Vec<_> = errors
+        .sorted()
+        .into_iter()
+        .filter(|(_, e)| e.file == file)
+        .map(|(level, e)| (level, e.span.to_line_col(sources, file), e))
+        .collect();
+    let name = escape(sources.name(file));
+    let mut html = page_start(&name);
+    html.push_str(&format!("<h1>{}</h1>\n<table>\n", name));
+    let mut rest = diagnostics.iter().peekable();
+    for (i, content) in highlight(sources, file, result).iter().enumerate() {
+        let line = i + 1;
+        html.push_str(&format!(
+            "<tr><td class=\"ln\">{}</td><td>{}</td></tr>\n",
+            line, content
+        ));
+        while let Some((level, _, e)) = rest.next_if(|(_, (l, _), _)| *l == line) {
+            html.push_str(&format!(
+                "<tr class=\"{}\"><td></td><td>{}</td></tr>\n",
+                level.to_lowercase(),
+                message(level, e)
+            ));
+        }
+    }
+    for (level, _, e) in rest {
+        html.push_str(&format!(
+            "<tr class=\"{}\"><td></td><td>{}[{}]: {}</td></tr>\n",
+            level.to_lowercase(),
+            level,
+            e.code,
+            escape(&e.msg)
+        ));
+    }
+    html.push_str(&format!(
+        "</table>\n<p>Errors: {}, Warnings: {}</p>\n</body>\n</html>\n",
+        errors.error_count(),
+        errors.warning_count()
+    ));
+    html
+}
+
+/// Source lines shown before and after the line of a diagnostic in the report.
+const CONTEXT: usize = 2;
+
+/// Render a standalone HTML report of the diagnostics of the files.
+/// A summary links to each diagnostic, shown with its message and notes above
+/// the highlighted source lines around it.
+pub fn to_report(sources: &SourceMap, results: &[(FileId, &ParseResult)]) -> String {
+    let error_count: usize = results.iter().map(|(_, r)| r.errors.error_count()).sum();
+    let warning_count: usize = results.iter().map(|(_, r)| r.errors.warning_count()).sum();
+    let mut summary = format!(
+        "<h1>Diagnostics</h1>\n<p>Files: {}, Errors: {}, Warnings: {}</p>\n<ul>\n",
+        results.len(),
+        error_count,
+        warning_count
+    );
+    let mut details = String::new();
+    for (i, (file, result)) in results.iter().enumerate() {
+        let name = escape(sources.name(*file));
+        let errors = &result.errors;
+        let status = match (errors.error_count(), errors.warning_count()) {
+            (0, 0) => "ok".to_string(),
+            (e, w) => format!("{} error(s), {} warning(s)", e, w),
+        };
+        summary.push_str(&format!(
+            "<li><a href=\"#file-{}\">{}</a>: {}\n<ul>\n",
+            i, name, status
+        ));
+        details.push_str(&format!("<h2 id=\"file-{}\">{}</h2>\n", i, name));
+        let lines = highlight(sources, *file, result);
+        for (j, (level, e)) in errors.sorted().into_iter().enumerate() {
+            let (line, col) = e.span.to_line_col(sources, *file);
+            let id = format!("d-{}-{}", i, j);
+            let class = level.to_lowercase();
+            summary.push_str(&format!(
+                "<li class=\"{}\"><a href=\"#{}\">{}:{}</a> {}[{}]: {}</li>\n",
+                class,
+                id,
+                line,
+                col,
+                level,
+                e.code,
+                escape(&e.msg)
+            ));
+            details.push_str(&format!(
+                "<p id=\"{}\" class=\"{}\">[{}:{}:{}] {}</p>\n<table>\n",
+                id,
+                class,
+                name,
+                line,
+                col,
+                message(level, e).replace('\n', "<br>\n")
+            ));
+            let first = line.saturating_sub(CONTEXT).max(1);
+            let last = (line + CONTEXT).min(lines.len());
+            for (k, content) in lines.iter().enumerate().take(last).skip(first - 1) {
+                details.push_str(&format!(
+                    "<tr><td class=\"ln\">{}</td><td>{}</td></tr>\n",
+                    k + 1,
+                    content
+                ));
+            }
+            details.push_str("</table>\n");
+        }
+        if errors.sorted().is_empty() {
+            details.push_str("<p>No diagnostics</p>\n");
+        }
+        summary.push_str("</ul>\n</li>\n");
+    }
+    summary.push_str("</ul>\n");
+    format!(
+        "{}{}{}</body>\n</html>\n",
+        page_start("Diagnostics"),
+        summary,
+        details
+    )
+}
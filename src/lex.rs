@@ -1,11 +1,24 @@
+use clap::ValueEnum;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use ts_rs::TS;
 
-use crate::error::ErrorRecorder;
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+use crate::{
+    codes::Code,
+    error::{ErrorRecorder, Fix},
+    source_map::Span,
+    table::Tables,
+};
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, JsonSchema, TS, PartialEq)]
 /// Token in PL/0 Like language.
 /// Ignore case.
 pub enum TokenEnum {
     // struct keywords
+    Program,
+    /// `const i = n;` constant declarations, before the type block
+    Const,
+    /// `type i = t;` alias declarations, before the var block
+    Type,
     Var,
     If,
     Then,
@@ -14,18 +27,45 @@ pub enum TokenEnum {
     Do,
     Begin,
     End,
+    /// `call p`, procedure call statement
+    Call,
+    /// `read(v0, v1)`, built-in input statement
+    Read,
+    /// `write(e0, e1)`, built-in output statement
+    Write,
+    /// `procedure p(i: t); var ...; begin ... end;`
+    Procedure,
+    /// `function f(i: t): t; var ...; begin ... end;`
+    Function,
+    /// `case e of l: s; ... end`
+    Case,
+    /// `array [...] of t` and `case e of`
+    Of,
 
     // operator keywords
     And,
     Or,
+    /// `odd e`, true if the integer operand is odd
+    Odd,
+    /// `not e`, negation of the Bool operand
+    Not,
 
     // type keywords
     Integer,
     Longint,
     Bool,
     Real,
+    Char,
+    /// `record i: t; ... end`
+    Record,
+    /// `array [l0..h0, l1..h1] of t`
+    Array,
 
-    // operators +|-|*|/|:=|<|>|<>|>=|<=|==|:|(|)
+    // literal keywords
+    True,
+    False,
+
+    // operators +|-|*|/|:=|<|>|<>|>=|<=|==|:|(|)|.|..|[|]
     /// +
     Add,
     /// -
@@ -46,7 +86,7 @@ pub enum TokenEnum {
     Ge,
     /// <=
     Le,
-    /// ==
+    /// ==, also `=` in a type declaration
     Eq,
     /// :
     Colon,
@@ -54,6 +94,14 @@ pub enum TokenEnum {
     LParen,
     /// )
     RParen,
+    /// ., record field access
+    Dot,
+    /// .., subrange bounds
+    DotDot,
+    /// [
+    LBracket,
+    /// ]
+    RBracket,
 
     // struct symbols
     /// ,
@@ -61,22 +109,246 @@ pub enum TokenEnum {
     /// ;
     SemiColon,
 
+    // I/O shorthand, extended dialect only
+    /// ?, `? x` reads into x
+    Question,
+    /// !, `! e` writes e
+    Exclamation,
+
     // Literals and Identifiers
     /// Identifier [a-zA-Z][a-zA-Z0-9]*, case insensitive
     Identifier,
     /// Integer literal [1-9][0-9]*|0, no leading 0
     IntLiteral,
+    /// Real literal with digits on both sides of the decimal point, e.g. `12.5`
+    RealLiteral,
+    /// Character literal of a single character, e.g. `'a'`, a quote is doubled: `''''`
+    CharLiteral,
+}
+impl TokenEnum {
+    /// The classic token code (种别码).
+    /// Codes are 1-based and never change once published: tokens added to the
+    /// language get the next free code, wherever they are declared in `TokenEnum`.
+    pub fn code(self) -> usize {
+        use TokenEnum::*;
+        match self {
+            Var => 1,
+            If => 2,
+            Then => 3,
+            Else => 4,
+            While => 5,
+            Do => 6,
+            Begin => 7,
+            End => 8,
+            And => 9,
+            Or => 10,
+            Integer => 11,
+            Longint => 12,
+            Bool => 13,
+            Real => 14,
+            Add => 15,
+            Sub => 16,
+            Mul => 17,
+            Div => 18,
+            Assign => 19,
+            Lt => 20,
+            Gt => 21,
+            Ne => 22,
+            Ge => 23,
+            Le => 24,
+            Eq => 25,
+            Colon => 26,
+            LParen => 27,
+            RParen => 28,
+            Comma => 29,
+            SemiColon => 30,
+            Identifier => 31,
+            IntLiteral => 32,
+            Program => 33,
+            Odd => 34,
+            Call => 35,
+            Question => 36,
+            Exclamation => 37,
+            Type => 38,
+            Record => 39,
+            Dot => 40,
+            DotDot => 41,
+            Of => 42,
+            Array => 43,
+            LBracket => 44,
+            RBracket => 45,
+            Procedure => 46,
+            Function => 47,
+            Const => 48,
+            Case => 49,
+            Read => 50,
+            Write => 51,
+            True => 52,
+            False => 53,
+            Not => 54,
+            RealLiteral => 55,
+            Char => 56,
+            CharLiteral => 57,
+        }
+    }
+    /// Highlighting category: `keyword`, `literal`, `identifier` or `operator`.
+    pub fn category(self) -> &'static str {
+        use TokenEnum::*;
+        match self {
+            IntLiteral | RealLiteral | CharLiteral | True | False => "literal",
+            Identifier => "identifier",
+            _ if KEYWORDS.iter().any(|(_, t)| *t == self) => "keyword",
+            _ => "operator",
+        }
+    }
+}
+/// Keywords, matched case-insensitively.
+pub const KEYWORDS: &[(&str, TokenEnum)] = &[
+    ("program", TokenEnum::Program),
+    ("const", TokenEnum::Const),
+    ("type", TokenEnum::Type),
+    ("var", TokenEnum::Var),
+    ("if", TokenEnum::If),
+    ("then", TokenEnum::Then),
+    ("else", TokenEnum::Else),
+    ("while", TokenEnum::While),
+    ("do", TokenEnum::Do),
+    ("begin", TokenEnum::Begin),
+    ("end", TokenEnum::End),
+    ("call", TokenEnum::Call),
+    ("read", TokenEnum::Read),
+    ("write", TokenEnum::Write),
+    ("procedure", TokenEnum::Procedure),
+    ("function", TokenEnum::Function),
+    ("case", TokenEnum::Case),
+    ("of", TokenEnum::Of),
+    ("and", TokenEnum::And),
+    ("or", TokenEnum::Or),
+    ("odd", TokenEnum::Odd),
+    ("not", TokenEnum::Not),
+    ("integer", TokenEnum::Integer),
+    ("longint", TokenEnum::Longint),
+    ("bool", TokenEnum::Bool),
+    ("real", TokenEnum::Real),
+    ("char", TokenEnum::Char),
+    ("record", TokenEnum::Record),
+    ("array", TokenEnum::Array),
+    ("true", TokenEnum::True),
+    ("false", TokenEnum::False),
+];
+/// Operators and struct symbols.
+/// Longer symbols come first, so that `:=` is matched before `:`.
+pub const SYMBOLS: &[(&str, TokenEnum)] = &[
+    (":=", TokenEnum::Assign),
+    ("..", TokenEnum::DotDot),
+    ("<>", TokenEnum::Ne),
+    (">=", TokenEnum::Ge),
+    ("<=", TokenEnum::Le),
+    ("==", TokenEnum::Eq),
+    ("+", TokenEnum::Add),
+    ("-", TokenEnum::Sub),
+    ("*", TokenEnum::Mul),
+    ("/", TokenEnum::Div),
+    ("<", TokenEnum::Lt),
+    (">", TokenEnum::Gt),
+    (":", TokenEnum::Colon),
+    ("(", TokenEnum::LParen),
+    (")", TokenEnum::RParen),
+    (".", TokenEnum::Dot),
+    ("[", TokenEnum::LBracket),
+    ("]", TokenEnum::RBracket),
+    (",", TokenEnum::Comma),
+    (";", TokenEnum::SemiColon),
+    ("?", TokenEnum::Question),
+    ("!", TokenEnum::Exclamation),
+];
+/// Length of the longest keyword, longer identifiers are never keywords.
+const MAX_KEYWORD_LEN: usize = {
+    let mut max = 0;
+    let mut i = 0;
+    while i < KEYWORDS.len() {
+        if KEYWORDS[i].0.len() > max {
+            max = KEYWORDS[i].0.len();
+        }
+        i += 1;
+    }
+    max
+};
+/// Look up a keyword, ignoring case.
+/// Keywords are ASCII, so the identifier is lowercased in a buffer on the stack
+/// and matched on its bytes. The arms list the same keywords as `KEYWORDS`.
+pub fn keyword(ident: &str) -> Option<TokenEnum> {
+    let mut buf = [0; MAX_KEYWORD_LEN];
+    let lower = buf.get_mut(..ident.len())?;
+    lower.copy_from_slice(ident.as_bytes());
+    lower.make_ascii_lowercase();
+    let token = match &*lower {
+        b"program" => TokenEnum::Program,
+        b"const" => TokenEnum::Const,
+        b"type" => TokenEnum::Type,
+        b"var" => TokenEnum::Var,
+        b"if" => TokenEnum::If,
+        b"then" => TokenEnum::Then,
+        b"else" => TokenEnum::Else,
+        b"while" => TokenEnum::While,
+        b"do" => TokenEnum::Do,
+        b"begin" => TokenEnum::Begin,
+        b"end" => TokenEnum::End,
+        b"call" => TokenEnum::Call,
+        b"read" => TokenEnum::Read,
+        b"write" => TokenEnum::Write,
+        b"procedure" => TokenEnum::Procedure,
+        b"function" => TokenEnum::Function,
+        b"case" => TokenEnum::Case,
+        b"of" => TokenEnum::Of,
+        b"and" => TokenEnum::And,
+        b"or" => TokenEnum::Or,
+        b"odd" => TokenEnum::Odd,
+        b"not" => TokenEnum::Not,
+        b"integer" => TokenEnum::Integer,
+        b"longint" => TokenEnum::Longint,
+        b"bool" => TokenEnum::Bool,
+        b"real" => TokenEnum::Real,
+        b"char" => TokenEnum::Char,
+        b"record" => TokenEnum::Record,
+        b"array" => TokenEnum::Array,
+        b"true" => TokenEnum::True,
+        b"false" => TokenEnum::False,
+        _ => return None,
+    };
+    Some(token)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, TS)]
 pub struct Token {
+    /// Character offset in the source
     pub offset: usize,
+    /// End offset in the source, which may differ from `offset + content.len()`
+    /// as the content of numbers is normalized.
+    #[serde(default)]
+    pub end: usize,
+    /// Line of the start, 1-based
+    #[serde(default)]
+    pub line: usize,
+    /// Column of the start in characters, 1-based
+    #[serde(default)]
+    pub col: usize,
+    /// Source text of the token, numbers have leading zeros and separators removed
     pub content: String,
     pub token: TokenEnum,
+    /// Index into the identifier table or the constant table.
+    /// Only present for `Identifier` and the literals.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+}
+impl Token {
+    pub fn span(&self) -> Span {
+        Span::new(self.offset, self.end)
+    }
 }
 /// To simplify the parser.
 enum NextToken {
-    EOF,
+    Eof,
     Blank,
     Type(TokenEnum),
     WithContent(TokenEnum, String),
@@ -92,22 +364,72 @@ impl From<(TokenEnum, String)> for NextToken {
     }
 }
 
+/// Language dialect accepted by the lexer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Dialect {
+    /// The language of the lab assignment
+    #[default]
+    Standard,
+    /// Extensions: `_` digit separators, `?` and `!` I/O shorthand
+    Extended,
+}
+/// Input size limits, unlimited by default.
+/// Exceeding a limit is an error, so that pathological inputs are rejected early.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct Limits {
+    /// Maximum input size in bytes, nothing is lexed beyond it
+    #[clap(long)]
+    pub max_file_size: Option<usize>,
+    /// Maximum line length in characters
+    #[clap(long)]
+    pub max_line_length: Option<usize>,
+    /// Maximum identifier length in characters
+    #[clap(long)]
+    pub max_identifier_length: Option<usize>,
+    /// Maximum integer literal length in characters
+    #[clap(long)]
+    pub max_number_length: Option<usize>,
+    /// Maximum number of tokens, lexing stops beyond it
+    #[clap(long)]
+    pub max_tokens: Option<usize>,
+}
+#[derive(Debug, Clone, Default)]
+pub struct LexOptions {
+    pub dialect: Dialect,
+    pub limits: Limits,
+}
+
 #[derive(Debug, Clone)]
 pub struct CharStream {
     input: Vec<char>,
     pos: usize,
+    /// Offsets of the line starts, for the line and column of the tokens.
+    line_starts: Vec<usize>,
+    options: LexOptions,
 }
 impl CharStream {
-    pub fn new(input: &str) -> Self {
+    pub fn new(input: &str, options: LexOptions) -> Self {
+        let input: Vec<char> = input.chars().collect();
+        let newlines = input.iter().enumerate().filter(|(_, c)| **c == '\n');
+        let line_starts = std::iter::once(0)
+            .chain(newlines.map(|(i, _)| i + 1))
+            .collect();
         Self {
-            input: input.chars().collect(),
+            input,
             pos: 0,
+            line_starts,
+            options,
         }
     }
-    pub fn peek(&self) -> Option<char> {
+    /// Line and column of the offset, 1-based.
+    fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        (line, offset - self.line_starts[line - 1] + 1)
+    }
+    fn peek(&self) -> Option<char> {
         self.input.get(self.pos).cloned()
     }
-    pub fn next(&mut self) -> Option<char> {
+    fn next(&mut self) -> Option<char> {
         match self.peek() {
             Some(c) => {
                 self.pos += 1;
@@ -120,7 +442,7 @@ impl CharStream {
     fn next_token_base(&mut self, errors: &mut ErrorRecorder) -> NextToken {
         let c = match self.peek() {
             Some(c) => c,
-            None => return NextToken::EOF,
+            None => return NextToken::Eof,
         };
         if c.is_whitespace() {
             self.next();
@@ -137,137 +459,358 @@ impl CharStream {
                     break;
                 }
             }
-            match ident.to_lowercase().as_str() {
-                "var" => TokenEnum::Var,
-                "integer" => TokenEnum::Integer,
-                "longint" => TokenEnum::Longint,
-                "bool" => TokenEnum::Bool,
-                "real" => TokenEnum::Real,
-                "if" => TokenEnum::If,
-                "then" => TokenEnum::Then,
-                "else" => TokenEnum::Else,
-                "while" => TokenEnum::While,
-                "do" => TokenEnum::Do,
-                "begin" => TokenEnum::Begin,
-                "end" => TokenEnum::End,
-                "and" => TokenEnum::And,
-                "or" => TokenEnum::Or,
-                _ => TokenEnum::Identifier,
-            }
-            .into()
+            keyword(&ident).unwrap_or(TokenEnum::Identifier).into()
         } else if c.is_numeric() {
-            let mut num = String::new();
-            while self.peek().map_or(false, |c| c.is_numeric()) {
-                num.push(self.next().unwrap());
+            let extended = self.options.dialect == Dialect::Extended;
+            let mut num = self.digits(errors);
+            let mut token = TokenEnum::IntLiteral;
+            // `1..2` is a subrange of integers
+            let after_dot = self.input.get(self.pos + 1).copied();
+            if self.peek() == Some('.') && after_dot.is_some_and(|c| c.is_numeric()) {
+                self.next();
+                token = TokenEnum::RealLiteral;
+                num.push('.');
+                num += &self.digits(errors);
+                let after_dot = self.input.get(self.pos + 1);
+                if self.peek() == Some('.') && after_dot.is_some_and(|c| c.is_numeric()) {
+                    while self
+                        .peek()
+                        .is_some_and(|c| c.is_numeric() || c == '.' || c == '_')
+                    {
+                        self.next();
+                    }
+                    errors.error(
+                        Span::new(start, self.pos),
+                        Code::MalformedNumber,
+                        "Malformed number, a number has at most one decimal point",
+                    );
+                    return NextToken::Blank;
+                }
+            } else if self.peek() == Some('.') && after_dot != Some('.') {
+                self.next();
+                let text: String = self.input[start..self.pos].iter().collect();
+                let fix = Fix {
+                    span: Span::new(start, self.pos),
+                    replacement: format!("{}0", text),
+                };
+                errors.error_with_fix(
+                    fix,
+                    Code::MalformedNumber,
+                    "Expected digits after the decimal point",
+                );
+                return NextToken::WithContent(TokenEnum::RealLiteral, format!("{}.0", num));
+            }
+            if !extended && self.peek() == Some('_') {
+                let separator = self.pos;
+                while self.peek().is_some_and(|c| c.is_numeric() || c == '_') {
+                    self.next();
+                }
+                errors.error(
+                    Span::new(separator, self.pos),
+                    Code::ExtendedOnly,
+                    "Digit separator `_` is only allowed with `--dialect extended`",
+                );
+                return NextToken::Blank;
             }
-            if self.peek().map_or(false, |c| c.is_ascii_alphabetic()) {
+            if self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
                 errors.error(
-                    start,
-                    &format!(
+                    Span::new(start, self.pos),
+                    Code::LeadingDigit,
+                    format!(
                         "Identifier should not start with a number. Consider removing `{}`",
                         num
                     ),
                 );
                 return NextToken::Blank;
             }
-            if num.starts_with('0') && num.len() > 1 {
-                errors.warning(start, "Number should not start with 0");
-                // Remove leading zeros
-                num = num.trim_start_matches('0').to_string();
-                if num.is_empty() {
-                    num.push('0');
-                }
-                return NextToken::WithContent(TokenEnum::IntLiteral, num);
+            let int_len = num.find('.').unwrap_or(num.len());
+            if num.starts_with('0') && int_len > 1 {
+                errors.warning(
+                    Span::new(start, self.pos),
+                    Code::LeadingZeros,
+                    "Number should not start with 0",
+                );
+                // Remove leading zeros of the integer part
+                let int = num[..int_len].trim_start_matches('0');
+                let int = if int.is_empty() { "0" } else { int };
+                num = format!("{}{}", int, &num[int_len..]);
+                return NextToken::WithContent(token, num);
             }
-            TokenEnum::IntLiteral.into()
-        } else {
+            if num.chars().count() != self.pos - start {
+                // Separators removed
+                return NextToken::WithContent(token, num);
+            }
+            token.into()
+        } else if c == '\'' {
             self.next();
-            match c {
-                '+' => TokenEnum::Add.into(),
-                '-' => TokenEnum::Sub.into(),
-                '*' => TokenEnum::Mul.into(),
-                '/' => {
-                    if self.peek() == Some('/') {
-                        while self.next() != Some('\n') {}
-                        return NextToken::Blank;
+            let mut value = String::new();
+            loop {
+                match self.peek() {
+                    Some('\'') if self.input.get(self.pos + 1) == Some(&'\'') => {
+                        self.pos += 2;
+                        value.push('\'');
                     }
-                    TokenEnum::Div.into()
-                }
-                ':' => {
-                    if self.peek() == Some('=') {
+                    Some('\'') => {
                         self.next();
-                        return TokenEnum::Assign.into();
+                        break;
                     }
-                    TokenEnum::Colon.into()
-                }
-                '<' => {
-                    if self.peek() == Some('>') {
-                        self.next();
-                        return TokenEnum::Ne.into();
-                    } else if self.peek() == Some('=') {
-                        self.next();
-                        return TokenEnum::Le.into();
+                    Some('\n') | None => {
+                        errors.error(
+                            Span::new(start, self.pos),
+                            Code::MalformedCharLiteral,
+                            "Unterminated character literal",
+                        );
+                        return NextToken::Blank;
                     }
-                    TokenEnum::Lt.into()
-                }
-                '>' => {
-                    if self.peek() == Some('=') {
+                    Some(c) => {
                         self.next();
-                        return TokenEnum::Ge.into();
+                        value.push(c);
                     }
-                    TokenEnum::Gt.into()
                 }
-                '=' => {
-                    if self.peek() == Some('=') {
-                        self.next();
-                        return TokenEnum::Eq.into();
-                    }
-                    errors.error(start, "Unexpected operator `=`. Did you mean `==` or `:=`?");
+            }
+            let span = Span::new(start, self.pos);
+            match value.chars().count() {
+                1 => TokenEnum::CharLiteral.into(),
+                0 => {
+                    errors.error(span, Code::MalformedCharLiteral, "Empty character literal");
+                    errors.help("write a quote as `''''`");
+                    NextToken::Blank
+                }
+                _ => {
+                    errors.error(
+                        span,
+                        Code::MalformedCharLiteral,
+                        "Character literal must contain exactly one character",
+                    );
+                    errors.note("strings are not supported");
                     NextToken::Blank
                 }
-                '(' => TokenEnum::LParen.into(),
-                ')' => TokenEnum::RParen.into(),
-                ',' => TokenEnum::Comma.into(),
-                ';' => TokenEnum::SemiColon.into(),
+            }
+        } else if self.starts_with("//") {
+            while !matches!(self.next(), Some('\n') | None) {}
+            NextToken::Blank
+        } else if let Some((open, close)) = [("(*", "*)"), ("{", "}")]
+            .into_iter()
+            .find(|(open, _)| self.starts_with(open))
+        {
+            // Block comments do not nest
+            self.pos += open.len();
+            while !self.starts_with(close) {
+                if self.next().is_none() {
+                    errors.error(
+                        Span::new(start, start + open.len()),
+                        Code::UnterminatedComment,
+                        format!("Unterminated comment, expected `{}`", close),
+                    );
+                    return NextToken::Blank;
+                }
+            }
+            self.pos += close.len();
+            NextToken::Blank
+        } else if let Some((symbol, token)) = SYMBOLS.iter().find(|(s, _)| self.starts_with(s)) {
+            self.pos += symbol.chars().count();
+            if matches!(token, TokenEnum::Question | TokenEnum::Exclamation)
+                && self.options.dialect != Dialect::Extended
+            {
+                errors.error(
+                    Span::new(start, self.pos),
+                    Code::ExtendedOnly,
+                    format!("`{}` is only allowed with `--dialect extended`", symbol),
+                );
+                return NextToken::Blank;
+            }
+            (*token).into()
+        } else {
+            self.next();
+            match c {
+                '_' if self.options.dialect == Dialect::Extended
+                    && self.peek().is_some_and(|c| c.is_numeric()) =>
+                {
+                    errors.error(
+                        Span::new(start, self.pos),
+                        Code::DigitSeparator,
+                        "Digit separator `_` should not start a number",
+                    );
+                    NextToken::Blank
+                }
+                // A single `=` declares a constant or an alias, the parser reports it elsewhere
+                '=' => TokenEnum::Eq.into(),
                 c => {
-                    errors.error(start, &format!("Unexpected character `{}`", c));
+                    errors.error(
+                        Span::new(start, self.pos),
+                        Code::UnexpectedCharacter,
+                        format!("Unexpected character `{}`", c),
+                    );
                     NextToken::Blank
                 }
             }
         }
     }
+    /// Read digits, with `_` separators in the extended dialect.
+    /// Returns the digits without separators.
+    fn digits(&mut self, errors: &mut ErrorRecorder) -> String {
+        let extended = self.options.dialect == Dialect::Extended;
+        let mut num = String::new();
+        while self
+            .peek()
+            .is_some_and(|c| c.is_numeric() || (extended && c == '_'))
+        {
+            let pos = self.pos;
+            let c = self.next().unwrap();
+            if c != '_' {
+                num.push(c);
+            } else if self.peek() == Some('_') {
+                errors.error(
+                    Span::new(pos, self.pos + 1),
+                    Code::DigitSeparator,
+                    "Digit separators `_` should not be doubled",
+                );
+            } else if !self.peek().is_some_and(|c| c.is_numeric()) {
+                errors.error(
+                    Span::new(pos, self.pos),
+                    Code::DigitSeparator,
+                    "Digit separator `_` should not end a number",
+                );
+            }
+        }
+        num
+    }
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.input.get(self.pos + i) == Some(&c))
+    }
+
+    fn check_length(&self, start: usize, token: TokenEnum, errors: &mut ErrorRecorder) {
+        let limits = &self.options.limits;
+        let (limit, kind) = match token {
+            TokenEnum::Identifier => (limits.max_identifier_length, "Identifier"),
+            TokenEnum::IntLiteral | TokenEnum::RealLiteral => (limits.max_number_length, "Number"),
+            _ => return,
+        };
+        let len = self.pos - start;
+        if let Some(limit) = limit.filter(|limit| len > *limit) {
+            errors.error(
+                Span::new(start, self.pos),
+                Code::LimitExceeded,
+                format!(
+                    "{} is {} characters long, exceeding the limit of {}",
+                    kind, len, limit
+                ),
+            );
+        }
+    }
 
     pub fn next_token(&mut self, errors: &mut ErrorRecorder) -> Option<Token> {
         loop {
             let start = self.pos;
             let next_token = self.next_token_base(errors);
-            match next_token {
-                NextToken::EOF => return None,
-                NextToken::Blank => {}
-                NextToken::Type(token) => {
-                    let content = self.input[start..self.pos].iter().collect();
-                    return Some(Token {
-                        offset: start,
-                        content,
-                        token,
-                    });
-                }
-                NextToken::WithContent(token, content) => {
-                    return Some(Token {
-                        offset: start,
-                        content,
-                        token,
-                    });
+            if let NextToken::Type(token) | NextToken::WithContent(token, _) = &next_token {
+                self.check_length(start, *token, errors);
+            }
+            let (token, content) = match next_token {
+                NextToken::Eof => return None,
+                NextToken::Blank => continue,
+                NextToken::Type(token) => (token, self.input[start..self.pos].iter().collect()),
+                NextToken::WithContent(token, content) => (token, content),
+            };
+            let (line, col) = self.line_col(start);
+            return Some(Token {
+                offset: start,
+                end: self.pos,
+                line,
+                col,
+                content,
+                token,
+                index: None,
+            });
+        }
+    }
+}
+/// Lexer yielding the tokens on demand, interning them in the identifier/constant tables.
+/// Input limits are checked when the lexer is created, an input exceeding the size limit
+/// yields no tokens.
+pub struct Lexer<'a> {
+    stream: CharStream,
+    errors: &'a mut ErrorRecorder,
+    tables: Tables,
+    max_tokens: Option<usize>,
+    count: usize,
+    done: bool,
+}
+impl<'a> Lexer<'a> {
+    pub fn new(input: &str, options: &LexOptions, errors: &'a mut ErrorRecorder) -> Self {
+        let limits = &options.limits;
+        let mut done = false;
+        if let Some(limit) = limits.max_file_size.filter(|limit| input.len() > *limit) {
+            errors.error(
+                0,
+                Code::LimitExceeded,
+                format!(
+                    "Input is {} bytes, exceeding the limit of {}",
+                    input.len(),
+                    limit
+                ),
+            );
+            done = true;
+        } else if let Some(limit) = limits.max_line_length {
+            let mut offset = 0;
+            for line in input.split('\n') {
+                let len = line.chars().count();
+                if len > limit {
+                    errors.error(
+                        Span::new(offset + limit, offset + len),
+                        Code::LimitExceeded,
+                        format!(
+                            "Line is {} characters long, exceeding the limit of {}",
+                            len, limit
+                        ),
+                    );
                 }
+                offset += len + 1;
             }
         }
+        Self {
+            stream: CharStream::new(if done { "" } else { input }, options.clone()),
+            errors,
+            tables: Tables::default(),
+            max_tokens: limits.max_tokens,
+            count: 0,
+            done,
+        }
+    }
+    /// The tables the yielded tokens refer to.
+    pub fn into_tables(self) -> Tables {
+        self.tables
     }
 }
-pub fn lex(input: &str, errors: &mut ErrorRecorder) -> Vec<Token> {
-    let mut stream = CharStream::new(input);
-    let mut tokens = Vec::new();
-    while let Some(token) = stream.next_token(errors) {
-        tokens.push(token);
+impl Iterator for Lexer<'_> {
+    type Item = Token;
+    fn next(&mut self) -> Option<Token> {
+        if self.done {
+            return None;
+        }
+        let Some(mut token) = self.stream.next_token(self.errors) else {
+            self.done = true;
+            return None;
+        };
+        if self.max_tokens == Some(self.count) {
+            self.errors.error(
+                token.span(),
+                Code::LimitExceeded,
+                format!("Too many tokens, exceeding the limit of {}", self.count),
+            );
+            self.done = true;
+            return None;
+        }
+        self.count += 1;
+        token.index = self.tables.intern(&token);
+        Some(token)
     }
-    tokens
+}
+/// Returns the tokens and the identifier/constant tables they refer to.
+pub fn lex(input: &str, options: &LexOptions, errors: &mut ErrorRecorder) -> (Vec<Token>, Tables) {
+    let mut lexer = Lexer::new(input, options, errors);
+    let tokens = lexer.by_ref().collect();
+    (tokens, lexer.into_tables())
 }
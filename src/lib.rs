@@ -0,0 +1,46 @@
+//! Lexer and checker of a PL/0-like language.
+//! ```ignore
+//! let mut sources = SourceMap::new();
+//! let file = sources.add("a.in", "var i: integer; begin i := 1 end".to_string());
+//! let result = parse(&sources, file, &LexOptions::default());
+//! result.errors.print_with(&sources);
+//! let statements = result.ast.map_or(0, |ast| ast.statement_count());
+//! ```
+//! `lex` only tokenizes, `parse` also checks the declarations and the program block,
+//! keeping everything recovered after an error. `Session` drives the checks and the
+//! artifacts of the command line tool.
+pub mod ast;
+pub mod codes;
+pub mod derivation;
+pub mod diagnostics;
+mod dump;
+mod earley;
+pub mod error;
+mod format;
+pub mod glob;
+pub mod grammar;
+mod html;
+pub mod lex;
+mod listing;
+pub mod lsp;
+pub mod msgpack;
+pub mod output;
+mod pairs;
+pub mod parse;
+mod parse_tree;
+pub mod pre_grammar;
+mod protobuf;
+pub mod repl;
+pub mod selftest;
+pub mod session;
+pub mod source_map;
+pub mod table;
+pub mod testcase;
+pub mod tmlanguage;
+pub mod typecheck;
+pub mod typescript;
+
+pub use error::ErrorRecorder;
+pub use lex::{lex, LexOptions, Lexer, Token, TokenEnum};
+pub use pre_grammar::{parse, ParseResult};
+pub use source_map::{FileId, SourceMap};
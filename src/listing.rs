@@ -0,0 +1,102 @@
+use crate::{
+    pre_grammar::ParseResult,
+    source_map::{FileId, SourceMap},
+};
+
+/// Render a classic compiler listing.
+/// ```plaintext
+/// Listing of file_name
+///
+///    1  var
+///    2      i j: integer;
+///             ^
+///             Error[E022]: Missing comma
+/// ...
+///
+/// Symbols:
+///     i: Integer
+///
+/// Tokens: 12
+/// Errors: 1, Warnings: 0
+/// ```
+/// Diagnostics are interleaved under the offending lines,
+/// diagnostics after the last line (e.g. unexpected EOF) are listed at the end.
+pub fn to_listing(sources: &SourceMap, file: FileId, parse_result: &ParseResult) -> String {
+    let errors = &parse_result.errors;
+    let diagnostics: Vec<_> = errors
+        .sorted()
+        .into_iter()
+        .filter(|(_, e)| e.file == file)
+        .map(|(level, e)| (level, e.span.to_line_col(sources, file), e))
+        .collect();
+    let mut result = format!("Listing of {}\n\n", sources.name(file));
+    let mut rest = diagnostics.iter().peekable();
+    let mut line = 1;
+    while let Some(content) = sources.line(file, line) {
+        result.push_str(&format!("{:>4}  {}\n", line, content.trim_end()));
+        while let Some((level, (_, col), e)) = rest.next_if(|(_, (l, _), _)| *l == line) {
+            let indent = " ".repeat(6 + col - 1);
+            let caret = "^".repeat(e.span.width_on_line(sources, file));
+            result.push_str(&format!(
+                "{}{}\n{}{}[{}]: {}\n",
+                indent, caret, indent, level, e.code, e.msg
+            ));
+            for note in &e.notes {
+                result.push_str(&format!(
+                    "{}{}: {}\n",
+                    indent,
+                    note.level.as_str(),
+                    note.msg
+                ));
+            }
+        }
+        line += 1;
+    }
+    for (level, _, e) in rest {
+        result.push_str(&format!("      {}[{}]: {}\n", level, e.code, e.msg));
+    }
+    if let Some(program) = &parse_result.program {
+        result.push_str(&format!("\nProgram: {}\n", program));
+    }
+    if !parse_result.consts.is_empty() {
+        result.push_str("\nConstants:\n");
+        for (name, value) in &parse_result.consts {
+            result.push_str(&format!("    {} = {}\n", name, value));
+        }
+    }
+    if !parse_result.types.is_empty() {
+        result.push_str("\nTypes:\n");
+        for (name, type_enum) in &parse_result.types {
+            result.push_str(&format!("    {} = {}\n", name, type_enum));
+        }
+    }
+    if !parse_result.enumerators.is_empty() {
+        result.push_str("\nEnumerators:\n");
+        for (name, (type_enum, ordinal)) in &parse_result.enumerators {
+            result.push_str(&format!("    {} = {} of {}\n", name, ordinal, type_enum));
+        }
+    }
+    result.push_str("\nSymbols:\n");
+    for (name, type_enum) in &parse_result.vars {
+        result.push_str(&format!("    {}: {}\n", name, type_enum));
+    }
+    if !parse_result.callables.is_empty() {
+        result.push_str("\nProcedures:\n");
+        for (name, callable) in &parse_result.callables {
+            result.push_str(&format!("    {}{}\n", name, callable));
+            for (local, type_enum) in &callable.locals {
+                result.push_str(&format!("        {}: {}\n", local, type_enum));
+            }
+        }
+    }
+    result.push_str(&format!("\nTokens: {}\n", parse_result.tokens.len()));
+    if let Some(ast) = &parse_result.ast {
+        result.push_str(&format!("Statements: {}\n", ast.statement_count()));
+    }
+    result.push_str(&format!(
+        "Errors: {}, Warnings: {}\n",
+        errors.error_count(),
+        errors.warning_count()
+    ));
+    result
+}
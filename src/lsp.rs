@@ -0,0 +1,200 @@
+use std::io::{BufRead, Write};
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+
+use crate::{
+    codes::WarningLevels,
+    error::DisplayOptions,
+    lex::LexOptions,
+    pre_grammar,
+    source_map::{FileId, SourceMap, Span},
+};
+
+/// Read a message framed by a `Content-Length` header, None at end of input.
+/// A body that is not JSON is returned as the parse error, to be answered
+/// without ending the server.
+fn read_message(input: &mut impl BufRead) -> Result<Option<serde_json::Result<Value>>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+    let length = length.ok_or_else(|| anyhow!("Missing Content-Length header"))?;
+    let mut content = vec![0; length];
+    input.read_exact(&mut content)?;
+    Ok(Some(serde_json::from_slice(&content)))
+}
+fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let content = serde_json::to_string(message)?;
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    output.flush()?;
+    Ok(())
+}
+/// LSP position of the character offset, the character counts UTF-16 code units.
+fn position(sources: &SourceMap, file: FileId, offset: usize) -> Value {
+    let (line, col) = sources.line_col(file, offset);
+    let content = sources.line(file, line).unwrap_or_default();
+    let character: usize = content.chars().take(col - 1).map(char::len_utf16).sum();
+    json!({ "line": line - 1, "character": character })
+}
+/// LSP range of the span, a span at EOF being clamped to the end of the document.
+fn range(sources: &SourceMap, file: FileId, span: Span) -> Value {
+    let span = span.clamp_to(sources, file);
+    json!({
+        "start": position(sources, file, span.start),
+        "end": position(sources, file, span.end),
+    })
+}
+/// `textDocument/publishDiagnostics` parameters with the diagnostics of the document,
+/// the warnings at the levels selected by `-W`, `-A` and `-D`.
+fn diagnostics(uri: &str, text: &str, options: &LexOptions, warnings: &WarningLevels) -> Value {
+    let mut sources = SourceMap::new();
+    let file = sources.add(uri, text);
+    let mut result = pre_grammar::parse(&sources, file, options);
+    result.errors.set_options(DisplayOptions {
+        warnings: warnings.clone(),
+        ..DisplayOptions::default()
+    });
+    let diagnostics: Vec<_> = result
+        .errors
+        .sorted()
+        .into_iter()
+        .map(|(level, e)| {
+            let mut message = e.msg.clone();
+            for note in &e.notes {
+                message.push_str(&format!("\n{}: {}", note.level.as_str(), note.msg));
+            }
+            let related: Vec<_> = e
+                .labels
+                .iter()
+                .map(|label| {
+                    json!({
+                        "location": {
+                            "uri": uri,
+                            "range": range(&sources, file, label.span),
+                        },
+                        "message": label.msg,
+                    })
+                })
+                .collect();
+            json!({
+                "range": range(&sources, file, e.span),
+                "severity": if level == "Error" { 1 } else { 2 },
+                "code": e.code.as_str(),
+                "source": env!("CARGO_PKG_NAME"),
+                "message": message,
+                "relatedInformation": related,
+            })
+        })
+        .collect();
+    json!({ "uri": uri, "diagnostics": diagnostics })
+}
+fn publish(output: &mut impl Write, params: Value) -> Result<()> {
+    write_message(
+        output,
+        &json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": params,
+        }),
+    )
+}
+
+/// Serve the Language Server Protocol over the streams until the `exit` notification
+/// or the end of input. A message that is not JSON is answered with a parse error.
+/// Documents are synchronized in full, and their diagnostics are published
+/// whenever they are opened or changed.
+pub fn serve(
+    mut input: impl BufRead,
+    mut output: impl Write,
+    options: &LexOptions,
+    warnings: &WarningLevels,
+) -> Result<()> {
+    while let Some(message) = read_message(&mut input)? {
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                write_message(
+                    &mut output,
+                    &json!({
+                        "jsonrpc": "2.0",
+                        "id": null,
+                        "error": { "code": -32700, "message": format!("Parse error: {}", e) },
+                    }),
+                )?;
+                continue;
+            }
+        };
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    // Full document sync
+                    "textDocumentSync": 1,
+                },
+                "serverInfo": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            }),
+            "shutdown" => Value::Null,
+            "exit" => return Ok(()),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                publish(&mut output, diagnostics(uri, text, options, warnings))?;
+                continue;
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|c| c.last()?["text"].as_str()) {
+                    publish(&mut output, diagnostics(uri, text, options, warnings))?;
+                }
+                continue;
+            }
+            "textDocument/didClose" => {
+                publish(&mut output, json!({ "uri": uri, "diagnostics": [] }))?;
+                continue;
+            }
+            _ if message.get("id").is_some() => {
+                write_message(
+                    &mut output,
+                    &json!({
+                        "jsonrpc": "2.0",
+                        "id": message["id"],
+                        "error": {
+                            "code": -32601,
+                            "message": format!("Method not found: {}", method),
+                        },
+                    }),
+                )?;
+                continue;
+            }
+            // Other notifications, e.g. `initialized`
+            _ => continue,
+        };
+        write_message(
+            &mut output,
+            &json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }),
+        )?;
+    }
+    Ok(())
+}
@@ -1,59 +1,697 @@
-mod error;
-mod lex;
-mod pre_grammar;
-
 use anyhow::{anyhow, Result};
-use clap::Parser;
-use lex::Token;
-use serde::Serialize;
-use std::path::Path;
+use clap::{
+    error::ErrorKind, parser::ValueSource, ColorChoice, CommandFactory, FromArgMatches, Parser,
+    Subcommand,
+};
+use clap_complete::Shell;
+use compilation_exp1::{
+    codes::{Code, Lint, LintLevel, WarningLevels},
+    diagnostics::DiagnosticsFormat,
+    error::{DisplayOptions, ErrorRecorder},
+    glob, grammar,
+    lex::{Dialect, LexOptions, Limits},
+    lsp, repl, selftest,
+    session::{self, Artifact, OutputFormat, Phase, Session},
+    source_map::SourceMap,
+    testcase, tmlanguage, typescript,
+};
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufWriter, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use terminal_size::{terminal_size_of, Width};
 
+/// Without a subcommand, checks the input files and writes the tokens to the output file,
+/// as `lex` after `check`.
+/// The global flags go before or after a subcommand, the others of the default mode
+/// are rejected with one, see `parse_args`.
 #[derive(Parser)]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
-    /// Input file (PL/0 code)
-    input_file: String,
-    /// Output file (JSON format tokens)
-    /// if not specified, check for errors only
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    /// Output format of the tokens
+    #[clap(short, long, value_enum, default_value_t = OutputFormat::Json)]
+    format: OutputFormat,
+    /// Artifact to emit
+    #[clap(short, long, value_enum, default_value_t = Artifact::Tokens)]
+    emit: Artifact,
+    /// Maximum width of diagnostics, 0 for unlimited.
+    /// Defaults to the terminal width, unlimited if stderr is not a terminal
+    #[clap(long, global = true)]
+    diagnostic_width: Option<usize>,
+    /// When to use ANSI colors in diagnostics and tables for the terminal.
+    /// `auto` colors terminals unless the `NO_COLOR` environment variable is set
+    #[clap(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    /// Format of the diagnostics printed to stderr
+    #[clap(long, global = true, value_enum, default_value_t = DiagnosticsFormat::Text)]
+    diagnostics_format: DiagnosticsFormat,
+    /// Write a JUnit XML report to this file, with a test case per input file
+    /// that fails if the file has errors
+    #[clap(long, global = true)]
+    junit: Option<String>,
+    /// Write a standalone HTML report of the diagnostics to this file,
+    /// with highlighted source excerpts
+    #[clap(long, global = true)]
+    report: Option<String>,
+    /// Report the warnings as warnings, e.g. after `--allow warnings`.
+    /// Takes a warning code like `W001`, or `warnings` for all of them
+    #[clap(short = 'W', long, global = true, value_name = "LINT")]
+    warn: Vec<Lint>,
+    /// Drop the warnings, e.g. `W001` or `warnings`.
+    /// A code overrides `warnings`, the strictest level of a lint given twice wins
+    #[clap(short = 'A', long, global = true, value_name = "LINT")]
+    allow: Vec<Lint>,
+    /// Report the warnings as errors, e.g. `W001` or `warnings`
+    #[clap(short = 'D', long, global = true, value_name = "LINT")]
+    deny: Vec<Lint>,
+    /// Apply machine-applicable fixes to the input file in place
+    #[clap(long, conflicts_with_all = ["stdin_filename", "watch", "from_tokens"])]
+    fix: bool,
+    /// Print a longer description of a diagnostic code, e.g. `E010`, with examples
+    #[clap(long, exclusive = true, value_name = "CODE")]
+    explain: Option<Code>,
+}
+impl Args {
+    fn display_options(&self) -> DisplayOptions {
+        let width = match self.diagnostic_width {
+            Some(0) => None,
+            Some(width) => Some(width),
+            None => terminal_size_of(std::io::stderr()).map(|(Width(w), _)| w as usize),
+        };
+        let mut warnings = WarningLevels::default();
+        for (lints, level) in [
+            (&self.allow, LintLevel::Allow),
+            (&self.warn, LintLevel::Warn),
+            (&self.deny, LintLevel::Deny),
+        ] {
+            for &lint in lints {
+                warnings.set(lint, level);
+            }
+        }
+        DisplayOptions {
+            warnings,
+            width,
+            format: self.diagnostics_format,
+            color: use_color(self.color, std::io::stderr().is_terminal()),
+        }
+    }
+}
+/// Input files and the language they are written in.
+#[derive(clap::Args)]
+struct InputArgs {
+    /// Input files (PL/0 code) or globs like `examples/*.pl0`, `-` for stdin
+    #[clap(required_unless_present = "stdin_filename")]
+    input_files: Vec<String>,
+    /// Read the source from stdin, reporting diagnostics under this file name
+    #[clap(long, conflicts_with = "input_files")]
+    stdin_filename: Option<String>,
+    /// Language dialect
+    #[clap(long, value_enum, default_value_t = Dialect::Standard)]
+    dialect: Dialect,
+    #[command(flatten)]
+    limits: Limits,
+    /// Check the input files again whenever one of them changes, until interrupted
+    #[clap(long, conflicts_with = "stdin_filename")]
+    watch: bool,
+    /// Read the inputs as token dumps written by `lex` (JSON or MessagePack)
+    /// and skip lexing
+    #[clap(long)]
+    from_tokens: bool,
+}
+impl InputArgs {
+    fn lex_options(&self) -> LexOptions {
+        LexOptions {
+            dialect: self.dialect,
+            limits: self.limits.clone(),
+        }
+    }
+}
+#[derive(clap::Args, Clone)]
+struct OutputArgs {
+    /// Output file, stdout if not specified
     #[clap(short, long)]
     output_file: Option<String>,
+    /// Write the output of each input file to this directory,
+    /// named after the input file, e.g. `a.json` for `a.in`
+    #[clap(long, conflicts_with = "output_file")]
+    output_dir: Option<String>,
+}
+impl OutputArgs {
+    fn is_some(&self) -> bool {
+        self.output_file.is_some() || self.output_dir.is_some()
+    }
+}
+/// What to do with each input file after reporting its diagnostics.
+struct Job {
+    phase: Phase,
+    format: OutputFormat,
+    /// Artifact to write, None to report whether errors were detected
+    emit: Option<Artifact>,
+    output: OutputArgs,
+    fix: bool,
+    /// Write the artifact back to the input file instead of the output
+    in_place: bool,
+    /// Interpret the programs free of errors
+    interpret: bool,
+    /// JUnit XML report to write
+    junit: Option<String>,
+    /// HTML report to write
+    report: Option<String>,
+}
+#[derive(Subcommand)]
+enum Command {
+    /// Lex the input files, reporting lexical errors only,
+    /// and write the tokens of those free of errors
+    Lex {
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Output format of the tokens
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Json)]
+        format: OutputFormat,
+        /// Print a table of the tokens (index, line:col, kind, lexeme) instead,
+        /// even if errors are detected
+        #[clap(long, conflicts_with = "format")]
+        dump_tokens: bool,
+    },
+    /// Check the input files for lexical, syntax and semantic errors
+    Check {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Apply machine-applicable fixes to the input files in place
+        #[clap(long, conflicts_with_all = ["stdin_filename", "watch", "from_tokens"])]
+        fix: bool,
+    },
+    /// Check the input files and write their AST, or another artifact of the parser
+    Parse {
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Artifact to emit
+        #[clap(short, long, value_enum, default_value_t = Artifact::Ast)]
+        emit: Artifact,
+    },
+    /// Reprint the input files free of errors with canonical indentation and spacing
+    Fmt {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Write the formatted source back to the input files instead of stdout
+        #[clap(long, conflicts_with_all = ["stdin_filename", "watch", "from_tokens"])]
+        write: bool,
+        /// Strip comments and collapse whitespace to the minimum that keeps the tokens apart
+        #[clap(long)]
+        minify: bool,
+    },
+    /// Check the input files and interpret them
+    Run {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Grammar utilities
+    #[command(subcommand)]
+    Grammar(GrammarCommand),
+    /// Generate files derived from the language definition
+    #[command(subcommand)]
+    Generate(GenerateCommand),
+    /// Check the bundled corpus, files named `*correct*` must be free of diagnostics
+    Selftest {
+        /// Directory of `.in` files
+        #[clap(default_value = "data")]
+        corpus: String,
+        /// Report how many files exercised each var block production
+        #[clap(long)]
+        coverage: bool,
+    },
+    /// Print a synthetic program of about the given size, as a benchmark workload
+    GenTestcase {
+        /// Approximate number of tokens
+        #[clap(long, default_value_t = 1000)]
+        tokens: usize,
+        /// Use an undeclared identifier in every tenth statement
+        #[clap(long)]
+        errors: bool,
+        /// Seed of the generated statements
+        #[clap(long, default_value_t = 0)]
+        seed: u64,
+    },
+    /// Check declarations and statements typed line by line, keeping the declarations
+    Repl,
+    /// Language server over stdio, publishing diagnostics as documents change
+    Lsp,
+    /// Print the shell completion script
+    Completions {
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+}
+#[derive(Subcommand)]
+enum GenerateCommand {
+    /// TextMate grammar JSON for editor syntax highlighting
+    Tmlanguage,
+    /// JSON Schema of the JSON token output
+    Schema,
+    /// TypeScript declarations (.d.ts) of the JSON token output
+    Typescript,
 }
-fn write_to_output(output_file: &str, tokens: Vec<Token>) -> Result<()> {
-    #[derive(Serialize)]
-    struct TokenOutput {
-        tokens: Vec<Token>,
+#[derive(Subcommand)]
+enum GrammarCommand {
+    /// Report first/follow/select sets, left recursion, common prefixes and LL(1) conflicts
+    Analyze {
+        /// Grammar file, one `A -> α | β` production per line
+        grammar_file: String,
+    },
+}
+/// Whether to use colors on an output, `terminal` if it is a terminal.
+fn use_color(choice: ColorChoice, terminal: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => terminal && std::env::var_os("NO_COLOR").is_none(),
     }
-    let tokens = TokenOutput { tokens };
-    let output_file = Path::new(output_file);
+}
+fn create_output(output_file: &Path) -> Result<BufWriter<File>> {
     let dir = output_file
         .parent()
         .ok_or_else(|| anyhow!("Invalid output file"))?;
     std::fs::create_dir_all(dir)?;
-    let output = std::fs::File::create(output_file)?;
-    let output = std::io::BufWriter::new(output);
-
-    serde_json::to_writer_pretty(output, &tokens)?;
+    let output = File::create(output_file)?;
+    Ok(BufWriter::new(output))
+}
+/// `<dir>/<stem>.<extension>`, the stem of `<stdin>` is `stdin`.
+fn output_path(dir: &str, input_name: &str, extension: &str) -> PathBuf {
+    let stem = Path::new(input_name).file_stem().unwrap_or_default();
+    let stem = stem.to_string_lossy();
+    let stem = stem.trim_matches(['<', '>']);
+    Path::new(dir).join(format!("{}.{}", stem, extension))
+}
+/// Modification time of each file, None if it does not exist.
+fn modified_times(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| path.metadata().and_then(|m| m.modified()).ok())
+        .collect()
+}
+fn analyze_grammar(grammar_file: &str, options: DisplayOptions) -> Result<()> {
+    let content = std::fs::read_to_string(grammar_file)?;
+    let mut sources = SourceMap::new();
+    let file = sources.add(grammar_file, content);
+    let mut errors = ErrorRecorder::with_options(file, options);
+    let grammar = grammar::Grammar::parse(sources.content(file), &mut errors);
+    errors.print_with(&sources);
+    print!("{}", grammar::analyze(&grammar?));
     Ok(())
 }
 
+/// Parse the arguments, rejecting those of the default mode given with a subcommand,
+/// e.g. the input file `a.in` in `a.in lex b.in`.
+fn parse_args() -> Args {
+    let mut command = Args::command();
+    let matches = command.get_matches_mut();
+    if let Some((name, _)) = matches.subcommand() {
+        let given = command.get_arguments().find(|arg| {
+            !arg.is_global_set()
+                && matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+        });
+        if let Some(arg) = given {
+            command
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("the argument '{}' cannot be used with '{}'", arg, name),
+                )
+                .exit();
+        }
+    }
+    Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+}
+
 fn main() -> Result<()> {
-    let args = Args::parse();
-    let input_file = Path::new(&args.input_file);
-    if !input_file.is_file() {
-        println!("File does not exist: {}", input_file.display());
+    let args = parse_args();
+    let display_options = args.display_options();
+    let color = args.color;
+    if let Some(code) = args.explain {
+        print!("{}: {}\n\n{}", code, code.summary(), code.explanation());
         return Ok(());
     }
-    let content = std::fs::read_to_string(input_file)?;
-    let mut errors = error::ErrorRecorder::new();
-    let tokens = pre_grammar::parse(&content, &mut errors);
-    errors.print_with(&input_file.display().to_string(), &content);
-    if errors.no_error() {
-        if let Some(output_file) = args.output_file {
-            write_to_output(&output_file, tokens)?;
+    match &args.command {
+        Some(Command::Grammar(GrammarCommand::Analyze { grammar_file })) => {
+            return analyze_grammar(grammar_file, args.display_options());
+        }
+        Some(Command::Generate(GenerateCommand::Tmlanguage)) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&tmlanguage::tmlanguage())?
+            );
+            return Ok(());
+        }
+        Some(Command::Generate(GenerateCommand::Schema)) => {
+            let schema = schemars::schema_for!(session::TokenOutput<'static>);
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            return Ok(());
+        }
+        Some(Command::Generate(GenerateCommand::Typescript)) => {
+            print!("{}", typescript::typescript());
+            return Ok(());
+        }
+        Some(Command::Selftest { corpus, coverage }) => {
+            let (report, failed) =
+                selftest::selftest(Path::new(corpus), *coverage, args.input.lex_options())?;
+            print!("{}", report);
+            return match failed {
+                0 => Ok(()),
+                n => Err(anyhow!("{} corpus file(s) failed", n)),
+            };
+        }
+        Some(Command::GenTestcase {
+            tokens,
+            errors,
+            seed,
+        }) => {
+            print!("{}", testcase::gen_testcase(*tokens, *errors, *seed));
+            return Ok(());
+        }
+        Some(Command::Repl) => {
+            let stdin = std::io::stdin().lock();
+            return repl::repl(
+                stdin,
+                std::io::stdout(),
+                &args.input.lex_options(),
+                &args.display_options(),
+            );
+        }
+        Some(Command::Lsp) => {
+            let stdin = std::io::stdin().lock();
+            return lsp::serve(
+                stdin,
+                std::io::stdout(),
+                &args.input.lex_options(),
+                &args.display_options().warnings,
+            );
+        }
+        Some(Command::Completions { shell }) => {
+            let mut command = Args::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(*shell, &mut command, name, &mut std::io::stdout());
+            return Ok(());
+        }
+        // Commands of the compilation phases
+        _ => {}
+    }
+    let (input, job) = match args.command {
+        Some(Command::Lex {
+            input,
+            output,
+            format,
+            dump_tokens,
+        }) => (
+            input,
+            Job {
+                phase: Phase::Lex,
+                format,
+                emit: Some(if dump_tokens {
+                    Artifact::TokenTable
+                } else {
+                    Artifact::Tokens
+                }),
+                output,
+                fix: false,
+                in_place: false,
+                interpret: false,
+                junit: args.junit.clone(),
+                report: args.report.clone(),
+            },
+        ),
+        Some(Command::Check { input, fix }) => (
+            input,
+            Job {
+                phase: Phase::Check,
+                format: OutputFormat::Json,
+                emit: None,
+                output: OutputArgs {
+                    output_file: None,
+                    output_dir: None,
+                },
+                fix,
+                in_place: false,
+                interpret: false,
+                junit: args.junit.clone(),
+                report: args.report.clone(),
+            },
+        ),
+        Some(Command::Parse {
+            input,
+            output,
+            emit,
+        }) => (
+            input,
+            Job {
+                phase: Phase::Check,
+                format: OutputFormat::Json,
+                emit: Some(emit),
+                output,
+                fix: false,
+                in_place: false,
+                interpret: false,
+                junit: args.junit.clone(),
+                report: args.report.clone(),
+            },
+        ),
+        Some(Command::Run { input }) => (
+            input,
+            Job {
+                phase: Phase::Check,
+                format: OutputFormat::Json,
+                emit: None,
+                output: OutputArgs {
+                    output_file: None,
+                    output_dir: None,
+                },
+                fix: false,
+                in_place: false,
+                interpret: true,
+                junit: args.junit.clone(),
+                report: args.report.clone(),
+            },
+        ),
+        Some(Command::Fmt {
+            input,
+            write,
+            minify,
+        }) => (
+            input,
+            Job {
+                phase: Phase::Check,
+                format: OutputFormat::Json,
+                emit: Some(if minify {
+                    Artifact::Minified
+                } else {
+                    Artifact::Formatted
+                }),
+                output: OutputArgs {
+                    output_file: None,
+                    output_dir: None,
+                },
+                fix: false,
+                in_place: write,
+                interpret: false,
+                junit: args.junit.clone(),
+                report: args.report.clone(),
+            },
+        ),
+        Some(_) => unreachable!("handled above"),
+        None => {
+            // Tokens are only written to an output file, without one this is a plain check
+            let emit = args.emit != Artifact::Tokens || args.output.is_some();
+            (
+                args.input,
+                Job {
+                    phase: Phase::Check,
+                    format: args.format,
+                    emit: emit.then_some(args.emit),
+                    output: args.output,
+                    fix: args.fix,
+                    in_place: false,
+                    interpret: false,
+                    junit: args.junit.clone(),
+                    report: args.report.clone(),
+                },
+            )
+        }
+    };
+    let stdin_name = match &input.stdin_filename {
+        Some(name) => Some(name.as_str()),
+        None if input.input_files.iter().any(|f| f == "-") => Some("<stdin>"),
+        None => None,
+    };
+    if job.fix && stdin_name.is_some() {
+        return Err(anyhow!("Cannot apply fixes in place to stdin"));
+    }
+    if job.in_place && stdin_name.is_some() {
+        return Err(anyhow!("Cannot write stdin in place"));
+    }
+    if input.watch && stdin_name.is_some() {
+        return Err(anyhow!("Cannot watch stdin"));
+    }
+    let stdin = match stdin_name {
+        Some(name) => {
+            let mut content = Vec::new();
+            std::io::stdin().read_to_end(&mut content)?;
+            Some((name, content))
+        }
+        None => None,
+    };
+    let mut inputs = Vec::new();
+    for pattern in input.input_files.iter().filter(|f| *f != "-") {
+        inputs.extend(glob::expand(pattern)?);
+    }
+    let session = || {
+        Session::new()
+            .lex_options(input.lex_options())
+            .display_options(display_options.clone())
+            .format(job.format)
+            .phase(job.phase)
+            .color(use_color(
+                color,
+                !job.output.is_some() && std::io::stdout().is_terminal(),
+            ))
+    };
+    if !input.watch {
+        return match run(session(), &job, &inputs, stdin, input.from_tokens)? {
+            true => Ok(()),
+            false => Err(anyhow!("Error detected")),
+        };
+    }
+    let mut modified = modified_times(&inputs);
+    loop {
+        if let Err(e) = run(session(), &job, &inputs, None, input.from_tokens) {
+            eprintln!("Error: {}", e);
+        }
+        println!(
+            "Watching {} file(s) for changes, press Ctrl-C to stop",
+            inputs.len()
+        );
+        while modified_times(&inputs) == modified {
+            std::thread::sleep(Duration::from_millis(300));
+        }
+        modified = modified_times(&inputs);
+        println!();
+    }
+}
+/// Check the input files and the source read from stdin, print their diagnostics
+/// and write their outputs, returns whether all are free of errors.
+/// With `from_tokens`, the inputs are token dumps.
+fn run(
+    mut session: Session,
+    job: &Job,
+    inputs: &[PathBuf],
+    stdin: Option<(&str, Vec<u8>)>,
+    from_tokens: bool,
+) -> Result<bool> {
+    let mut files = Vec::new();
+    if let Some((name, content)) = stdin {
+        files.push(if from_tokens {
+            session.add_token_dump(name, &content)?
         } else {
-            println!("No error detected");
+            session.add_source(name, String::from_utf8(content)?)
+        });
+    }
+    for input_file in inputs {
+        if !input_file.is_file() {
+            println!("File does not exist: {}", input_file.display());
+            continue;
         }
-        Ok(())
-    } else {
-        Err(anyhow!("Error detected"))
+        files.push(if from_tokens {
+            let name = input_file.display().to_string();
+            session.add_token_dump(&name, &std::fs::read(input_file)?)?
+        } else {
+            session.add_file(input_file)?
+        });
+    }
+    if files.is_empty() {
+        return Ok(true);
+    }
+    if files.len() > 1 && job.output.output_file.is_some() {
+        return Err(anyhow!(
+            "Cannot write {} inputs to one output file, use --output-dir instead",
+            files.len()
+        ));
+    }
+    let no_error = session.check();
+    session.print_diagnostics();
+    if let Some(junit) = &job.junit {
+        create_output(Path::new(junit))?.write_all(session.junit_report().as_bytes())?;
+    }
+    if let Some(report) = &job.report {
+        create_output(Path::new(report))?.write_all(session.html_report().as_bytes())?;
+    }
+    let mut written = HashSet::new();
+    for &file in &files {
+        if job.fix {
+            let count = session.apply_fixes(file)?;
+            if count > 0 {
+                println!("Applied {} fix(es) to {}", count, session.name(file));
+            }
+        }
+        let Some(emit) = job.emit else {
+            if files.len() == 1 && no_error && !job.interpret {
+                println!("No error detected");
+            }
+            continue;
+        };
+        // Tokens and the reprinted source are only written if no error detected
+        let exact = matches!(
+            emit,
+            Artifact::Tokens | Artifact::Formatted | Artifact::Minified
+        );
+        if exact && !session.errors(file).no_error() {
+            continue;
+        }
+        if job.in_place {
+            let text = session.emit(file, emit)?;
+            if std::fs::read(session.name(file))? != text {
+                std::fs::write(session.name(file), text)?;
+                println!("Formatted {}", session.name(file));
+            }
+            continue;
+        }
+        let output_file = match (&job.output.output_file, &job.output.output_dir) {
+            (Some(output_file), _) => Some(PathBuf::from(output_file)),
+            (None, Some(dir)) => {
+                let path = output_path(dir, session.name(file), session.extension(emit));
+                if !written.insert(path.clone()) {
+                    return Err(anyhow!("Output file {} is written twice", path.display()));
+                }
+                Some(path)
+            }
+            (None, None) => None,
+        };
+        let text = session.emit(file, emit)?;
+        match &output_file {
+            Some(output_file) => create_output(output_file)?.write_all(&text)?,
+            None => std::io::stdout().write_all(&text)?,
+        }
+    }
+    if files.len() > 1 {
+        for &file in &files {
+            let errors = session.errors(file);
+            match (errors.error_count(), errors.warning_count()) {
+                (0, 0) => println!("{}: ok", session.name(file)),
+                (e, w) => println!("{}: {} error(s), {} warning(s)", session.name(file), e, w),
+            }
+        }
+    }
+    if job.interpret && no_error {
+        return Err(anyhow!("Interpreting programs is not supported yet"));
     }
+    Ok(no_error)
 }
@@ -0,0 +1,585 @@
+use std::{fmt, ops::Range};
+
+use anyhow::{anyhow, Result};
+use serde::{
+    de::{self, DeserializeSeed, IntoDeserializer, Visitor},
+    ser::{self, Serialize},
+    Deserialize,
+};
+
+/// Serialize the value as MessagePack, structs and maps as maps keyed by name,
+/// enums as in `serde_json`, the same as `rmp_serde::to_vec_named`.
+/// The token dumps only need this subset, which is small enough to keep here
+/// rather than to depend on `rmp-serde`, and any MessagePack library reads them.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut serializer = Serializer { output: Vec::new() };
+    value.serialize(&mut serializer)?;
+    Ok(serializer.output)
+}
+/// Read a value written by `to_vec`.
+pub fn from_slice<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T> {
+    let mut deserializer = Deserializer { bytes, pos: 0 };
+    let value = T::deserialize(&mut deserializer)?;
+    if deserializer.pos != bytes.len() {
+        return Err(anyhow!("Trailing bytes after MessagePack value"));
+    }
+    Ok(value)
+}
+
+#[derive(Debug)]
+pub struct Error(String);
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+impl std::error::Error for Error {}
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+/// Markers of the variable-length types: the fix form with its maximum length,
+/// then the 8-bit (0 if absent), 16-bit and 32-bit length forms.
+struct Markers {
+    fix: u8,
+    fix_max: usize,
+    sized: [u8; 3],
+}
+const STR: Markers = Markers {
+    fix: 0xa0,
+    fix_max: 31,
+    sized: [0xd9, 0xda, 0xdb],
+};
+const BIN: Markers = Markers {
+    fix: 0,
+    fix_max: 0,
+    sized: [0xc4, 0xc5, 0xc6],
+};
+const ARRAY: Markers = Markers {
+    fix: 0x90,
+    fix_max: 15,
+    sized: [0, 0xdc, 0xdd],
+};
+const MAP: Markers = Markers {
+    fix: 0x80,
+    fix_max: 15,
+    sized: [0, 0xde, 0xdf],
+};
+/// Write the header of a value of `len` bytes or elements in its smallest form.
+fn header(len: usize, markers: &Markers, output: &mut Vec<u8>) {
+    let [sized8, sized16, sized32] = markers.sized;
+    if len <= markers.fix_max && markers.fix != 0 {
+        output.push(markers.fix | len as u8);
+    } else if len <= u8::MAX as usize && sized8 != 0 {
+        output.extend([sized8, len as u8]);
+    } else if len <= u16::MAX as usize {
+        output.push(sized16);
+        output.extend((len as u16).to_be_bytes());
+    } else {
+        output.push(sized32);
+        output.extend((len as u32).to_be_bytes());
+    }
+}
+
+struct Serializer {
+    output: Vec<u8>,
+}
+impl Serializer {
+    /// Smallest encoding of the integer.
+    fn int(&mut self, v: i128) {
+        let out = &mut self.output;
+        match v {
+            0..=0x7f => out.push(v as u8),
+            -32..=-1 => out.push(v as i8 as u8),
+            0x80..=0xff => out.extend([0xcc, v as u8]),
+            0x100..=0xffff => {
+                out.push(0xcd);
+                out.extend((v as u16).to_be_bytes());
+            }
+            0x1_0000..=0xffff_ffff => {
+                out.push(0xce);
+                out.extend((v as u32).to_be_bytes());
+            }
+            0x1_0000_0000.. => {
+                out.push(0xcf);
+                out.extend((v as u64).to_be_bytes());
+            }
+            -0x80..=-33 => out.extend([0xd0, v as i8 as u8]),
+            -0x8000..=-0x81 => {
+                out.push(0xd1);
+                out.extend((v as i16).to_be_bytes());
+            }
+            -0x8000_0000..=-0x8001 => {
+                out.push(0xd2);
+                out.extend((v as i32).to_be_bytes());
+            }
+            _ => {
+                out.push(0xd3);
+                out.extend((v as i64).to_be_bytes());
+            }
+        }
+    }
+    /// Start a compound of `len` elements. If the length is unknown or turns out wrong,
+    /// e.g. when struct fields are skipped, the header is rewritten by `finish`.
+    fn compound(&mut self, len: Option<usize>, markers: &'static Markers) -> Compound<'_> {
+        let start = self.output.len();
+        if let Some(len) = len {
+            header(len, markers, &mut self.output);
+        }
+        Compound {
+            header: start..self.output.len(),
+            len,
+            markers,
+            count: 0,
+            ser: self,
+        }
+    }
+    /// `{variant: value}` prefix of the non-unit enum variants.
+    fn variant(&mut self, variant: &str) {
+        header(1, &MAP, &mut self.output);
+        self.str(variant);
+    }
+    fn str(&mut self, v: &str) {
+        header(v.len(), &STR, &mut self.output);
+        self.output.extend(v.as_bytes());
+    }
+}
+struct Compound<'a> {
+    ser: &'a mut Serializer,
+    /// Range of the header written for `len`, empty if the length is unknown
+    header: Range<usize>,
+    len: Option<usize>,
+    markers: &'static Markers,
+    count: usize,
+}
+impl Compound<'_> {
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.count += 1;
+        value.serialize(&mut *self.ser)
+    }
+    fn finish(self) -> Result<(), Error> {
+        if self.len != Some(self.count) {
+            let mut fixed = Vec::new();
+            header(self.count, self.markers, &mut fixed);
+            self.ser.output.splice(self.header, fixed);
+        }
+        Ok(())
+    }
+}
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.output.push(if v { 0xc3 } else { 0xc2 });
+        Ok(())
+    }
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.int(v.into());
+        Ok(())
+    }
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.int(v.into());
+        Ok(())
+    }
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.serialize_f64(v.into())
+    }
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.output.push(0xcb);
+        self.output.extend(v.to_be_bytes());
+        Ok(())
+    }
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.str(v.encode_utf8(&mut [0; 4]));
+        Ok(())
+    }
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.str(v);
+        Ok(())
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        header(v.len(), &BIN, &mut self.output);
+        self.output.extend(v);
+        Ok(())
+    }
+    fn serialize_none(self) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.output.push(0xc0);
+        Ok(())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.str(variant);
+        Ok(())
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.variant(variant);
+        value.serialize(self)
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(len, &ARRAY))
+    }
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(Some(len), &ARRAY))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(Some(len), &ARRAY))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        self.variant(variant);
+        Ok(self.compound(Some(len), &ARRAY))
+    }
+    fn serialize_map(self, len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(len, &MAP))
+    }
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(Some(len), &MAP))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        self.variant(variant);
+        Ok(self.compound(Some(len), &MAP))
+    }
+}
+impl ser::SerializeSeq for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+impl ser::SerializeTuple for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+impl ser::SerializeTupleStruct for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+impl ser::SerializeTupleVariant for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+impl ser::SerializeMap for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.element(key)
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        // The key counted the entry
+        value.serialize(&mut *self.ser)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+impl ser::SerializeStruct for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.element(key)?;
+        value.serialize(&mut *self.ser)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+impl ser::SerializeStructVariant for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.element(key)?;
+        value.serialize(&mut *self.ser)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+struct Deserializer<'de> {
+    bytes: &'de [u8],
+    pos: usize,
+}
+impl<'de> Deserializer<'de> {
+    fn take(&mut self, n: usize) -> Result<&'de [u8], Error> {
+        let end = self.pos + n;
+        let bytes = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or_else(|| Error("Unexpected end of MessagePack data".to_string()))?;
+        self.pos = end;
+        Ok(bytes)
+    }
+    fn peek(&self) -> Result<u8, Error> {
+        let byte = self.bytes.get(self.pos);
+        byte.copied()
+            .ok_or_else(|| Error("Unexpected end of MessagePack data".to_string()))
+    }
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+    /// Big-endian unsigned integer of `n` bytes.
+    fn uint(&mut self, n: usize) -> Result<u64, Error> {
+        let bytes = self.take(n)?;
+        Ok(bytes.iter().fold(0, |acc, b| acc << 8 | u64::from(*b)))
+    }
+    /// Big-endian signed integer of `n` bytes.
+    fn int(&mut self, n: usize) -> Result<i64, Error> {
+        let shift = 64 - 8 * n as u32;
+        Ok(((self.uint(n)? << shift) as i64) >> shift)
+    }
+    /// Length of the `u8`, `u16` or `u32` length form, by offset from its first marker.
+    fn len(&mut self, offset: u8) -> Result<usize, Error> {
+        Ok(self.uint(1 << offset)? as usize)
+    }
+    fn str(&mut self, len: usize) -> Result<&'de str, Error> {
+        std::str::from_utf8(self.take(len)?).map_err(|e| Error(e.to_string()))
+    }
+}
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let marker = self.byte()?;
+        match marker {
+            0x00..=0x7f => visitor.visit_u64(marker.into()),
+            0x80..=0x8f => visitor.visit_map(Access::new(self, marker & 0x0f)),
+            0x90..=0x9f => visitor.visit_seq(Access::new(self, marker & 0x0f)),
+            0xa0..=0xbf => visitor.visit_borrowed_str(self.str(usize::from(marker & 0x1f))?),
+            0xc0 => visitor.visit_unit(),
+            0xc2 => visitor.visit_bool(false),
+            0xc3 => visitor.visit_bool(true),
+            0xc4..=0xc6 => {
+                let len = self.len(marker - 0xc4)?;
+                visitor.visit_borrowed_bytes(self.take(len)?)
+            }
+            0xca => visitor.visit_f32(f32::from_bits(self.uint(4)? as u32)),
+            0xcb => visitor.visit_f64(f64::from_bits(self.uint(8)?)),
+            0xcc..=0xcf => visitor.visit_u64(self.uint(1 << (marker - 0xcc))?),
+            0xd0..=0xd3 => visitor.visit_i64(self.int(1 << (marker - 0xd0))?),
+            0xd9..=0xdb => {
+                let len = self.len(marker - 0xd9)?;
+                visitor.visit_borrowed_str(self.str(len)?)
+            }
+            // Arrays and maps have no 8-bit length form
+            0xdc | 0xdd => {
+                let len = self.len(marker - 0xdc + 1)?;
+                visitor.visit_seq(Access::new(self, len))
+            }
+            0xde | 0xdf => {
+                let len = self.len(marker - 0xde + 1)?;
+                visitor.visit_map(Access::new(self, len))
+            }
+            0xe0..=0xff => visitor.visit_i64((marker as i8).into()),
+            _ => Err(Error(format!(
+                "Unsupported MessagePack marker 0x{:02x}",
+                marker
+            ))),
+        }
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.peek()? == 0xc0 {
+            self.pos += 1;
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        // Unit variants are strings, the others `{variant: value}`
+        match self.peek()? {
+            0x81 => {
+                self.pos += 1;
+                visitor.visit_enum(self)
+            }
+            _ => visitor.visit_enum(<&str>::deserialize(self)?.into_deserializer()),
+        }
+    }
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+/// Elements of an array or entries of a map.
+struct Access<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    remaining: usize,
+}
+impl<'a, 'de> Access<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>, len: impl Into<usize>) -> Self {
+        Self {
+            de,
+            remaining: len.into(),
+        }
+    }
+}
+impl<'de> de::SeqAccess<'de> for Access<'_, 'de> {
+    type Error = Error;
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+impl<'de> de::MapAccess<'de> for Access<'_, 'de> {
+    type Error = Error;
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(&mut *self.de)
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+impl<'de> de::EnumAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+    type Variant = Self;
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let variant = seed.deserialize(&mut *self)?;
+        Ok((variant, self))
+    }
+}
+impl<'de> de::VariantAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+    fn unit_variant(self) -> Result<(), Error> {
+        <()>::deserialize(self)
+    }
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+}
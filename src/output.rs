@@ -0,0 +1,160 @@
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::{
+    msgpack, pairs,
+    pre_grammar::ParseResult,
+    protobuf,
+    session::{TokenDump, TokenOutput},
+};
+
+/// Writer of the token output of a file in one output format.
+pub trait OutputWriter {
+    fn write(&self, result: &ParseResult) -> Result<Vec<u8>>;
+}
+/// `TokenOutput` as a JSON value, shared by the self-describing formats.
+fn token_output(result: &ParseResult) -> Result<Value> {
+    Ok(serde_json::to_value(TokenOutput::new(
+        &result.tokens,
+        &result.tables,
+    ))?)
+}
+
+/// Pretty JSON `TokenOutput`.
+pub struct Json;
+impl OutputWriter for Json {
+    fn write(&self, result: &ParseResult) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(&TokenOutput::new(
+            &result.tokens,
+            &result.tables,
+        ))?)
+    }
+}
+/// Classic (code, attribute) pairs.
+pub struct Pairs;
+impl OutputWriter for Pairs {
+    fn write(&self, result: &ParseResult) -> Result<Vec<u8>> {
+        Ok(pairs::to_pairs(&result.tokens, &result.tables).into_bytes())
+    }
+}
+/// Binary protobuf `TokenOutput` message.
+pub struct Protobuf;
+impl OutputWriter for Protobuf {
+    fn write(&self, result: &ParseResult) -> Result<Vec<u8>> {
+        Ok(protobuf::to_protobuf(
+            &result.tokens,
+            &result.tables,
+            &result.errors,
+        ))
+    }
+}
+
+/// Block style YAML of `TokenOutput`, with the same structure as the JSON.
+pub struct Yaml;
+impl OutputWriter for Yaml {
+    fn write(&self, result: &ParseResult) -> Result<Vec<u8>> {
+        let mut output = String::new();
+        yaml_block(&token_output(result)?, 0, &mut output);
+        Ok(output.into_bytes())
+    }
+}
+/// Scalar or empty collection, written on the line of its key or dash.
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => yaml_string(s),
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+        // null, booleans and numbers are written as in JSON
+        _ => value.to_string(),
+    }
+}
+/// Plain string if it cannot be read as anything else, otherwise double-quoted,
+/// JSON escapes being valid in YAML double-quoted strings.
+fn yaml_string(s: &str) -> String {
+    let reserved = ["true", "false", "null", "yes", "no", "on", "off", "y", "n"];
+    let plain = s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-. ".contains(c))
+        && !s.ends_with(' ')
+        && !reserved.contains(&s.to_ascii_lowercase().as_str());
+    if plain {
+        s.to_string()
+    } else {
+        Value::String(s.to_string()).to_string()
+    }
+}
+fn is_collection(value: &Value) -> bool {
+    match value {
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+        _ => false,
+    }
+}
+/// Write the value on lines of its own, indented by `indent` spaces.
+fn yaml_block(value: &Value, indent: usize, output: &mut String) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                output.push_str(&format!("{}{}:", pad, yaml_string(key)));
+                if is_collection(value) {
+                    output.push('\n');
+                    yaml_block(value, indent + 2, output);
+                } else {
+                    output.push_str(&format!(" {}\n", yaml_scalar(value)));
+                }
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                if is_collection(item) {
+                    // The first line of the item follows the dash
+                    let mut nested = String::new();
+                    yaml_block(item, indent + 2, &mut nested);
+                    output.push_str(&format!("{}- {}", pad, &nested[indent + 2..]));
+                } else {
+                    output.push_str(&format!("{}- {}\n", pad, yaml_scalar(item)));
+                }
+            }
+        }
+        _ => output.push_str(&format!("{}{}\n", pad, yaml_scalar(value))),
+    }
+}
+
+/// CSV of the tokens with `offset,content,kind` columns, the tables are left out.
+pub struct Csv;
+impl OutputWriter for Csv {
+    fn write(&self, result: &ParseResult) -> Result<Vec<u8>> {
+        let mut output = String::from("offset,content,kind\n");
+        for token in &result.tokens {
+            output.push_str(&format!(
+                "{},{},{:?}\n",
+                token.offset,
+                csv_field(&token.content),
+                token.token
+            ));
+        }
+        Ok(output.into_bytes())
+    }
+}
+/// Quote the field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// MessagePack of `TokenOutput`, a compact binary form of the JSON.
+/// Serialized directly, it is several times faster to write than the pretty JSON.
+pub struct MessagePack;
+impl OutputWriter for MessagePack {
+    fn write(&self, result: &ParseResult) -> Result<Vec<u8>> {
+        msgpack::to_vec(&TokenOutput::new(&result.tokens, &result.tables))
+    }
+}
+/// Read back the token output written by `MessagePack`.
+pub fn read_msgpack(bytes: &[u8]) -> Result<TokenDump> {
+    msgpack::from_slice(bytes)
+}
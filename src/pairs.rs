@@ -0,0 +1,36 @@
+use crate::{lex::Token, table::Tables};
+
+/// Render tokens as classic `(code, attribute)` pairs.
+/// ```plaintext
+/// (1, -)
+/// (31, 0)
+/// ...
+///
+/// Identifiers:
+///     0 i
+///
+/// Constants:
+///     0 50
+/// ```
+/// Identifiers and integer literals carry the index into the corresponding table,
+/// other tokens carry an empty attribute `-`.
+pub fn to_pairs(tokens: &[Token], tables: &Tables) -> String {
+    let mut result = String::new();
+    for token in tokens {
+        let attr = match token.index {
+            Some(index) => index.to_string(),
+            None => "-".to_string(),
+        };
+        result.push_str(&format!("({}, {})\n", token.token.code(), attr));
+    }
+    for (title, table) in [
+        ("Identifiers", &tables.identifiers),
+        ("Constants", &tables.constants),
+    ] {
+        result.push_str(&format!("\n{}:\n", title));
+        for (i, entry) in table.entries().iter().enumerate() {
+            result.push_str(&format!("    {} {}\n", i, entry));
+        }
+    }
+    result
+}
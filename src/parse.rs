@@ -0,0 +1,565 @@
+use anyhow::{anyhow, Error, Result};
+
+use crate::{
+    ast::{
+        BinaryOp, CaseArm, Expr, ExprKind, Program, Selector, Statement, StatementKind, UnaryOp,
+        Variable,
+    },
+    codes::Code,
+    error::{ErrorRecorder, Fix},
+    lex::{Token, TokenEnum},
+    source_map::Span,
+};
+
+/// Recursive-descent parser of the program block, with a precedence-climbing expression parser.
+/// ```plaintext
+/// program   -> begin statement { ; statement } end [.]
+/// statement -> variable := expr | if expr then statement [else statement]
+///            | while expr do statement | begin ... end | call i [args] | ? variable | ! expr | ε
+///            | read ( variable { , variable } ) | write ( expr { , expr } )
+///            | case expr of arm { ; arm } end
+/// arm       -> expr { , expr } : statement | ε
+/// expr      -> unary { binop unary }, by precedence climbing, loosest first:
+///              or, and, relational (< > <> >= <= ==), + -, * /
+/// unary     -> - unary | + unary | odd expr | not expr | factor
+/// factor    -> number | real | char | true | false | i args | variable | ( expr )
+/// variable  -> i { .i | [ expr { , expr } ] }
+/// args      -> ( [ expr { , expr } ] )
+/// ```
+/// A syntax error skips the rest of the statement.
+/// It is not reported if an earlier phase already reported an error there.
+/// A single `=` is reported and read as `:=` after an assignment target, as `==` elsewhere.
+pub struct Parser<'a> {
+    tokens: &'a [Token],
+    index: usize,
+}
+impl<'a> Parser<'a> {
+    pub fn new(tokens: &'a [Token]) -> Self {
+        Self { tokens, index: 0 }
+    }
+    fn peek(&self) -> Option<TokenEnum> {
+        self.tokens.get(self.index).map(|t| t.token)
+    }
+    /// Span of the next token, empty after the last token at the end of input.
+    fn peek_span(&self) -> Span {
+        self.tokens
+            .get(self.index)
+            .map(|t| t.span())
+            .unwrap_or(Span::from(self.prev_end()))
+    }
+    /// End of the previous token, where a missing token is reported.
+    fn prev_end(&self) -> usize {
+        self.index
+            .checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .map(|t| t.span().end)
+            .unwrap_or(0)
+    }
+    /// Whether the next token is a single `=`, which the lexer does not tell from `==`.
+    fn at_single_eq(&self) -> bool {
+        self.tokens
+            .get(self.index)
+            .is_some_and(|t| t.token == TokenEnum::Eq && t.content == "=")
+    }
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.index);
+        if token.is_some() {
+            self.index += 1;
+        }
+        token
+    }
+    fn eat(&mut self, token: TokenEnum) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+    /// Record a syntax error at the span, unless an error is already recorded
+    /// at it or between the previous token and it.
+    /// At EOF, the gap extends to the end of input, e.g. over an unterminated comment.
+    fn error(&self, span: Span, code: Code, msg: String, errors: &mut ErrorRecorder) -> Error {
+        let end = if self.peek().is_none() {
+            usize::MAX
+        } else {
+            span.end
+        };
+        let gap = Span::new(self.prev_end(), end);
+        if !errors.has_error_at(span.start) && !errors.has_error_in(gap) {
+            errors.error(span, code, &msg);
+        }
+        anyhow!(msg)
+    }
+    fn unexpected(&self, expected: &str, errors: &mut ErrorRecorder) -> Error {
+        let msg = match self.peek() {
+            Some(token) => format!("Expected {}, found {:?}", expected, token),
+            None => format!("Expected {}, found EOF", expected),
+        };
+        self.error(self.peek_span(), Code::UnexpectedToken, msg, errors)
+    }
+    fn expect(&mut self, token: TokenEnum, errors: &mut ErrorRecorder) -> Result<&'a Token> {
+        match self.tokens.get(self.index) {
+            Some(t) if t.token == token => {
+                self.index += 1;
+                Ok(t)
+            }
+            _ => Err(self.unexpected(&format!("{:?}", token), errors)),
+        }
+    }
+    /// Span from the start to the end of the previous token.
+    fn span_from(&self, start: Span) -> Span {
+        Span::new(start.start, self.prev_end())
+    }
+    /// Match: begin s0; s1 end [.]
+    /// The procedures are parsed separately and left empty.
+    pub fn program(&mut self, errors: &mut ErrorRecorder) -> Result<Program> {
+        let start = self.peek_span();
+        self.expect(TokenEnum::Begin, errors)?;
+        let statements = self.block(errors);
+        self.eat(TokenEnum::Dot);
+        if self.peek().is_some() {
+            self.unexpected("EOF", errors);
+        }
+        Ok(Program {
+            procedures: Vec::new(),
+            statements,
+            span: self.span_from(start),
+        })
+    }
+    /// Match: s0; s1 end, after `begin`.
+    /// Empty statements are allowed, so `;` before `end` is fine.
+    fn block(&mut self, errors: &mut ErrorRecorder) -> Vec<Statement> {
+        let mut statements = Vec::new();
+        loop {
+            match self.peek() {
+                Some(TokenEnum::End) => {
+                    self.next();
+                    return statements;
+                }
+                Some(TokenEnum::SemiColon) => {
+                    self.next();
+                }
+                None => {
+                    self.unexpected("End", errors);
+                    return statements;
+                }
+                Some(TokenEnum::Else) => {
+                    self.unexpected("statement", errors);
+                    self.next();
+                }
+                _ => {
+                    match self.statement(errors) {
+                        Ok(statement) => statements.push(statement),
+                        Err(_) => self.skip_statement(),
+                    }
+                    let end = Span::from(self.prev_end());
+                    match self.peek() {
+                        Some(TokenEnum::SemiColon | TokenEnum::End) | None => {}
+                        Some(_) => {
+                            self.error(
+                                end,
+                                Code::MissingSemicolon,
+                                "Missing semicolon".to_string(),
+                                errors,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+    /// Skip to the `;`, `end` or `else` after the statement, skipping nested blocks.
+    fn skip_statement(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                TokenEnum::SemiColon | TokenEnum::End | TokenEnum::Else if depth == 0 => return,
+                TokenEnum::Begin | TokenEnum::Case => depth += 1,
+                TokenEnum::End => depth -= 1,
+                _ => {}
+            }
+            self.next();
+        }
+    }
+    fn statement(&mut self, errors: &mut ErrorRecorder) -> Result<Statement> {
+        let start = self.peek_span();
+        let kind = match self.peek() {
+            Some(TokenEnum::Identifier) => {
+                let target = self.variable(errors)?;
+                if self.at_single_eq() {
+                    let fix = Fix {
+                        span: self.peek_span(),
+                        replacement: ":=".to_string(),
+                    };
+                    errors.error_with_fix(
+                        fix,
+                        Code::UnexpectedEq,
+                        "Unexpected operator `=`. Did you mean `:=`?",
+                    );
+                    self.next();
+                } else if self.peek() == Some(TokenEnum::Eq) {
+                    let fix = Fix {
+                        span: self.peek_span(),
+                        replacement: ":=".to_string(),
+                    };
+                    errors.warning_with_fix(
+                        fix,
+                        Code::DiscardedComparison,
+                        "Comparison result is discarded. Did you mean `:=`?",
+                    );
+                    self.next();
+                } else {
+                    self.expect(TokenEnum::Assign, errors)?;
+                }
+                let value = self.expression(errors)?;
+                StatementKind::Assign { target, value }
+            }
+            Some(TokenEnum::If) => {
+                self.next();
+                let condition = self.expression(errors)?;
+                self.expect(TokenEnum::Then, errors)?;
+                let then_branch = Box::new(self.statement(errors)?);
+                let else_branch = if self.eat(TokenEnum::Else) {
+                    Some(Box::new(self.statement(errors)?))
+                } else {
+                    None
+                };
+                StatementKind::If {
+                    condition,
+                    then_branch,
+                    else_branch,
+                }
+            }
+            Some(TokenEnum::While) => {
+                self.next();
+                let condition = self.expression(errors)?;
+                self.expect(TokenEnum::Do, errors)?;
+                let body = Box::new(self.statement(errors)?);
+                StatementKind::While { condition, body }
+            }
+            Some(TokenEnum::Begin) => {
+                self.next();
+                StatementKind::Block {
+                    statements: self.block(errors),
+                }
+            }
+            Some(TokenEnum::Case) => {
+                self.next();
+                let selector = self.expression(errors)?;
+                self.expect(TokenEnum::Of, errors)?;
+                StatementKind::Case {
+                    selector,
+                    arms: self.arms(errors),
+                }
+            }
+            Some(TokenEnum::Call) => {
+                self.next();
+                let procedure = self.expect(TokenEnum::Identifier, errors)?;
+                let procedure = procedure.content.to_lowercase();
+                let args = match self.peek() {
+                    Some(TokenEnum::LParen) => self.args(errors)?,
+                    _ => Vec::new(),
+                };
+                StatementKind::Call { procedure, args }
+            }
+            Some(TokenEnum::Question) => {
+                self.next();
+                StatementKind::Read {
+                    targets: vec![self.variable(errors)?],
+                }
+            }
+            Some(TokenEnum::Exclamation) => {
+                self.next();
+                StatementKind::Write {
+                    values: vec![self.expression(errors)?],
+                }
+            }
+            Some(TokenEnum::Read) => {
+                self.next();
+                self.expect(TokenEnum::LParen, errors)?;
+                let mut targets = vec![self.variable(errors)?];
+                while self.eat(TokenEnum::Comma) {
+                    targets.push(self.variable(errors)?);
+                }
+                self.expect(TokenEnum::RParen, errors)?;
+                StatementKind::Read { targets }
+            }
+            Some(TokenEnum::Write) => {
+                self.next();
+                self.expect(TokenEnum::LParen, errors)?;
+                let mut values = vec![self.expression(errors)?];
+                while self.eat(TokenEnum::Comma) {
+                    values.push(self.expression(errors)?);
+                }
+                self.expect(TokenEnum::RParen, errors)?;
+                StatementKind::Write { values }
+            }
+            Some(TokenEnum::SemiColon | TokenEnum::End | TokenEnum::Else) | None => {
+                return Ok(Statement {
+                    kind: StatementKind::Empty,
+                    span: Span::from(self.prev_end()),
+                });
+            }
+            _ => {
+                let e = self.unexpected("statement", errors);
+                self.next();
+                return Err(e);
+            }
+        };
+        Ok(Statement {
+            kind,
+            span: self.span_from(start),
+        })
+    }
+    /// Match: l0, l1: s0; l2: s1 end, after `case e of`.
+    /// Like a block, empty arms are allowed and a syntax error skips the arm.
+    fn arms(&mut self, errors: &mut ErrorRecorder) -> Vec<CaseArm> {
+        let mut arms = Vec::new();
+        loop {
+            match self.peek() {
+                Some(TokenEnum::End) => {
+                    self.next();
+                    return arms;
+                }
+                Some(TokenEnum::SemiColon) => {
+                    self.next();
+                }
+                None => {
+                    self.unexpected("End", errors);
+                    return arms;
+                }
+                _ => {
+                    match self.arm(errors) {
+                        Ok(arm) => arms.push(arm),
+                        Err(_) => self.skip_statement(),
+                    }
+                    let end = Span::from(self.prev_end());
+                    match self.peek() {
+                        Some(TokenEnum::SemiColon | TokenEnum::End) | None => {}
+                        Some(_) => {
+                            self.error(
+                                end,
+                                Code::MissingSemicolon,
+                                "Missing semicolon".to_string(),
+                                errors,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+    fn arm(&mut self, errors: &mut ErrorRecorder) -> Result<CaseArm> {
+        let start = self.peek_span();
+        let mut labels = vec![self.expression(errors)?];
+        while self.eat(TokenEnum::Comma) {
+            labels.push(self.expression(errors)?);
+        }
+        self.expect(TokenEnum::Colon, errors)?;
+        let body = self.statement(errors)?;
+        Ok(CaseArm {
+            labels,
+            body,
+            span: self.span_from(start),
+        })
+    }
+    /// Match: i .f [e0, e1] ...
+    fn variable(&mut self, errors: &mut ErrorRecorder) -> Result<Variable> {
+        let start = self.peek_span();
+        let name = self
+            .expect(TokenEnum::Identifier, errors)?
+            .content
+            .to_lowercase();
+        let mut selectors = Vec::new();
+        loop {
+            if self.eat(TokenEnum::Dot) {
+                let field = self.expect(TokenEnum::Identifier, errors)?;
+                selectors.push(Selector::Field {
+                    name: field.content.to_lowercase(),
+                    span: field.span(),
+                });
+            } else if self.peek() == Some(TokenEnum::LBracket) {
+                let start = self.peek_span();
+                self.next();
+                let mut indices = vec![self.expression(errors)?];
+                while self.eat(TokenEnum::Comma) {
+                    indices.push(self.expression(errors)?);
+                }
+                self.expect(TokenEnum::RBracket, errors)?;
+                selectors.push(Selector::Index {
+                    indices,
+                    span: self.span_from(start),
+                });
+            } else {
+                break;
+            }
+        }
+        Ok(Variable {
+            name,
+            selectors,
+            span: self.span_from(start),
+        })
+    }
+    /// Match: (e0, e1)
+    fn args(&mut self, errors: &mut ErrorRecorder) -> Result<Vec<Expr>> {
+        self.expect(TokenEnum::LParen, errors)?;
+        let mut args = Vec::new();
+        if !self.eat(TokenEnum::RParen) {
+            args.push(self.expression(errors)?);
+            while self.eat(TokenEnum::Comma) {
+                args.push(self.expression(errors)?);
+            }
+            self.expect(TokenEnum::RParen, errors)?;
+        }
+        Ok(args)
+    }
+    fn expression(&mut self, errors: &mut ErrorRecorder) -> Result<Expr> {
+        self.climb(0, errors)
+    }
+    /// Precedence climbing: match operands joined by operators binding tighter than `min`.
+    /// Operators of the same precedence are left associative,
+    /// relational operators are not associative, so `a < b < c` is an error.
+    fn climb(&mut self, min: u8, errors: &mut ErrorRecorder) -> Result<Expr> {
+        let start = self.peek_span();
+        let mut lhs = self.unary(errors)?;
+        let mut chained = false;
+        while let Some(op) = self
+            .peek()
+            .and_then(BinaryOp::from_token)
+            .filter(|op| op.precedence() > min)
+        {
+            if op.is_relational() && chained {
+                let msg = "Comparisons cannot be chained, combine them with `and`".to_string();
+                self.error(self.peek_span(), Code::ChainedComparison, msg, errors);
+            }
+            chained = op.is_relational();
+            if self.at_single_eq() {
+                errors.error(
+                    self.peek_span(),
+                    Code::UnexpectedEq,
+                    "Unexpected operator `=`. Did you mean `==` or `:=`?",
+                );
+            }
+            self.next();
+            let rhs = self.climb(op.precedence(), errors)?;
+            lhs = Expr {
+                kind: ExprKind::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+                span: self.span_from(start),
+            };
+        }
+        Ok(lhs)
+    }
+    /// Match: - unary | + unary | odd e | not e | factor
+    /// The operand of `odd` extends over additive operators, `odd x + 1` is `odd (x + 1)`,
+    /// and the operand of `not` over relational ones, `not x < 1` is `not (x < 1)`.
+    fn unary(&mut self, errors: &mut ErrorRecorder) -> Result<Expr> {
+        let start = self.peek_span();
+        let (op, operand) = match self.peek() {
+            Some(TokenEnum::Add) => {
+                self.next();
+                return self.unary(errors);
+            }
+            Some(TokenEnum::Sub) => {
+                self.next();
+                (UnaryOp::Neg, self.unary(errors)?)
+            }
+            Some(TokenEnum::Odd) => {
+                self.next();
+                let relational = BinaryOp::Eq.precedence();
+                (UnaryOp::Odd, self.climb(relational, errors)?)
+            }
+            Some(TokenEnum::Not) => {
+                self.next();
+                let and = BinaryOp::And.precedence();
+                (UnaryOp::Not, self.climb(and, errors)?)
+            }
+            _ => return self.factor(errors),
+        };
+        Ok(Expr {
+            kind: ExprKind::Unary {
+                op,
+                operand: Box::new(operand),
+            },
+            span: self.span_from(start),
+        })
+    }
+    fn factor(&mut self, errors: &mut ErrorRecorder) -> Result<Expr> {
+        let start = self.peek_span();
+        let kind = match self.peek() {
+            Some(TokenEnum::IntLiteral) => {
+                let token = self.next().unwrap();
+                match token.content.parse() {
+                    Ok(value) => ExprKind::Number { value },
+                    Err(_) => {
+                        let msg = format!("Number out of range: {}", token.content);
+                        return Err(self.error(start, Code::NumberOutOfRange, msg, errors));
+                    }
+                }
+            }
+            Some(TokenEnum::RealLiteral) => {
+                let token = self.next().unwrap();
+                match token.content.parse() {
+                    Ok(value) => ExprKind::Real { value },
+                    Err(_) => {
+                        let msg = format!("Malformed number: {}", token.content);
+                        return Err(self.error(start, Code::MalformedNumber, msg, errors));
+                    }
+                }
+            }
+            Some(TokenEnum::CharLiteral) => {
+                let token = self.next().unwrap();
+                // The lexer only accepts a single character, a quote is doubled,
+                // but the tokens of a dump are not lexed
+                let inner = token
+                    .content
+                    .strip_prefix('\'')
+                    .and_then(|s| s.strip_suffix('\''));
+                let mut chars = inner.unwrap_or_default().chars();
+                match (chars.next(), chars.next(), chars.next()) {
+                    (Some('\''), Some('\''), None) => ExprKind::Char { value: '\'' },
+                    (Some(value), None, _) if value != '\'' => ExprKind::Char { value },
+                    _ => {
+                        let msg = format!("Malformed character literal: {}", token.content);
+                        return Err(self.error(start, Code::MalformedCharLiteral, msg, errors));
+                    }
+                }
+            }
+            Some(token @ (TokenEnum::True | TokenEnum::False)) => {
+                self.next();
+                ExprKind::Bool {
+                    value: token == TokenEnum::True,
+                }
+            }
+            Some(TokenEnum::Identifier)
+                if self.tokens.get(self.index + 1).map(|t| t.token) == Some(TokenEnum::LParen) =>
+            {
+                let function = self.next().unwrap().content.to_lowercase();
+                ExprKind::Call {
+                    function,
+                    args: self.args(errors)?,
+                }
+            }
+            Some(TokenEnum::Identifier) => ExprKind::Variable {
+                variable: self.variable(errors)?,
+            },
+            Some(TokenEnum::LParen) => {
+                self.next();
+                let expr = self.expression(errors)?;
+                self.expect(TokenEnum::RParen, errors)?;
+                return Ok(Expr {
+                    span: self.span_from(start),
+                    ..expr
+                });
+            }
+            _ => return Err(self.unexpected("expression", errors)),
+        };
+        Ok(Expr {
+            kind,
+            span: self.span_from(start),
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use std::iter::Peekable;
+
+use crate::derivation::{is_nonterminal, Production, START_SYMBOL};
+
+/// Concrete parse tree of the var block.
+/// Internal nodes are labeled by the production applied,
+/// leaves are terminals or nonterminals left unexpanded by a failed parse.
+#[derive(Debug, Clone)]
+pub struct ParseTree {
+    pub symbol: &'static str,
+    pub production: Option<Production>,
+    pub children: Vec<ParseTree>,
+}
+impl ParseTree {
+    /// Rebuild the tree from the leftmost derivation.
+    /// In preorder, the next production always expands the current nonterminal.
+    pub fn from_trace(trace: &[Production]) -> Self {
+        Self::build(START_SYMBOL, &mut trace.iter().copied().peekable())
+    }
+    fn build<I: Iterator<Item = Production>>(
+        symbol: &'static str,
+        trace: &mut Peekable<I>,
+    ) -> Self {
+        let production = if is_nonterminal(symbol) {
+            trace.next_if(|p| p.lhs() == symbol)
+        } else {
+            None
+        };
+        let children = production
+            .map(|p| p.rhs().iter().map(|s| Self::build(s, trace)).collect())
+            .unwrap_or_default();
+        Self {
+            symbol,
+            production,
+            children,
+        }
+    }
+    pub fn label(&self) -> String {
+        match self.production {
+            Some(p) => p.display(),
+            None => self.symbol.to_string(),
+        }
+    }
+    /// Render the tree with box-drawing characters.
+    /// ```plaintext
+    /// S -> Var D
+    /// ├── Var
+    /// └── D -> D1 D'
+    ///     ├── D1 -> I : t ;
+    /// ```
+    pub fn to_text(&self) -> String {
+        let mut result = format!("{}\n", self.label());
+        self.text_children("", &mut result);
+        result
+    }
+    fn text_children(&self, prefix: &str, result: &mut String) {
+        for (i, child) in self.children.iter().enumerate() {
+            let last = i + 1 == self.children.len();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            result.push_str(&format!("{}{}{}\n", prefix, branch, child.label()));
+            child.text_children(&format!("{}{}", prefix, indent), result);
+        }
+    }
+    /// Render the tree in Graphviz DOT format.
+    /// Terminals are drawn without a box.
+    pub fn to_dot(&self) -> String {
+        let mut result = "digraph ParseTree {\n".to_string();
+        self.dot_node(&mut 0, &mut result);
+        result.push_str("}\n");
+        result
+    }
+    /// Returns the id of the node.
+    fn dot_node(&self, next_id: &mut usize, result: &mut String) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+        let shape = if is_nonterminal(self.symbol) {
+            "box"
+        } else {
+            "plaintext"
+        };
+        result.push_str(&format!(
+            "    n{} [label=\"{}\", shape={}];\n",
+            id,
+            self.label().replace('\\', "\\\\").replace('"', "\\\""),
+            shape
+        ));
+        for child in &self.children {
+            let child_id = child.dot_node(next_id, result);
+            result.push_str(&format!("    n{} -> n{};\n", id, child_id));
+        }
+        id
+    }
+}
@@ -1,9 +1,17 @@
 use anyhow::{anyhow, Error, Result};
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, fmt, mem, ops::Range};
 
 use crate::{
+    ast::{Procedure, Program},
+    codes::Code,
+    derivation::{describe_terminal, terminal_of, var_block_grammar, Production},
+    earley::{Earley, Repair},
     error::ErrorRecorder,
-    lex::{lex, Token, TokenEnum},
+    lex::{lex, LexOptions, Token, TokenEnum},
+    parse::Parser,
+    source_map::{FileId, SourceMap, Span},
+    table::Tables,
+    typecheck::TypeChecker,
 };
 #[derive(Debug, Clone, PartialEq)]
 pub enum TypeEnum {
@@ -11,6 +19,73 @@ pub enum TypeEnum {
     Longint,
     Bool,
     Real,
+    Char,
+    /// Fields in declaration order.
+    Record(Vec<(String, TypeEnum)>),
+    /// Enumerators in ordinal order.
+    Enum(Vec<String>),
+    /// Integer subrange `low..high`, inclusive.
+    Subrange(i64, i64),
+    /// Bounds of each dimension and the element type.
+    Array(Vec<(i64, i64)>, Box<TypeEnum>),
+}
+impl fmt::Display for TypeEnum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Record(fields) => {
+                write!(f, "record")?;
+                for (name, type_enum) in fields {
+                    write!(f, " {}: {};", name, type_enum)?;
+                }
+                write!(f, " end")
+            }
+            Self::Enum(enumerators) => write!(f, "({})", enumerators.join(", ")),
+            Self::Subrange(low, high) => write!(f, "{}..{}", low, high),
+            Self::Array(bounds, element) => {
+                let bounds: Vec<_> = bounds
+                    .iter()
+                    .map(|(l, h)| format!("{}..{}", l, h))
+                    .collect();
+                write!(f, "array [{}] of {}", bounds.join(", "), element)
+            }
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+/// Procedure or function in the symbol table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Callable {
+    /// Parameters in declaration order.
+    pub params: Vec<(String, TypeEnum)>,
+    /// Result type, None for a procedure.
+    pub result: Option<TypeEnum>,
+    /// Local variables, not including the parameters.
+    pub locals: BTreeMap<String, TypeEnum>,
+}
+impl fmt::Display for Callable {
+    /// Signature after the name, e.g. `(a: Integer, b: Real): Bool`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params: Vec<_> = self
+            .params
+            .iter()
+            .map(|(name, t)| format!("{}: {}", name, t))
+            .collect();
+        write!(f, "({})", params.join(", "))?;
+        match &self.result {
+            Some(result) => write!(f, ": {}", result),
+            None => Ok(()),
+        }
+    }
+}
+impl TypeEnum {
+    /// Integer, longint or an integer subrange.
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Self::Integer | Self::Longint | Self::Subrange(..))
+    }
+    /// Integer types and real.
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || *self == Self::Real
+    }
 }
 impl TryFrom<TokenEnum> for TypeEnum {
     type Error = Error;
@@ -20,18 +95,58 @@ impl TryFrom<TokenEnum> for TypeEnum {
             TokenEnum::Longint => Ok(Self::Longint),
             TokenEnum::Bool => Ok(Self::Bool),
             TokenEnum::Real => Ok(Self::Real),
+            TokenEnum::Char => Ok(Self::Char),
             _ => Err(anyhow!("Expected type, found {:?}", token)),
         }
     }
 }
 #[derive(Debug, Clone)]
-pub struct TokenStream {
-    tokens: Vec<Token>,
+pub struct TokenStream<'a> {
+    tokens: &'a [Token],
     index: usize,
+    /// Index of an identifier glued to `var`, e.g. `varx`, read without the prefix.
+    glued_var: Option<usize>,
+    /// Productions applied so far, in leftmost derivation order.
+    trace: Vec<Production>,
+    /// Symbol table of the var block.
+    vars: BTreeMap<String, TypeEnum>,
+    /// Name in the program header.
+    program: Option<String>,
+    /// Integer constants with their values.
+    consts: BTreeMap<String, i64>,
+    /// Type aliases, resolved to the underlying type.
+    types: BTreeMap<String, TypeEnum>,
+    /// Enumerators with their enumeration type and ordinal.
+    enumerators: BTreeMap<String, (TypeEnum, usize)>,
+    /// Procedures and functions.
+    callables: BTreeMap<String, Callable>,
+    /// Spans of the declarations in scope, to point at the original of a duplicate.
+    declarations: BTreeMap<String, Span>,
+    /// Token ranges of the procedure bodies, from `begin` to `end`.
+    bodies: Vec<(String, Range<usize>)>,
+    /// Index of the first token after the program header.
+    var_block_start: usize,
+    /// Index of the `begin` of the program block.
+    program_block_start: usize,
 }
-impl TokenStream {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, index: 0 }
+impl<'a> TokenStream<'a> {
+    pub fn new(tokens: &'a [Token]) -> Self {
+        Self {
+            tokens,
+            index: 0,
+            glued_var: None,
+            trace: Vec::new(),
+            vars: BTreeMap::new(),
+            program: None,
+            consts: BTreeMap::new(),
+            types: BTreeMap::new(),
+            enumerators: BTreeMap::new(),
+            callables: BTreeMap::new(),
+            declarations: BTreeMap::new(),
+            bodies: Vec::new(),
+            var_block_start: 0,
+            program_block_start: 0,
+        }
     }
     pub fn peek(&self) -> Option<&TokenEnum> {
         self.tokens.get(self.index).map(|t| &t.token)
@@ -39,13 +154,22 @@ impl TokenStream {
     pub fn peek_content(&self) -> Option<&str> {
         self.tokens.get(self.index).map(|t| t.content.as_str())
     }
-    pub fn peek_pos(&self) -> usize {
+    /// Span of the next token, empty at the end of input.
+    pub fn peek_span(&self) -> Span {
         self.tokens
             .get(self.index)
-            .map(|t| t.offset)
-            .unwrap_or(usize::MAX)
+            .map(|t| t.span())
+            .unwrap_or(Span::from(usize::MAX))
     }
-    pub fn next(&mut self) -> Option<&Token> {
+    /// End of the previous token, where a missing token is reported.
+    fn prev_end(&self) -> usize {
+        self.index
+            .checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .map(|t| t.span().end)
+            .unwrap_or(0)
+    }
+    fn next(&mut self) -> Option<&Token> {
         let token = self.tokens.get(self.index);
         if token.is_some() {
             self.index += 1;
@@ -53,95 +177,394 @@ impl TokenStream {
         token
     }
     pub fn expect(&mut self, token: TokenEnum, errors: &mut ErrorRecorder) -> Result<&Token> {
-        let pos = self.peek_pos();
+        let span = self.peek_span();
         match self.next() {
             Some(t) if t.token == token => Ok(t),
-            Some(t) => errors.hard(pos, format!("Expected {:?}, found {:?}", token, t.token)),
-            None => errors.hard(pos, format!("Expected {:?}, found EOF", token)),
+            Some(t) => errors.hard(
+                span,
+                Code::UnexpectedToken,
+                format!("Expected {:?}, found {:?}", token, t.token),
+            ),
+            None => errors.hard(
+                span,
+                Code::UnexpectedToken,
+                format!("Expected {:?}, found EOF", token),
+            ),
         }
     }
     /// Match identifier
-    /// Returns (identifier_lowercase, offset)
-    pub fn identifier(&mut self, errors: &mut ErrorRecorder) -> Result<(String, usize)> {
+    /// Returns (identifier_lowercase, span)
+    pub fn identifier(&mut self, errors: &mut ErrorRecorder) -> Result<(String, Span)> {
+        let glued = self.glued_var == Some(self.index);
         let token = self.expect(TokenEnum::Identifier, errors)?;
-        Ok((token.content.to_lowercase(), token.offset))
+        if glued {
+            let span = Span::new(token.offset + "var".len(), token.end);
+            Ok((token.content["var".len()..].to_lowercase(), span))
+        } else {
+            Ok((token.content.to_lowercase(), token.span()))
+        }
     }
     /// Match: i0, i1, i2
-    /// Return vec of (identifier, offset)
-    pub fn identifier_list(&mut self, errors: &mut ErrorRecorder) -> Result<Vec<(String, usize)>> {
+    /// Return vec of (identifier, span)
+    pub fn identifier_list(&mut self, errors: &mut ErrorRecorder) -> Result<Vec<(String, Span)>> {
         let mut identifiers = Vec::new();
+        self.trace.push(Production::IdentList);
         identifiers.push(self.identifier(errors)?);
         loop {
             match self.peek() {
-                Some(TokenEnum::Colon) => return Ok(identifiers),
+                Some(TokenEnum::Colon) => {
+                    self.trace.push(Production::IdentListEnd);
+                    return Ok(identifiers);
+                }
                 Some(TokenEnum::Comma) => {
+                    self.trace.push(Production::IdentListMore);
                     self.next();
                     identifiers.push(self.identifier(errors)?);
                 }
                 Some(TokenEnum::Identifier) => {
-                    errors.error(self.peek_pos(), "Missing comma");
+                    errors.error(
+                        self.peek_span().start,
+                        Code::MissingDelimiter,
+                        "Missing comma",
+                    );
+                    self.trace.push(Production::IdentListMore);
                     identifiers.push(self.identifier(errors)?);
                 }
                 _ => {
-                    return errors.hard(self.peek_pos(), "Expected comma or colon");
+                    return errors.hard(
+                        self.peek_span(),
+                        Code::UnexpectedToken,
+                        "Expected comma or colon",
+                    );
                 }
             }
         }
     }
-    /// Match: i0, i1, i2: Type;
-    pub fn def_line(
-        &mut self,
-        vars: &mut BTreeMap<String, TypeEnum>,
-        errors: &mut ErrorRecorder,
-    ) -> Result<()> {
-        let identifiers = self.identifier_list(errors)?;
-        self.expect(TokenEnum::Colon, errors)?;
-        let pos = self.peek_pos();
-        let type_enum = match self.next().and_then(|t| TypeEnum::try_from(t.token).ok()) {
-            Some(t) => t,
-            None => {
-                return errors.hard(pos, "Expected type");
+    /// Match: a type keyword, a record type or a declared alias, returns the underlying type.
+    pub fn type_name(&mut self, errors: &mut ErrorRecorder) -> Result<TypeEnum> {
+        let span = self.peek_span();
+        match self.next().map(|t| (t.token, t.content.to_lowercase())) {
+            Some((TokenEnum::Record, _)) => self.record_fields(errors),
+            Some((TokenEnum::LParen, _)) => self.enumeration(errors),
+            Some((TokenEnum::Array, _)) => self.array(errors),
+            Some((TokenEnum::IntLiteral | TokenEnum::Sub, _)) => {
+                self.index -= 1;
+                self.subrange(errors)
+            }
+            Some((TokenEnum::Identifier, name)) if self.consts.contains_key(&name) => {
+                self.index -= 1;
+                self.subrange(errors)
+            }
+            Some((TokenEnum::Identifier, name)) => match self.types.get(&name) {
+                Some(t) => Ok(t.clone()),
+                None => errors.hard(span, Code::UnknownType, format!("Unknown type: {}", name)),
+            },
+            Some((token, _)) => match TypeEnum::try_from(token) {
+                Ok(t) => Ok(t),
+                Err(_) => errors.hard(span, Code::UnexpectedToken, "Expected type"),
+            },
+            None => errors.hard(span, Code::UnexpectedToken, "Expected type"),
+        }
+    }
+    /// Match: i0, i1: Type; ... end
+    /// The fields are not part of the var block grammar, so no production is recorded.
+    fn record_fields(&mut self, errors: &mut ErrorRecorder) -> Result<TypeEnum> {
+        let mut fields: Vec<(String, TypeEnum)> = Vec::new();
+        while self.peek() != Some(&TokenEnum::End) {
+            let mut names = vec![self.identifier(errors)?];
+            while self.peek() == Some(&TokenEnum::Comma) {
+                self.next();
+                names.push(self.identifier(errors)?);
+            }
+            self.expect(TokenEnum::Colon, errors)?;
+            let type_enum = self.type_name(errors)?;
+            if self.peek() != Some(&TokenEnum::End) {
+                self.semicolon(errors);
+            }
+            for (name, span) in names {
+                if fields.iter().any(|(f, _)| *f == name) {
+                    errors.error(
+                        span,
+                        Code::DuplicateField,
+                        format!("Duplicate field: {}", name),
+                    );
+                } else {
+                    fields.push((name, type_enum.clone()));
+                }
+            }
+        }
+        self.next();
+        Ok(TypeEnum::Record(fields))
+    }
+    /// Match: i0, i1, ...)
+    /// The enumerators are declared as constants of the enumeration.
+    fn enumeration(&mut self, errors: &mut ErrorRecorder) -> Result<TypeEnum> {
+        let mut names = vec![self.identifier(errors)?];
+        while self.peek() == Some(&TokenEnum::Comma) {
+            self.next();
+            names.push(self.identifier(errors)?);
+        }
+        self.expect(TokenEnum::RParen, errors)?;
+        let mut enumerators: Vec<String> = Vec::new();
+        let mut declared = Vec::new();
+        for (name, span) in names {
+            if enumerators.contains(&name) {
+                errors.error(
+                    span,
+                    Code::DuplicateEnumerator,
+                    format!("Duplicate enumerator: {}", name),
+                );
+                self.label_declaration(&name, errors);
+            } else if let Some((other, _)) = self.enumerators.get(&name) {
+                errors.error(
+                    span,
+                    Code::DuplicateEnumerator,
+                    format!("Duplicate enumerator: {}", name),
+                );
+                self.label_declaration(&name, errors);
+                errors.note(format!("{} is already an enumerator of {}", name, other));
+            } else if let Some(declared_as) = self.declared_as(&name) {
+                errors.error(
+                    span,
+                    Code::DuplicateIdentifier,
+                    format!("Duplicate identifier: {}", name),
+                );
+                self.label_declaration(&name, errors);
+                errors.note(format!("{} is declared as {}", name, declared_as));
+            } else {
+                self.declarations.insert(name.clone(), span);
+                declared.push(name.clone());
+            }
+            enumerators.push(name);
+        }
+        enumerators.dedup();
+        let type_enum = TypeEnum::Enum(enumerators.clone());
+        for name in declared {
+            let ordinal = enumerators.iter().position(|e| *e == name).unwrap();
+            self.enumerators.insert(name, (type_enum.clone(), ordinal));
+        }
+        Ok(type_enum)
+    }
+    /// Match: an integer literal with an optional `-`.
+    /// Match: an optionally negated integer literal or constant.
+    fn integer(&mut self, errors: &mut ErrorRecorder) -> Result<i64> {
+        let negative = self.peek() == Some(&TokenEnum::Sub);
+        if negative {
+            self.next();
+        }
+        let span = self.peek_span();
+        if let Some(&value) = self
+            .peek_content()
+            .and_then(|s| self.consts.get(&s.to_lowercase()))
+        {
+            self.next();
+            return Ok(if negative { -value } else { value });
+        }
+        let token = self.expect(TokenEnum::IntLiteral, errors)?;
+        let value = match token.content.parse::<i64>() {
+            Ok(value) => value,
+            Err(_) => {
+                return errors.hard(
+                    span,
+                    Code::NumberOutOfRange,
+                    format!("Number out of range: {}", token.content),
+                )
             }
         };
+        Ok(if negative { -value } else { value })
+    }
+    /// Match: low..high
+    fn subrange(&mut self, errors: &mut ErrorRecorder) -> Result<TypeEnum> {
+        let span = self.peek_span();
+        let low = self.integer(errors)?;
+        self.expect(TokenEnum::DotDot, errors)?;
+        let high = self.integer(errors)?;
+        if low > high {
+            errors.error(
+                span.merge(self.peek_span()),
+                Code::EmptySubrange,
+                format!("Empty subrange: {}..{}", low, high),
+            );
+        }
+        Ok(TypeEnum::Subrange(low, high))
+    }
+    /// Match: [l0..h0, l1..h1] of Type
+    /// Each index type is a subrange, possibly through an alias.
+    fn array(&mut self, errors: &mut ErrorRecorder) -> Result<TypeEnum> {
+        self.expect(TokenEnum::LBracket, errors)?;
+        let mut bounds = Vec::new();
+        loop {
+            let span = self.peek_span();
+            match self.type_name(errors)? {
+                TypeEnum::Subrange(low, high) => bounds.push((low, high)),
+                t => {
+                    return errors.hard(
+                        span,
+                        Code::InvalidIndexType,
+                        format!("Expected subrange as index type, found {}", t),
+                    )
+                }
+            }
+            if self.peek() != Some(&TokenEnum::Comma) {
+                break;
+            }
+            self.next();
+        }
+        self.expect(TokenEnum::RBracket, errors)?;
+        self.expect(TokenEnum::Of, errors)?;
+        let element = self.type_name(errors)?;
+        Ok(TypeEnum::Array(bounds, Box::new(element)))
+    }
+    /// What the identifier is declared as, if it is a type alias, a variable or an enumerator.
+    fn declared_as(&self, name: &str) -> Option<String> {
+        if let Some(value) = self.consts.get(name) {
+            Some(format!("a constant of value {}", value))
+        } else if let Some(t) = self.types.get(name) {
+            Some(format!("a type alias of {}", t))
+        } else if let Some(t) = self.vars.get(name) {
+            Some(format!("a variable of type {}", t))
+        } else if let Some((t, _)) = self.enumerators.get(name) {
+            Some(format!("an enumerator of {}", t))
+        } else {
+            self.callables.get(name).map(|c| match &c.result {
+                Some(t) => format!("a function returning {}", t),
+                None => "a procedure".to_string(),
+            })
+        }
+    }
+    /// Label the last diagnostic with the original declaration of the identifier.
+    fn label_declaration(&self, name: &str, errors: &mut ErrorRecorder) {
+        if let Some(&span) = self.declarations.get(name) {
+            errors.label(span, format!("{} first declared here", name));
+        }
+    }
+    /// Match `;`, a missing one is not a hard error.
+    fn semicolon(&mut self, errors: &mut ErrorRecorder) {
         match self.peek() {
             Some(TokenEnum::SemiColon) => {
                 self.next();
             }
             _ => {
-                let pos = self
-                    .tokens
-                    .get(self.index - 1)
-                    .map(|t| t.offset + t.content.len())
-                    .unwrap_or(0);
-                errors.error(pos, "Missing semicolon");
+                errors.error(self.prev_end(), Code::MissingSemicolon, "Missing semicolon");
             }
         }
-        for (identifier, offset) in identifiers {
-            if vars.contains_key(&identifier) {
-                errors.error(offset, format!("Duplicate identifier: {}", identifier));
+    }
+    /// Match: i0, i1, i2: Type;
+    pub fn def_line(&mut self, errors: &mut ErrorRecorder) -> Result<()> {
+        self.trace.push(Production::DefLine);
+        let identifiers = self.identifier_list(errors)?;
+        self.expect(TokenEnum::Colon, errors)?;
+        let type_enum = self.type_name(errors)?;
+        self.semicolon(errors);
+        for (identifier, span) in identifiers {
+            if self.vars.contains_key(&identifier) {
+                errors.error(
+                    span,
+                    Code::DuplicateIdentifier,
+                    format!("Duplicate identifier: {}", identifier),
+                );
+                self.label_declaration(&identifier, errors);
+                errors.note("each identifier may be declared only once, ignoring case");
+            } else if let Some(declared_as) = self.declared_as(&identifier) {
+                errors.error(
+                    span,
+                    Code::DuplicateIdentifier,
+                    format!("Duplicate identifier: {}", identifier),
+                );
+                self.label_declaration(&identifier, errors);
+                errors.note(format!("{} is declared as {}", identifier, declared_as));
             } else {
-                vars.insert(identifier.clone(), type_enum.clone());
+                self.declarations.insert(identifier.clone(), span);
+                self.vars.insert(identifier.clone(), type_enum.clone());
+            }
+        }
+        Ok(())
+    }
+    /// Match: const i = n; ... ;
+    /// The const block is optional and not part of the var block grammar.
+    pub fn const_block(&mut self, errors: &mut ErrorRecorder) -> Result<()> {
+        if self.peek() != Some(&TokenEnum::Const) {
+            return Ok(());
+        }
+        self.next();
+        while self.peek() == Some(&TokenEnum::Identifier) {
+            let (name, span) = self.identifier(errors)?;
+            self.expect(TokenEnum::Eq, errors)?;
+            let value = self.integer(errors)?;
+            self.semicolon(errors);
+            if let Some(declared_as) = self.declared_as(&name) {
+                errors.error(
+                    span,
+                    Code::DuplicateIdentifier,
+                    format!("Duplicate identifier: {}", name),
+                );
+                self.label_declaration(&name, errors);
+                errors.note(format!("{} is declared as {}", name, declared_as));
+            } else {
+                self.declarations.insert(name.clone(), span);
+                self.consts.insert(name, value);
+            }
+        }
+        Ok(())
+    }
+    /// Match: type i = Type; ... ;
+    /// The type block is optional and not part of the var block grammar.
+    /// Aliases are resolved to the underlying type when declared.
+    pub fn type_block(&mut self, errors: &mut ErrorRecorder) -> Result<()> {
+        if self.peek() != Some(&TokenEnum::Type) {
+            return Ok(());
+        }
+        self.next();
+        while self.peek() == Some(&TokenEnum::Identifier) {
+            let (name, span) = self.identifier(errors)?;
+            self.expect(TokenEnum::Eq, errors)?;
+            let underlying = self.type_name(errors)?;
+            self.semicolon(errors);
+            if let Some(previous) = self.types.get(&name) {
+                errors.error(
+                    span,
+                    Code::DuplicateType,
+                    format!("Duplicate type: {}", name),
+                );
+                self.label_declaration(&name, errors);
+                errors.note(format!(
+                    "{} is already declared as a type alias of {}",
+                    name, previous
+                ));
+            } else if let Some(declared_as) = self.declared_as(&name) {
+                errors.error(
+                    span,
+                    Code::DuplicateIdentifier,
+                    format!("Duplicate identifier: {}", name),
+                );
+                self.label_declaration(&name, errors);
+                errors.note(format!("{} is declared as {}", name, declared_as));
+            } else {
+                self.declarations.insert(name.clone(), span);
+                self.types.insert(name, underlying);
             }
         }
         Ok(())
     }
     /// Match: var i0, i1, i2: Type; ... ;
-    pub fn var_block(&mut self, errors: &mut ErrorRecorder) -> Result<BTreeMap<String, TypeEnum>> {
-        let mut identifiers = BTreeMap::new();
+    /// Declared identifiers are recorded in the symbol table.
+    pub fn var_block(&mut self, errors: &mut ErrorRecorder) -> Result<()> {
         match self.peek() {
             Some(TokenEnum::Var) => {
                 self.next();
             }
-            Some(TokenEnum::Begin) => {
-                return Ok(identifiers);
+            Some(TokenEnum::Begin | TokenEnum::Procedure | TokenEnum::Function) => {
+                self.trace.push(Production::EmptyVarBlock);
+                return Ok(());
             }
             Some(TokenEnum::Identifier) => match self.peek_content() {
                 Some(s) if s.to_lowercase().starts_with("var") => {
                     errors.error(
-                        self.peek_pos() + "var".len(),
+                        self.peek_span().start + "var".len(),
+                        Code::GluedVar,
                         "Did you forget a space after var?",
                     );
-                    self.tokens[self.index].content = s[3..].to_string();
+                    self.glued_var = Some(self.index);
                 }
                 _ => {
                     self.expect(TokenEnum::Var, errors)?;
@@ -151,50 +574,471 @@ impl TokenStream {
                 self.expect(TokenEnum::Var, errors)?;
             }
         }
-        while self.peek().map_or(false, |t| t != &TokenEnum::Begin) {
-            self.def_line(&mut identifiers, errors)?;
+        self.trace.push(Production::VarBlock);
+        let mut first = true;
+        while self.peek().is_some_and(|t| {
+            !matches!(
+                t,
+                TokenEnum::Begin | TokenEnum::Procedure | TokenEnum::Function
+            )
+        }) {
+            self.trace.push(if first {
+                Production::DefList
+            } else {
+                Production::DefListMore
+            });
+            first = false;
+            self.def_line(errors)?;
+        }
+        if !first {
+            self.trace.push(Production::DefListEnd);
+        }
+        Ok(())
+    }
+    /// Match: procedure i (i0: Type; ...); var ... begin ... end;
+    /// or: function i (i0: Type; ...): Type; var ... begin ... end;
+    /// Declarations follow the var block, a callable is visible from its own body on.
+    /// Parameters and locals are only visible in the body, where they shadow the globals.
+    pub fn procedure_block(&mut self, errors: &mut ErrorRecorder) -> Result<()> {
+        while let Some(&kind @ (TokenEnum::Procedure | TokenEnum::Function)) = self.peek() {
+            self.next();
+            let (name, span) = self.identifier(errors)?;
+            let globals_declared = self.declarations.clone();
+            let params = match self.peek() {
+                Some(TokenEnum::LParen) => self.parameters(errors)?,
+                _ => Vec::new(),
+            };
+            let result = match kind {
+                TokenEnum::Function => {
+                    self.expect(TokenEnum::Colon, errors)?;
+                    Some(self.type_name(errors)?)
+                }
+                _ => None,
+            };
+            self.semicolon(errors);
+            let duplicate = self.declared_as(&name);
+            if let Some(declared_as) = &duplicate {
+                errors.error(
+                    span,
+                    Code::DuplicateIdentifier,
+                    format!("Duplicate identifier: {}", name),
+                );
+                if let Some(&original) = globals_declared.get(&name) {
+                    errors.label(original, format!("{} first declared here", name));
+                }
+                errors.note(format!("{} is declared as {}", name, declared_as));
+            }
+            let mut scope: BTreeMap<_, _> = params.iter().cloned().collect();
+            if let Some(result) = &result {
+                // Assigning to the function name sets the result
+                scope.insert(name.clone(), result.clone());
+                self.declarations.insert(name.clone(), span);
+            }
+            let globals = mem::replace(&mut self.vars, scope);
+            let locals = self.local_vars(errors);
+            let scope = mem::replace(&mut self.vars, globals);
+            locals?;
+            let locals = scope
+                .iter()
+                .filter(|(n, _)| **n != name && params.iter().all(|(p, _)| p != *n))
+                .map(|(n, t)| (n.clone(), t.clone()))
+                .collect();
+            if duplicate.is_none() {
+                let callable = Callable {
+                    params,
+                    result,
+                    locals,
+                };
+                self.callables.insert(name.clone(), callable);
+            }
+            let start = self.index;
+            let body = self.statements(true, errors);
+            self.declarations = globals_declared;
+            if duplicate.is_none() {
+                self.declarations.insert(name.clone(), span);
+            }
+            body?;
+            self.bodies.push((name, start..self.index));
+            self.semicolon(errors);
+        }
+        Ok(())
+    }
+    /// Match: (i0, i1: Type; i2: Type)
+    fn parameters(&mut self, errors: &mut ErrorRecorder) -> Result<Vec<(String, TypeEnum)>> {
+        self.expect(TokenEnum::LParen, errors)?;
+        let mut params: Vec<(String, TypeEnum)> = Vec::new();
+        while self.peek() != Some(&TokenEnum::RParen) {
+            let mut names = vec![self.identifier(errors)?];
+            while self.peek() == Some(&TokenEnum::Comma) {
+                self.next();
+                names.push(self.identifier(errors)?);
+            }
+            self.expect(TokenEnum::Colon, errors)?;
+            let type_enum = self.type_name(errors)?;
+            for (name, span) in names {
+                if params.iter().any(|(p, _)| *p == name) {
+                    errors.error(
+                        span,
+                        Code::DuplicateParameter,
+                        format!("Duplicate parameter: {}", name),
+                    );
+                    self.label_declaration(&name, errors);
+                } else {
+                    self.declarations.insert(name.clone(), span);
+                    params.push((name, type_enum.clone()));
+                }
+            }
+            if self.peek() != Some(&TokenEnum::SemiColon) {
+                break;
+            }
+            self.next();
+        }
+        self.expect(TokenEnum::RParen, errors)?;
+        Ok(params)
+    }
+    /// Match: var i0, i1: Type; ... of a procedure, declared in `self.vars`.
+    /// Local declarations are not part of the var block grammar, so no production is recorded.
+    fn local_vars(&mut self, errors: &mut ErrorRecorder) -> Result<()> {
+        if self.peek() != Some(&TokenEnum::Var) {
+            return Ok(());
+        }
+        self.next();
+        let trace_len = self.trace.len();
+        let mut result = Ok(());
+        while result.is_ok() && self.peek() == Some(&TokenEnum::Identifier) {
+            result = self.def_line(errors);
         }
-        Ok(identifiers)
+        self.trace.truncate(trace_len);
+        result
     }
     /// Match: begin ... end
-    /// Check if the identifiers have been declared.
-    pub fn program_block(
-        &mut self,
-        vars: &BTreeMap<String, TypeEnum>,
-        errors: &mut ErrorRecorder,
-    ) -> Result<()> {
+    /// The statements are parsed and checked on the AST after the declarations.
+    pub fn program_block(&mut self, errors: &mut ErrorRecorder) -> Result<()> {
+        self.statements(false, errors)
+    }
+    /// Match: begin ... end, skipping the statements.
+    /// A procedure body ends at the matching `end`, the program block at the end of input.
+    fn statements(&mut self, body: bool, errors: &mut ErrorRecorder) -> Result<()> {
         if self.peek() != Some(&TokenEnum::Begin) {
-            return errors.hard(self.peek_pos(), "Expected begin");
+            return errors.hard(self.peek_span(), Code::UnexpectedToken, "Expected begin");
         }
-        while let Some(token) = self.peek() {
+        let mut depth = 0;
+        while let Some(&token) = self.peek() {
+            self.next();
             match token {
-                TokenEnum::Identifier => {
-                    let token = self.next().unwrap();
-                    let s = token.content.to_lowercase();
-                    if !vars.contains_key(&s) {
-                        errors.error(token.offset, format!("Undeclared identifier: {}", s));
+                TokenEnum::Begin | TokenEnum::Case => depth += 1,
+                TokenEnum::End => {
+                    depth -= 1;
+                    if body && depth == 0 {
+                        return Ok(());
                     }
                 }
-                _ => {
-                    self.next();
-                }
+                _ => {}
             }
         }
         Ok(())
     }
+    /// Match: program i ;
+    /// The header is optional. Errors in it are not hard, so the var block is still checked.
+    pub fn program_header(&mut self, errors: &mut ErrorRecorder) {
+        if self.peek() == Some(&TokenEnum::Program) {
+            self.next();
+            match self.peek() {
+                Some(TokenEnum::Identifier) => {
+                    let name = self.next().unwrap().content.to_lowercase();
+                    self.program = Some(name);
+                }
+                _ => errors.error(
+                    self.prev_end(),
+                    Code::UnexpectedToken,
+                    "Expected program name",
+                ),
+            }
+            if self.peek() == Some(&TokenEnum::SemiColon) {
+                self.next();
+            } else {
+                errors.error(self.prev_end(), Code::MissingSemicolon, "Missing semicolon");
+            }
+        }
+        self.var_block_start = self.index;
+    }
     pub fn code(&mut self, errors: &mut ErrorRecorder) -> Result<()> {
-        let vars = self.var_block(errors)?;
-        self.program_block(&vars, errors)
+        self.program_header(errors);
+        self.const_block(errors)?;
+        self.type_block(errors)?;
+        self.var_block_start = self.index;
+        self.var_block(errors)?;
+        self.procedure_block(errors)?;
+        self.program_block_start = self.index;
+        self.program_block(errors)
+    }
+}
+/// Everything recovered by the pre-grammar phase.
+#[derive(Debug, Clone)]
+pub struct ParseResult {
+    pub tokens: Vec<Token>,
+    pub tables: Tables,
+    /// Productions applied to the var block, in leftmost derivation order.
+    pub trace: Vec<Production>,
+    /// Symbol table, partial if the var block was aborted.
+    pub vars: BTreeMap<String, TypeEnum>,
+    /// Name in the program header, if any.
+    pub program: Option<String>,
+    /// Integer constants with their values.
+    pub consts: BTreeMap<String, i64>,
+    /// Type aliases with their underlying types.
+    pub types: BTreeMap<String, TypeEnum>,
+    /// Enumerators with their enumeration type and ordinal.
+    pub enumerators: BTreeMap<String, (TypeEnum, usize)>,
+    /// Procedures and functions.
+    pub callables: BTreeMap<String, Callable>,
+    /// Program block, None if parsing was aborted.
+    pub ast: Option<Program>,
+    /// The message of the hard error that aborted parsing, if any.
+    /// It is also recorded in `errors`.
+    pub hard_error: Option<String>,
+    pub errors: ErrorRecorder,
+}
+/// Lex the program only, the symbol tables are left empty and there is no AST.
+pub fn lex_only(sources: &SourceMap, file: FileId, options: &LexOptions) -> ParseResult {
+    let mut errors = ErrorRecorder::new(file);
+    let (tokens, tables) = lex(sources.content(file), options, &mut errors);
+    unchecked(tokens, tables, errors)
+}
+/// Result of tokens that are not checked, as `lex_only`.
+pub fn unchecked(tokens: Vec<Token>, tables: Tables, errors: ErrorRecorder) -> ParseResult {
+    ParseResult {
+        tokens,
+        tables,
+        trace: Vec::new(),
+        vars: BTreeMap::new(),
+        program: None,
+        consts: BTreeMap::new(),
+        types: BTreeMap::new(),
+        enumerators: BTreeMap::new(),
+        callables: BTreeMap::new(),
+        ast: None,
+        hard_error: None,
+        errors,
     }
 }
-pub fn parse(content: &str, errors: &mut ErrorRecorder) -> Vec<Token> {
-    let tokens = lex(content, errors);
-    let mut stream = TokenStream::new(tokens.clone());
-    match stream.code(errors) {
-        Ok(()) => {}
+/// Lex and check the program, keeping everything recovered even after a hard error.
+pub fn parse(sources: &SourceMap, file: FileId, options: &LexOptions) -> ParseResult {
+    let mut errors = ErrorRecorder::new(file);
+    let (tokens, tables) = lex(sources.content(file), options, &mut errors);
+    check(tokens, tables, errors)
+}
+/// Check the tokens of a program lexed beforehand, e.g. read back from a token dump.
+pub fn check(tokens: Vec<Token>, tables: Tables, mut errors: ErrorRecorder) -> ParseResult {
+    let mut stream = TokenStream::new(&tokens);
+    let mut ast = None;
+    let (trace, hard_error) = match stream.code(&mut errors) {
+        Ok(()) => {
+            let mut procedures = Vec::new();
+            for (name, range) in &stream.bodies {
+                if let Ok(body) = Parser::new(&tokens[range.clone()]).program(&mut errors) {
+                    procedures.push(Procedure {
+                        name: name.clone(),
+                        statements: body.statements,
+                        span: body.span,
+                    });
+                }
+            }
+            let mut parser = Parser::new(&tokens[stream.program_block_start..]);
+            ast = parser.program(&mut errors).ok().map(|program| Program {
+                procedures,
+                ..program
+            });
+            if let Some(ast) = &ast {
+                let checker = TypeChecker::new(
+                    &stream.vars,
+                    &stream.consts,
+                    &stream.enumerators,
+                    &stream.callables,
+                    &stream.declarations,
+                );
+                checker.program(ast, &mut errors);
+            }
+            (stream.trace, None)
+        }
         Err(e) => {
-            eprintln!("Hard error detected, aborting: {:?}", e);
+            let mut start = stream.var_block_start;
+            if tokens
+                .get(start)
+                .is_some_and(|t| matches!(t.token, TokenEnum::Const | TokenEnum::Type))
+            {
+                // Aborted in the const or type block, recover from the var block.
+                start = tokens[start..]
+                    .iter()
+                    .position(|t| {
+                        matches!(
+                            t.token,
+                            TokenEnum::Var
+                                | TokenEnum::Begin
+                                | TokenEnum::Procedure
+                                | TokenEnum::Function
+                        )
+                    })
+                    .map_or(tokens.len(), |i| start + i);
+            }
+            (
+                recover_var_block(&tokens[start..], &mut errors),
+                Some(e.to_string()),
+            )
+        }
+    };
+    let TokenStream {
+        vars,
+        program,
+        consts,
+        types,
+        enumerators,
+        callables,
+        ..
+    } = stream;
+    ParseResult {
+        tokens,
+        tables,
+        trace,
+        vars,
+        program,
+        consts,
+        types,
+        enumerators,
+        callables,
+        ast,
+        hard_error,
+        errors,
+    }
+}
+/// Fallback after a hard error: parse the var block with the error-tolerant Earley parser.
+/// Reports the repairs not already covered by an error,
+/// and returns the derivation of the most plausible (possibly partial) parse tree.
+fn recover_var_block(tokens: &[Token], errors: &mut ErrorRecorder) -> Vec<Production> {
+    let end = tokens
+        .iter()
+        .position(|t| {
+            matches!(
+                t.token,
+                TokenEnum::Begin | TokenEnum::Procedure | TokenEnum::Function
+            )
+        })
+        .unwrap_or(tokens.len());
+    // Record, enumeration, subrange and array types are a single `t` to the grammar,
+    // their contents are skipped.
+    let mut kept = Vec::new();
+    let mut depth = 0;
+    let mut in_subrange = false;
+    let mut in_array = false;
+    for (i, t) in tokens[..end].iter().enumerate() {
+        let prev = i.checked_sub(1).map(|i| tokens[i].token);
+        let bound = matches!(t.token, TokenEnum::IntLiteral | TokenEnum::Sub);
+        let type_start = matches!(prev, Some(TokenEnum::Colon | TokenEnum::Of));
+        // Subrange and array types, only their first token is kept
+        let subrange_rest = in_subrange && (bound || t.token == TokenEnum::DotDot);
+        let skipped = subrange_rest || in_array || prev == Some(TokenEnum::Of);
+        in_subrange = subrange_rest || (bound && type_start);
+        in_array = (in_array && t.token != TokenEnum::Of) || t.token == TokenEnum::Array;
+        if depth == 0 && !skipped {
+            kept.push(t);
         }
+        match t.token {
+            TokenEnum::Record => depth += 1,
+            // Enumeration type
+            TokenEnum::LParen if type_start => depth += 1,
+            TokenEnum::End | TokenEnum::RParen if depth > 0 => depth -= 1,
+            _ => {}
+        }
+    }
+    let input: Vec<_> = kept
+        .iter()
+        .enumerate()
+        .map(|(i, t)| match t.token {
+            // Type alias, enumeration or subrange
+            TokenEnum::Identifier | TokenEnum::LParen | TokenEnum::IntLiteral | TokenEnum::Sub
+                if i > 0 && kept[i - 1].token == TokenEnum::Colon =>
+            {
+                "t".to_string()
+            }
+            token => terminal_of(token),
+        })
+        .collect();
+    let grammar = var_block_grammar();
+    let result = Earley::new(&grammar).parse(&input);
+    // Not reported if the deterministic parser already reported an error there,
+    // or between the previous token and there, e.g. a missing `;` after the previous token
+    let mut report = |span: Span, code: Code, msg: String| {
+        let prev_end = tokens
+            .iter()
+            .take_while(|t| t.end <= span.start)
+            .last()
+            .map_or(0, |t| t.end);
+        if !errors.has_error_at(span.start) && !errors.has_error_in(Span::new(prev_end, span.end)) {
+            errors.error(span, code, msg);
+        }
+    };
+    let expected_list = |e: &[String]| {
+        e.iter()
+            .map(|t| describe_terminal(t))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut repairs = result.repairs.iter().peekable();
+    while let Some(repair) = repairs.next() {
+        match repair {
+            Repair::Insert(i, terminal) => report(
+                Span::from(kept[*i].offset),
+                match terminal.as_str() {
+                    ";" => Code::MissingSemicolon,
+                    "," => Code::MissingDelimiter,
+                    _ => Code::UnexpectedToken,
+                },
+                format!(
+                    "Missing {} before {:?}",
+                    describe_terminal(terminal),
+                    kept[*i].token
+                ),
+            ),
+            Repair::Skip(i, expected) => {
+                // A run of skipped tokens is reported once.
+                let mut span = kept[*i].span();
+                let mut last = *i;
+                while let Some(Repair::Skip(j, _)) =
+                    repairs.next_if(|r| matches!(r, Repair::Skip(j, _) if *j == last + 1))
+                {
+                    span = span.merge(kept[*j].span());
+                    last = *j;
+                }
+                let unexpected = match last - i {
+                    0 => format!("{:?}", kept[*i].token),
+                    n => format!("{:?} and {} more tokens", kept[*i].token, n),
+                };
+                report(
+                    span,
+                    Code::UnexpectedToken,
+                    format!(
+                        "Unexpected {}, expected one of {}",
+                        unexpected,
+                        expected_list(expected)
+                    ),
+                );
+            }
+        }
+    }
+    if !result.complete {
+        let offset = tokens.get(end).map(|t| t.offset).unwrap_or(usize::MAX);
+        report(
+            Span::from(offset),
+            Code::UnexpectedToken,
+            format!(
+                "Unexpected end of var block, expected one of {}",
+                expected_list(&result.expected_at_end)
+            ),
+        );
     }
-    tokens
+    result
+        .trace
+        .into_iter()
+        .map(|i| Production::ALL[i])
+        .collect()
 }
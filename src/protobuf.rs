@@ -0,0 +1,108 @@
+use prost::Message;
+
+use crate::{error::ErrorRecorder, lex::Token as LexToken, table::Tables};
+
+/// Messages of `docs/tokens.proto`.
+#[derive(Clone, PartialEq, Message)]
+pub struct Token {
+    #[prost(uint64, tag = "1")]
+    pub offset: u64,
+    #[prost(uint64, tag = "2")]
+    pub end: u64,
+    #[prost(string, tag = "3")]
+    pub content: String,
+    /// `TokenKind`, the classic token code.
+    #[prost(int32, tag = "4")]
+    pub kind: i32,
+    #[prost(uint64, optional, tag = "5")]
+    pub index: Option<u64>,
+    #[prost(uint64, tag = "6")]
+    pub line: u64,
+    #[prost(uint64, tag = "7")]
+    pub col: u64,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub enum Level {
+    Unspecified = 0,
+    Error = 1,
+    Warning = 2,
+}
+#[derive(Clone, PartialEq, Message)]
+pub struct Note {
+    #[prost(string, tag = "1")]
+    pub level: String,
+    #[prost(string, tag = "2")]
+    pub message: String,
+}
+#[derive(Clone, PartialEq, Message)]
+pub struct Diagnostic {
+    #[prost(enumeration = "Level", tag = "1")]
+    pub level: i32,
+    #[prost(uint64, tag = "2")]
+    pub start: u64,
+    #[prost(uint64, tag = "3")]
+    pub end: u64,
+    #[prost(string, tag = "4")]
+    pub message: String,
+    #[prost(message, repeated, tag = "5")]
+    pub notes: Vec<Note>,
+    #[prost(string, tag = "6")]
+    pub code: String,
+}
+#[derive(Clone, PartialEq, Message)]
+pub struct TokenOutput {
+    #[prost(message, repeated, tag = "1")]
+    pub tokens: Vec<Token>,
+    #[prost(string, repeated, tag = "2")]
+    pub identifiers: Vec<String>,
+    #[prost(string, repeated, tag = "3")]
+    pub constants: Vec<String>,
+    #[prost(message, repeated, tag = "4")]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Encode the token output as a `TokenOutput` message.
+pub fn to_protobuf(tokens: &[LexToken], tables: &Tables, errors: &ErrorRecorder) -> Vec<u8> {
+    let tokens = tokens
+        .iter()
+        .map(|t| Token {
+            offset: t.offset as u64,
+            end: t.end as u64,
+            content: t.content.clone(),
+            kind: t.token.code() as i32,
+            index: t.index.map(|i| i as u64),
+            line: t.line as u64,
+            col: t.col as u64,
+        })
+        .collect();
+    let diagnostics = errors
+        .sorted()
+        .into_iter()
+        .map(|(level, e)| Diagnostic {
+            level: match level {
+                "Error" => Level::Error,
+                _ => Level::Warning,
+            } as i32,
+            start: e.span.start as u64,
+            end: e.span.end as u64,
+            message: e.msg.clone(),
+            notes: e
+                .notes
+                .iter()
+                .map(|n| Note {
+                    level: n.level.as_str().to_string(),
+                    message: n.msg.clone(),
+                })
+                .collect(),
+            code: e.code.to_string(),
+        })
+        .collect();
+    TokenOutput {
+        tokens,
+        identifiers: tables.identifiers.entries().to_vec(),
+        constants: tables.constants.entries().to_vec(),
+        diagnostics,
+    }
+    .encode_to_vec()
+}
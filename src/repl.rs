@@ -0,0 +1,202 @@
+use std::io::{BufRead, Write};
+
+use anyhow::Result;
+
+use crate::{
+    error::{DisplayOptions, ErrorRecorder},
+    lex::{lex, LexOptions, TokenEnum},
+    pre_grammar::{self, ParseResult},
+    source_map::{FileId, SourceMap},
+};
+
+const HELP: &str = "\
+Enter declarations (`const`, `type`, `var`, `procedure`, `function`) or statements.
+Declarations free of errors are kept, statements are checked against them.
+    :vars     list the declared symbols
+    :program  print the program built from the declarations
+    :reset    forget all declarations
+    :quit     exit, as does end of input
+";
+
+/// Declarations entered so far, by block in program order, keywords stripped
+/// from `const`, `type` and `var` so that each block is declared once.
+#[derive(Default, Clone)]
+struct Declarations {
+    consts: Vec<String>,
+    types: Vec<String>,
+    vars: Vec<String>,
+    procedures: Vec<String>,
+}
+impl Declarations {
+    /// Program with the declarations, and the statement as its program block.
+    fn program(&self, statement: &str) -> String {
+        let mut result = String::from("program repl;\n");
+        for (keyword, block) in [
+            ("const", &self.consts),
+            ("type", &self.types),
+            ("var", &self.vars),
+        ] {
+            if !block.is_empty() {
+                result.push_str(&format!("{}\n    {}\n", keyword, block.join("\n    ")));
+            }
+        }
+        for procedure in &self.procedures {
+            result.push_str(&format!("{}\n", procedure));
+        }
+        match statement {
+            "" => result.push_str("begin\nend.\n"),
+            _ => result.push_str(&format!("begin\n    {}\nend.\n", statement)),
+        }
+        result
+    }
+    /// Check the snippet in the program, keeping it if it is a declaration free of errors.
+    /// Returns the diagnostics, empty if there is none.
+    fn enter(
+        &mut self,
+        snippet: &str,
+        options: &LexOptions,
+        display_options: &DisplayOptions,
+    ) -> (String, Option<ParseResult>) {
+        let mut entered = self.clone();
+        let (block, body) = match first_token(snippet) {
+            Some((TokenEnum::Const, end)) => (&mut entered.consts, &snippet[end..]),
+            Some((TokenEnum::Type, end)) => (&mut entered.types, &snippet[end..]),
+            Some((TokenEnum::Var, end)) => (&mut entered.vars, &snippet[end..]),
+            Some((TokenEnum::Procedure | TokenEnum::Function, _)) => {
+                (&mut entered.procedures, snippet)
+            }
+            _ => {
+                let (diagnostics, _) = check(&self.program(snippet), options, display_options);
+                return (diagnostics, None);
+            }
+        };
+        block.push(body.trim().to_string());
+        let (diagnostics, result) = check(&entered.program(""), options, display_options);
+        if diagnostics.is_empty() {
+            *self = entered;
+            (diagnostics, Some(result))
+        } else {
+            (diagnostics, None)
+        }
+    }
+}
+/// Kind and end offset of the first token, None if the snippet has no token.
+fn first_token(snippet: &str) -> Option<(TokenEnum, usize)> {
+    let mut errors = ErrorRecorder::new(FileId::default());
+    let (tokens, _) = lex(snippet, &LexOptions::default(), &mut errors);
+    tokens.first().map(|t| (t.token, t.end))
+}
+/// Whether the snippet needs more lines: a `begin`, `case` or `record` is not closed yet,
+/// or a procedure header is not followed by its body.
+fn incomplete(snippet: &str) -> bool {
+    let mut errors = ErrorRecorder::new(FileId::default());
+    let (tokens, _) = lex(snippet, &LexOptions::default(), &mut errors);
+    let mut depth = 0;
+    let mut body = false;
+    for token in &tokens {
+        match token.token {
+            TokenEnum::Begin => {
+                depth += 1;
+                body = true;
+            }
+            TokenEnum::Case | TokenEnum::Record => depth += 1,
+            TokenEnum::End => depth -= 1,
+            _ => {}
+        }
+    }
+    let callable = matches!(
+        tokens.first().map(|t| t.token),
+        Some(TokenEnum::Procedure | TokenEnum::Function)
+    );
+    depth > 0 || (callable && !body)
+}
+/// Check the program, returns the diagnostics and the parse result.
+fn check(
+    program: &str,
+    options: &LexOptions,
+    display_options: &DisplayOptions,
+) -> (String, ParseResult) {
+    let mut sources = SourceMap::new();
+    let file = sources.add("<repl>", program.to_string());
+    let mut result = pre_grammar::parse(&sources, file, options);
+    result.errors.set_options(display_options.clone());
+    (result.errors.display_with(&sources), result)
+}
+/// Symbols of the checked declarations, in the format of the listing.
+fn symbols(result: &ParseResult) -> String {
+    let mut output = String::new();
+    for (name, value) in &result.consts {
+        output.push_str(&format!("    {} = {}\n", name, value));
+    }
+    for (name, type_enum) in &result.types {
+        output.push_str(&format!("    type {} = {}\n", name, type_enum));
+    }
+    for (name, type_enum) in &result.vars {
+        output.push_str(&format!("    {}: {}\n", name, type_enum));
+    }
+    for (name, callable) in &result.callables {
+        output.push_str(&format!("    {}{}\n", name, callable));
+    }
+    output
+}
+
+/// Read declarations and statements line by line, reporting the diagnostics of each.
+/// A snippet continues on the next lines until its `begin`, `case` and `record` are closed.
+/// ```plaintext
+/// > var i: integer;
+/// > i := j
+/// [<repl>:5:10] Error: Undeclared identifier: j
+/// ```
+pub fn repl(
+    input: impl BufRead,
+    mut output: impl Write,
+    options: &LexOptions,
+    display_options: &DisplayOptions,
+) -> Result<()> {
+    let mut declarations = Declarations::default();
+    let mut symbol_table = None;
+    let mut snippet = String::new();
+    let mut lines = input.lines();
+    loop {
+        write!(output, "{}", if snippet.is_empty() { "> " } else { "... " })?;
+        output.flush()?;
+        let Some(line) = lines.next() else {
+            writeln!(output)?;
+            return Ok(());
+        };
+        let line = line?;
+        if snippet.is_empty() {
+            match line.trim() {
+                "" => continue,
+                ":quit" => return Ok(()),
+                ":help" => write!(output, "{}", HELP)?,
+                ":reset" => {
+                    declarations = Declarations::default();
+                    symbol_table = None;
+                }
+                ":program" => write!(output, "{}", declarations.program(""))?,
+                ":vars" => {
+                    if let Some(result) = &symbol_table {
+                        write!(output, "{}", symbols(result))?;
+                    }
+                }
+                command if command.starts_with(':') => {
+                    writeln!(output, "Unknown command {}, see :help", command)?;
+                }
+                _ => snippet = line,
+            }
+        } else {
+            snippet.push('\n');
+            snippet.push_str(&line);
+        }
+        if snippet.is_empty() || incomplete(&snippet) {
+            continue;
+        }
+        let (diagnostics, result) = declarations.enter(&snippet, options, display_options);
+        write!(output, "{}", diagnostics)?;
+        if result.is_some() {
+            symbol_table = result;
+        }
+        snippet.clear();
+    }
+}
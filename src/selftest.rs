@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::{derivation::Production, lex::LexOptions, session::Session};
+
+/// Check every `.in` file of the corpus against the expectation encoded in its name:
+/// files named `*correct*` must be free of diagnostics, the others must report at least one.
+/// With `coverage`, also report how many files exercised each var block production.
+/// Returns the report and the number of failed files.
+/// ```plaintext
+/// data/0-correct.in: ok
+/// data/1-no-space-after-var.in: ok (1 error, 0 warnings)
+///
+/// 11 passed, 0 failed
+///
+/// Coverage: 8/9 productions
+///     9  S -> Var D
+///     0  S -> ε    (uncovered)
+/// ```
+pub fn selftest(corpus: &Path, coverage: bool, options: LexOptions) -> Result<(String, usize)> {
+    let mut paths: Vec<_> = std::fs::read_dir(corpus)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|p| p.extension().is_some_and(|e| e == "in"));
+    paths.sort();
+    if paths.is_empty() {
+        return Err(anyhow!("No .in file in {}", corpus.display()));
+    }
+    let mut session = Session::new().lex_options(options);
+    let files = paths
+        .iter()
+        .map(|p| session.add_file(p))
+        .collect::<Result<Vec<_>>>()?;
+    session.check();
+    fn plural(n: usize, word: &str) -> String {
+        format!("{} {}{}", n, word, if n == 1 { "" } else { "s" })
+    }
+    let mut result = String::new();
+    let mut failed = 0;
+    let mut counts = [0; Production::ALL.len()];
+    for (path, file) in paths.iter().zip(files) {
+        let errors = session.errors(file);
+        let (e, w) = (errors.error_count(), errors.warning_count());
+        let expect_clean = path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().contains("correct"));
+        let counts_text = format!("{}, {}", plural(e, "error"), plural(w, "warning"));
+        let status = match (expect_clean, e + w) {
+            (true, 0) => "ok".to_string(),
+            (false, n) if n > 0 => format!("ok ({})", counts_text),
+            (true, _) => format!("FAILED, expected no diagnostic ({})", counts_text),
+            (false, _) => "FAILED, expected diagnostics".to_string(),
+        };
+        if status.starts_with("FAILED") {
+            failed += 1;
+        }
+        result.push_str(&format!("{}: {}\n", session.name(file), status));
+        for (count, production) in counts.iter_mut().zip(Production::ALL) {
+            if session.trace(file).contains(&production) {
+                *count += 1;
+            }
+        }
+    }
+    result.push_str(&format!(
+        "\n{} passed, {} failed\n",
+        paths.len() - failed,
+        failed
+    ));
+    if coverage {
+        let covered = counts.iter().filter(|c| **c > 0).count();
+        result.push_str(&format!(
+            "\nCoverage: {}/{} productions\n",
+            covered,
+            counts.len()
+        ));
+        for (count, production) in counts.iter().zip(Production::ALL) {
+            let uncovered = if *count == 0 { "    (uncovered)" } else { "" };
+            result.push_str(&format!(
+                "{:>5}  {}{}\n",
+                count,
+                production.display(),
+                uncovered
+            ));
+        }
+    }
+    Ok((result, failed))
+}
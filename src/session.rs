@@ -0,0 +1,364 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    derivation::{self, Production},
+    diagnostics, dump,
+    error::{self, DisplayOptions, ErrorRecorder},
+    format, html,
+    lex::{LexOptions, Token},
+    listing,
+    output::{self, OutputWriter},
+    parse_tree::ParseTree,
+    pre_grammar::{self, ParseResult},
+    source_map::{FileId, SourceMap},
+    table::Tables,
+};
+
+/// Artifact produced by `Session::emit`.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Artifact {
+    /// Tokens in the selected format, only if no error detected
+    Tokens,
+    /// Source reprinted with canonical layout, only if no error detected
+    Formatted,
+    /// Tokens separated by the fewest spaces that keep them apart, only if no error detected
+    Minified,
+    /// Line-numbered source listing with diagnostics.
+    /// This and the following artifacts are written even if errors are detected
+    /// (stdout if no output file)
+    Listing,
+    /// Table of the tokens with their index, position, kind and lexeme
+    TokenTable,
+    /// Standalone HTML page of the source with syntax highlighting and the diagnostics
+    Html,
+    /// Leftmost derivation of the var block as numbered steps
+    Derivation,
+    /// Concrete parse tree of the var block annotated with productions
+    ParseTree,
+    /// Concrete parse tree in Graphviz DOT format
+    ParseTreeDot,
+    /// AST of the program block as JSON, with the character span of every node
+    Ast,
+}
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// JSON tokens with their offsets, line and column, and content
+    #[default]
+    Json,
+    /// Classic (code, attribute) pairs with identifier and constant tables
+    Pairs,
+    /// Binary protobuf `TokenOutput` message, see `docs/tokens.proto`
+    Protobuf,
+    /// YAML with the structure of the JSON
+    Yaml,
+    /// CSV with offset, content and kind columns, without the tables
+    Csv,
+    /// MessagePack with the structure of the JSON
+    Msgpack,
+}
+impl OutputFormat {
+    pub fn writer(self) -> Box<dyn OutputWriter> {
+        match self {
+            Self::Json => Box::new(output::Json),
+            Self::Pairs => Box::new(output::Pairs),
+            Self::Protobuf => Box::new(output::Protobuf),
+            Self::Yaml => Box::new(output::Yaml),
+            Self::Csv => Box::new(output::Csv),
+            Self::Msgpack => Box::new(output::MessagePack),
+        }
+    }
+}
+
+/// Last phase run by `Session::check`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Phase {
+    /// Lexical errors only, the artifacts beyond the tokens are empty
+    Lex,
+    /// Lexical, syntax and semantic errors
+    #[default]
+    Check,
+}
+
+/// Version of the token output format.
+/// Renaming or removing a field or a token kind bumps it, adding one does not.
+pub const TOKEN_OUTPUT_VERSION: u32 = 1;
+
+/// Token output in JSON format (`--emit tokens --format json`),
+/// described by `docs/token-output.schema.json`.
+#[derive(Serialize, JsonSchema, TS)]
+pub struct TokenOutput<'a> {
+    /// Version of the format, bumped when a field or a token kind is renamed or removed
+    pub version: u32,
+    pub tokens: &'a [Token],
+    #[serde(flatten)]
+    pub tables: &'a Tables,
+}
+impl<'a> TokenOutput<'a> {
+    pub fn new(tokens: &'a [Token], tables: &'a Tables) -> Self {
+        Self {
+            version: TOKEN_OUTPUT_VERSION,
+            tokens,
+            tables,
+        }
+    }
+}
+/// Owned counterpart of `TokenOutput`, as read back from a token dump.
+#[derive(Debug, Deserialize)]
+pub struct TokenDump {
+    /// 0 for dumps written before the format was versioned
+    #[serde(default)]
+    pub version: u32,
+    pub tokens: Vec<Token>,
+    #[serde(flatten)]
+    pub tables: Tables,
+}
+
+/// Source with the tokens at their offsets, lines and columns, and spaces elsewhere,
+/// so that diagnostics on the tokens of a dump point at the right place.
+/// Comments are lost, and tokens normalized to a longer content shift the rest of their line.
+fn rebuild_source(tokens: &[Token]) -> String {
+    let mut source = String::new();
+    let (mut offset, mut line) = (0, 1);
+    for token in tokens {
+        let gap = token.offset.saturating_sub(offset);
+        let newlines = token.line.saturating_sub(line);
+        if newlines > 0 {
+            // Trailing spaces on the previous line, then the indentation of this one
+            let indent = token.col.saturating_sub(1);
+            let trailing = gap.saturating_sub(newlines + indent);
+            source.push_str(&" ".repeat(trailing));
+            source.push_str(&"\n".repeat(newlines));
+            source.push_str(&" ".repeat(indent));
+            offset += trailing + newlines + indent;
+            line = token.line;
+        } else {
+            source.push_str(&" ".repeat(gap));
+            offset += gap;
+        }
+        source.push_str(&token.content);
+        offset += token.content.chars().count();
+        if token.end > offset {
+            source.push_str(&" ".repeat(token.end - offset));
+            offset = token.end;
+        }
+    }
+    source.push('\n');
+    source
+}
+
+/// Owns the options, the source map and the diagnostics of each file.
+/// ```ignore
+/// let mut session = Session::new().lex_options(lex_options);
+/// let file = session.add_file("a.in")?;
+/// session.check();
+/// session.print_diagnostics();
+/// let listing = session.emit(file, Artifact::Listing)?;
+/// ```
+#[derive(Default)]
+pub struct Session {
+    lex_options: LexOptions,
+    display_options: DisplayOptions,
+    format: OutputFormat,
+    phase: Phase,
+    /// Whether the artifacts for the terminal use ANSI colors
+    color: bool,
+    sources: SourceMap,
+    /// Tokens of the files added as token dumps, until they are checked.
+    dumps: BTreeMap<FileId, TokenDump>,
+    /// Results of the checked files.
+    results: BTreeMap<FileId, ParseResult>,
+}
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn lex_options(self, lex_options: LexOptions) -> Self {
+        Self {
+            lex_options,
+            ..self
+        }
+    }
+    pub fn display_options(self, display_options: DisplayOptions) -> Self {
+        Self {
+            display_options,
+            ..self
+        }
+    }
+    /// Format of `Artifact::Tokens`.
+    pub fn format(self, format: OutputFormat) -> Self {
+        Self { format, ..self }
+    }
+    pub fn phase(self, phase: Phase) -> Self {
+        Self { phase, ..self }
+    }
+    /// Whether `Artifact::TokenTable` uses ANSI colors.
+    pub fn color(self, color: bool) -> Self {
+        Self { color, ..self }
+    }
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> Result<FileId> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        Ok(self.sources.add(path.display().to_string(), content))
+    }
+    /// Add a source that is not read from disk, e.g. stdin or an editor buffer.
+    pub fn add_source(&mut self, name: &str, content: String) -> FileId {
+        self.sources.add(name, content)
+    }
+    /// Add a token dump written by `--emit tokens` in JSON or MessagePack format.
+    /// Lexing is skipped, diagnostics are rendered on a source rebuilt from the tokens.
+    pub fn add_token_dump(&mut self, name: &str, bytes: &[u8]) -> Result<FileId> {
+        let dump: Result<TokenDump> = if bytes.trim_ascii_start().starts_with(b"{") {
+            serde_json::from_slice(bytes).map_err(Into::into)
+        } else {
+            output::read_msgpack(bytes)
+        };
+        let dump = dump.map_err(|e| anyhow!("{} is not a token dump: {}", name, e))?;
+        if dump.version > TOKEN_OUTPUT_VERSION {
+            return Err(anyhow!(
+                "Token dump {} has version {}, newer than the supported version {}",
+                name,
+                dump.version,
+                TOKEN_OUTPUT_VERSION
+            ));
+        }
+        let file = self.sources.add(name, rebuild_source(&dump.tokens));
+        self.dumps.insert(file, dump);
+        Ok(file)
+    }
+    pub fn name(&self, file: FileId) -> &str {
+        self.sources.name(file)
+    }
+    /// Check the files not checked yet, returns whether all files are free of errors.
+    pub fn check(&mut self) -> bool {
+        for file in self.sources.ids() {
+            if !self.results.contains_key(&file) {
+                let mut result = match (self.dumps.remove(&file), self.phase) {
+                    (Some(dump), Phase::Lex) => {
+                        pre_grammar::unchecked(dump.tokens, dump.tables, ErrorRecorder::new(file))
+                    }
+                    (Some(dump), Phase::Check) => {
+                        pre_grammar::check(dump.tokens, dump.tables, ErrorRecorder::new(file))
+                    }
+                    (None, Phase::Lex) => {
+                        pre_grammar::lex_only(&self.sources, file, &self.lex_options)
+                    }
+                    (None, Phase::Check) => {
+                        pre_grammar::parse(&self.sources, file, &self.lex_options)
+                    }
+                };
+                result.errors.set_options(self.display_options.clone());
+                self.results.insert(file, result);
+            }
+        }
+        self.results.values().all(|r| r.errors.no_error())
+    }
+    fn result(&self, file: FileId) -> &ParseResult {
+        self.results.get(&file).expect("file is not checked")
+    }
+    pub fn errors(&self, file: FileId) -> &ErrorRecorder {
+        &self.result(file).errors
+    }
+    /// Var block productions applied to the file, in leftmost derivation order.
+    pub fn trace(&self, file: FileId) -> &[Production] {
+        &self.result(file).trace
+    }
+    /// Print the diagnostics of every checked file to stderr.
+    /// The machine-readable formats list the diagnostics of all files at once.
+    pub fn print_diagnostics(&self) {
+        let all: Vec<_> = self
+            .results
+            .values()
+            .flat_map(|r| r.errors.sorted())
+            .collect();
+        if let Some(output) = diagnostics::render(self.display_options.format, &self.sources, &all)
+        {
+            eprint!("{}", output);
+            return;
+        }
+        for result in self.results.values() {
+            if let Some(e) = &result.hard_error {
+                eprintln!("Hard error detected, aborting: {}", e);
+            }
+            result.errors.print_with(&self.sources);
+        }
+    }
+    /// JUnit XML report of the checked files, see `diagnostics::to_junit`.
+    pub fn junit_report(&self) -> String {
+        let files: Vec<_> = self
+            .results
+            .iter()
+            .map(|(&file, result)| (self.sources.name(file), &result.errors))
+            .collect();
+        diagnostics::to_junit(&self.sources, &files)
+    }
+    /// Standalone HTML report of the diagnostics of the checked files,
+    /// see `html::to_report`.
+    pub fn html_report(&self) -> String {
+        let results: Vec<_> = self
+            .results
+            .iter()
+            .map(|(&file, result)| (file, result))
+            .collect();
+        html::to_report(&self.sources, &results)
+    }
+    /// Apply the machine-applicable fixes to the file on disk, returns the number applied.
+    pub fn apply_fixes(&self, file: FileId) -> Result<usize> {
+        let fixes = self.errors(file).fixes();
+        if !fixes.is_empty() {
+            let content = error::apply_fixes(self.sources.content(file), &fixes);
+            std::fs::write(self.sources.name(file), content)?;
+        }
+        Ok(fixes.len())
+    }
+    /// Extension of the output file of the artifact, e.g. `ast.json` for `a.ast.json`.
+    pub fn extension(&self, artifact: Artifact) -> &'static str {
+        match artifact {
+            Artifact::Tokens => match self.format {
+                OutputFormat::Json => "json",
+                OutputFormat::Pairs => "pairs.txt",
+                OutputFormat::Protobuf => "pb",
+                OutputFormat::Yaml => "yaml",
+                OutputFormat::Csv => "csv",
+                OutputFormat::Msgpack => "msgpack",
+            },
+            Artifact::Formatted => "fmt.in",
+            Artifact::Minified => "min.in",
+            Artifact::Listing => "lst",
+            Artifact::TokenTable => "tokens.txt",
+            Artifact::Html => "html",
+            Artifact::Derivation => "derivation.txt",
+            Artifact::ParseTree => "tree.txt",
+            Artifact::ParseTreeDot => "dot",
+            Artifact::Ast => "ast.json",
+        }
+    }
+    pub fn emit(&self, file: FileId, artifact: Artifact) -> Result<Vec<u8>> {
+        let result = self.result(file);
+        Ok(match artifact {
+            Artifact::Tokens => return self.format.writer().write(result),
+            Artifact::Formatted => format::to_formatted(self.sources.content(file), &result.tokens),
+            Artifact::Minified => format::to_minified(
+                self.sources.content(file),
+                &result.tokens,
+                &self.lex_options,
+            ),
+            Artifact::Listing => listing::to_listing(&self.sources, file, result),
+            Artifact::TokenTable => dump::to_token_table(&result.tokens, self.color),
+            Artifact::Html => html::to_html(&self.sources, file, result),
+            Artifact::Derivation => derivation::to_derivation(&result.trace),
+            Artifact::ParseTree => ParseTree::from_trace(&result.trace).to_text(),
+            Artifact::ParseTreeDot => ParseTree::from_trace(&result.trace).to_dot(),
+            Artifact::Ast => match &result.ast {
+                Some(ast) => serde_json::to_string_pretty(ast)?,
+                None => return Err(anyhow!("No AST, parsing was aborted by a hard error")),
+            },
+        }
+        .into_bytes())
+    }
+}
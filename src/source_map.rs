@@ -0,0 +1,151 @@
+use serde::Serialize;
+
+/// Handle of a file in a `SourceMap`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FileId(usize);
+
+/// Half-open character range `start..end` within a file.
+/// An empty span marks a position, e.g. a missing token.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+    /// Smallest span covering both spans.
+    pub fn merge(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+    /// Whether the offset is within the span, an empty span contains its start.
+    pub fn contains(self, offset: usize) -> bool {
+        offset == self.start || (self.start..self.end).contains(&offset)
+    }
+    /// 1-based line and column of the start of the span.
+    pub fn to_line_col(self, sources: &SourceMap, file: FileId) -> (usize, usize) {
+        sources.line_col(file, self.start)
+    }
+    /// Number of characters of the span on the line of its start, up to the end of
+    /// the line content, at least 1 so that an empty span is still marked.
+    pub fn width_on_line(self, sources: &SourceMap, file: FileId) -> usize {
+        let (line, col) = self.to_line_col(sources, file);
+        let (end_line, end_col) = sources.line_col(file, self.end);
+        let line_len = sources
+            .line(file, line)
+            .map_or(0, |content| content.trim_end().chars().count());
+        let available = (line_len + 1).saturating_sub(col);
+        let width = if end_line == line {
+            end_col.saturating_sub(col)
+        } else {
+            available
+        };
+        width.min(available).max(1)
+    }
+    /// Span clamped to the content of the file, e.g. a span at EOF to its end.
+    pub fn clamp_to(self, sources: &SourceMap, file: FileId) -> Span {
+        let len = sources.char_count(file);
+        Span::new(self.start.min(len), self.end.min(len))
+    }
+}
+impl From<usize> for Span {
+    /// Empty span at the offset.
+    fn from(offset: usize) -> Self {
+        Span::new(offset, offset)
+    }
+}
+
+/// Line start offsets of a file, in characters.
+#[derive(Debug, Clone)]
+struct LinePos {
+    content: Vec<char>,
+    start_offset: Vec<usize>,
+}
+
+impl LinePos {
+    fn new(content: &str) -> Self {
+        let mut content: Vec<_> = content.chars().collect();
+        if content.last() != Some(&'\n') {
+            content.push('\n');
+        }
+        let mut start_offset = vec![0];
+        for (i, c) in content.iter().enumerate() {
+            if *c == '\n' {
+                start_offset.push(i + 1);
+            }
+        }
+        LinePos {
+            content,
+            start_offset,
+        }
+    }
+    /// Get the line and column number from the offset.
+    /// The line and column number are 1-based.
+    fn line_col(&self, offset: usize) -> (usize, usize) {
+        if offset > self.content.len() {
+            return (self.start_offset.len(), 1);
+        }
+        let line = self
+            .start_offset
+            .binary_search(&offset)
+            .map(|x| x + 1)
+            .unwrap_or_else(|x| x);
+        let col = offset - self.start_offset[line - 1] + 1;
+        (line, col)
+    }
+    /// Get the line content from the line number.
+    /// The line number is 1-based.
+    fn get_line(&self, line: usize) -> Option<String> {
+        let start = self.start_offset.get(line - 1)?;
+        let end = self.start_offset.get(line)?;
+        Some(self.content[*start..*end].iter().collect::<String>())
+    }
+}
+#[derive(Debug, Clone)]
+struct SourceFile {
+    name: String,
+    content: String,
+    line_pos: LinePos,
+}
+/// Files of a compilation, addressed by `FileId`.
+/// Offsets are character offsets into the file content.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add(&mut self, name: impl Into<String>, content: impl Into<String>) -> FileId {
+        let content = content.into();
+        self.files.push(SourceFile {
+            name: name.into(),
+            line_pos: LinePos::new(&content),
+            content,
+        });
+        FileId(self.files.len() - 1)
+    }
+    pub fn ids(&self) -> impl Iterator<Item = FileId> {
+        (0..self.files.len()).map(FileId)
+    }
+    pub fn name(&self, file: FileId) -> &str {
+        &self.files[file.0].name
+    }
+    pub fn content(&self, file: FileId) -> &str {
+        &self.files[file.0].content
+    }
+    /// Number of characters of the file content.
+    pub fn char_count(&self, file: FileId) -> usize {
+        self.content(file).chars().count()
+    }
+    /// 1-based line and column of the offset in the file.
+    pub fn line_col(&self, file: FileId, offset: usize) -> (usize, usize) {
+        self.files[file.0].line_pos.line_col(offset)
+    }
+    /// Content of the 1-based line, including the line break.
+    pub fn line(&self, file: FileId, line: usize) -> Option<String> {
+        self.files[file.0].line_pos.get_line(line)
+    }
+}
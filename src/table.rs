@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use ts_rs::TS;
+
+use crate::lex::{Token, TokenEnum};
+
+/// Deduplicated, insertion-ordered table of strings.
+/// Used for the identifier table and the constant table.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    entries: Vec<String>,
+    index: HashMap<String, usize>,
+}
+impl Table {
+    /// Insert the entry if absent.
+    /// Returns the 0-based index of the entry.
+    pub fn intern(&mut self, entry: &str) -> usize {
+        if let Some(i) = self.index.get(entry) {
+            return *i;
+        }
+        let i = self.entries.len();
+        self.entries.push(entry.to_string());
+        self.index.insert(entry.to_string(), i);
+        i
+    }
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+}
+impl Serialize for Table {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.entries.serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for Table {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut table = Table::default();
+        for entry in Vec::<String>::deserialize(deserializer)? {
+            table.intern(&entry);
+        }
+        Ok(table)
+    }
+}
+
+/// Identifier table and integer constant table built during lexing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, TS)]
+pub struct Tables {
+    /// Lowercase identifiers
+    #[schemars(with = "Vec<String>")]
+    #[ts(as = "Vec<String>")]
+    pub identifiers: Table,
+    /// Integer and real literals with leading zeros and `_` separators removed, and character literals quoted as written
+    #[schemars(with = "Vec<String>")]
+    #[ts(as = "Vec<String>")]
+    pub constants: Table,
+}
+impl Tables {
+    /// Record the token in the corresponding table.
+    /// Returns the table index for `Identifier` and the literals.
+    pub fn intern(&mut self, token: &Token) -> Option<usize> {
+        match token.token {
+            TokenEnum::Identifier => Some(self.identifiers.intern(&token.content.to_lowercase())),
+            TokenEnum::IntLiteral | TokenEnum::RealLiteral | TokenEnum::CharLiteral => {
+                Some(self.constants.intern(&token.content))
+            }
+            _ => None,
+        }
+    }
+}
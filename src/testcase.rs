@@ -0,0 +1,58 @@
+/// Minimal linear congruential generator, so that the same seed gives the same program
+/// without a dependency on `rand`.
+struct Lcg(u64);
+impl Lcg {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) as usize % n
+    }
+}
+
+/// Generate a synthetic program of about `tokens` tokens, as a benchmark workload.
+/// With `errors`, every tenth statement uses an undeclared identifier.
+/// ```plaintext
+/// program bench;
+/// var v0, v1, v2: integer;
+/// begin
+///     v1 := v0 + 7;
+///     while v2 > 0 do v2 := v2 - 1;
+///     ...
+/// end
+/// ```
+pub fn gen_testcase(tokens: usize, errors: bool, seed: u64) -> String {
+    let mut rng = Lcg(seed);
+    let var_count = (tokens / 100).clamp(1, 1000);
+    let names: Vec<_> = (0..var_count).map(|i| format!("v{}", i)).collect();
+    let mut result = format!(
+        "program bench;\nvar {}: integer;\nbegin\n",
+        names.join(", ")
+    );
+    // program bench ; var ... : integer ; begin end
+    let mut count = 9 + 2 * var_count - 1;
+    let mut statements = Vec::new();
+    while count < tokens {
+        let undeclared = errors && statements.len() % 10 == 9;
+        let mut var = || {
+            if undeclared {
+                format!("u{}", statements.len())
+            } else {
+                names[rng.below(var_count)].clone()
+            }
+        };
+        let (a, b) = (var(), var());
+        let statement = match rng.below(3) {
+            0 => format!("{} := {} + {}", a, b, rng.below(100)),
+            1 => format!("if {} < {} then {} := {} * 2", a, b, a, a),
+            _ => format!("while {} > 0 do {} := {} - 1", a, a, a),
+        };
+        // Words are tokens, plus the separator
+        count += statement.split(' ').count() + 1;
+        statements.push(statement);
+    }
+    result.push_str(&format!("    {}\n", statements.join(";\n    ")));
+    result.push_str("end\n");
+    result
+}
@@ -0,0 +1,127 @@
+use serde_json::{json, Value};
+
+use crate::lex::{TokenEnum, KEYWORDS, SYMBOLS};
+
+/// Escape regex metacharacters.
+fn escape(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if "\\^$.|?*+()[]{}".contains(c) {
+                format!("\\{}", c)
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+fn keyword_pattern(scope: &str, filter: impl Fn(TokenEnum) -> bool) -> Value {
+    let words: Vec<_> = KEYWORDS
+        .iter()
+        .filter(|(_, t)| filter(*t))
+        .map(|(k, _)| *k)
+        .collect();
+    json!({
+        "name": scope,
+        "match": format!("(?i)\\b({})\\b", words.join("|")),
+    })
+}
+fn symbol_pattern(scope: &str, filter: impl Fn(TokenEnum) -> bool) -> Value {
+    // SYMBOLS is ordered longest first, which the alternation relies on.
+    let symbols: Vec<_> = SYMBOLS
+        .iter()
+        .filter(|(_, t)| filter(*t))
+        .map(|(s, _)| escape(s))
+        .collect();
+    json!({
+        "name": scope,
+        "match": symbols.join("|"),
+    })
+}
+
+/// TextMate grammar (as used by VS Code) derived from the lexer's keyword and symbol tables.
+pub fn tmlanguage() -> Value {
+    let patterns = vec![
+        json!({
+            "name": "comment.line.double-slash.pl0",
+            "match": "//.*$",
+        }),
+        json!({
+            "name": "comment.block.pl0",
+            "begin": "\\(\\*",
+            "end": "\\*\\)",
+        }),
+        json!({
+            "name": "comment.block.pl0",
+            "begin": "\\{",
+            "end": "\\}",
+        }),
+        keyword_pattern("storage.type.pl0", |t| {
+            matches!(
+                t,
+                TokenEnum::Integer
+                    | TokenEnum::Longint
+                    | TokenEnum::Bool
+                    | TokenEnum::Real
+                    | TokenEnum::Char
+                    | TokenEnum::Record
+                    | TokenEnum::Array
+            )
+        }),
+        keyword_pattern("keyword.operator.logical.pl0", |t| {
+            matches!(
+                t,
+                TokenEnum::And | TokenEnum::Or | TokenEnum::Odd | TokenEnum::Not
+            )
+        }),
+        keyword_pattern("constant.language.pl0", |t| {
+            matches!(t, TokenEnum::True | TokenEnum::False)
+        }),
+        keyword_pattern("keyword.control.pl0", |t| {
+            !matches!(
+                t,
+                TokenEnum::Integer
+                    | TokenEnum::Longint
+                    | TokenEnum::Bool
+                    | TokenEnum::Real
+                    | TokenEnum::Char
+                    | TokenEnum::Record
+                    | TokenEnum::Array
+                    | TokenEnum::And
+                    | TokenEnum::Or
+                    | TokenEnum::Odd
+                    | TokenEnum::Not
+                    | TokenEnum::True
+                    | TokenEnum::False
+            )
+        }),
+        json!({
+            "name": "constant.character.pl0",
+            "match": "'([^']|'')'",
+        }),
+        json!({
+            "name": "constant.numeric.float.pl0",
+            "match": "\\b[0-9]+\\.[0-9]+\\b",
+        }),
+        json!({
+            "name": "constant.numeric.integer.pl0",
+            "match": "\\b[0-9]+\\b",
+        }),
+        symbol_pattern("punctuation.separator.pl0", |t| {
+            matches!(t, TokenEnum::Comma | TokenEnum::SemiColon)
+        }),
+        symbol_pattern("keyword.operator.pl0", |t| {
+            !matches!(t, TokenEnum::Comma | TokenEnum::SemiColon)
+        }),
+        json!({
+            "name": "variable.other.pl0",
+            "match": "\\b[a-zA-Z][a-zA-Z0-9]*\\b",
+        }),
+    ];
+    json!({
+        "$schema": "https://raw.githubusercontent.com/martinring/tmlanguage/master/tmlanguage.json",
+        "name": "PL/0",
+        "scopeName": "source.pl0",
+        "fileTypes": ["pl0", "in"],
+        "patterns": patterns,
+    })
+}
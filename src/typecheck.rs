@@ -0,0 +1,640 @@
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use crate::{
+    ast::{
+        BinaryOp, Expr, ExprKind, Program, Selector, Statement, StatementKind, UnaryOp, Variable,
+    },
+    codes::Code,
+    error::ErrorRecorder,
+    pre_grammar::{Callable, TypeEnum},
+    source_map::Span,
+};
+
+/// Check the statements of the program block and the procedure bodies:
+/// the identifiers, fields and indices against the declarations, the targets of
+/// assignments and calls, and the types of assignments, conditions and expressions.
+/// A type is None where an error was already reported, e.g. an undeclared identifier,
+/// so that no follow-up error is reported.
+pub struct TypeChecker<'a> {
+    vars: &'a BTreeMap<String, TypeEnum>,
+    consts: &'a BTreeMap<String, i64>,
+    enumerators: &'a BTreeMap<String, (TypeEnum, usize)>,
+    callables: &'a BTreeMap<String, Callable>,
+    /// Spans of the global declarations, to point at the declaration of a constant.
+    declarations: &'a BTreeMap<String, Span>,
+}
+impl<'a> TypeChecker<'a> {
+    pub fn new(
+        vars: &'a BTreeMap<String, TypeEnum>,
+        consts: &'a BTreeMap<String, i64>,
+        enumerators: &'a BTreeMap<String, (TypeEnum, usize)>,
+        callables: &'a BTreeMap<String, Callable>,
+        declarations: &'a BTreeMap<String, Span>,
+    ) -> Self {
+        Self {
+            vars,
+            consts,
+            enumerators,
+            callables,
+            declarations,
+        }
+    }
+    pub fn program(&self, program: &Program, errors: &mut ErrorRecorder) {
+        for procedure in &program.procedures {
+            let Some(callable) = self.callables.get(&procedure.name) else {
+                continue;
+            };
+            // Parameters and locals shadow the globals, the function name holds the result
+            let mut scope = self.vars.clone();
+            scope.extend(callable.params.iter().cloned());
+            scope.extend(callable.locals.clone());
+            if let Some(result) = &callable.result {
+                scope.insert(procedure.name.clone(), result.clone());
+            }
+            let checker = TypeChecker {
+                vars: &scope,
+                ..*self
+            };
+            for statement in &procedure.statements {
+                checker.statement(statement, errors);
+            }
+        }
+        for statement in &program.statements {
+            self.statement(statement, errors);
+        }
+    }
+    fn statement(&self, statement: &Statement, errors: &mut ErrorRecorder) {
+        match &statement.kind {
+            StatementKind::Assign { target, value }
+                if self.constant_target(target, "assign to", errors) =>
+            {
+                self.expr(value, errors);
+            }
+            StatementKind::Assign { target, value } => {
+                let target_type = self.variable(target, errors);
+                let value_type = self.expr(value, errors);
+                if let (Some(t), Some(v)) = (target_type, value_type) {
+                    if !assignable(&t, &v, value) {
+                        errors.error(
+                            value.span,
+                            Code::AssignMismatch,
+                            format!("Cannot assign {} to {}", v, target),
+                        );
+                        errors.note(format!("{} is of type {}", target, t));
+                    } else if let TypeEnum::Subrange(low, high) = t {
+                        self.check_range(target, value, low, high, errors);
+                    }
+                }
+            }
+            StatementKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.condition(condition, "if", errors);
+                self.statement(then_branch, errors);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch, errors);
+                }
+            }
+            StatementKind::While { condition, body } => {
+                self.condition(condition, "while", errors);
+                self.statement(body, errors);
+            }
+            StatementKind::Block { statements } => {
+                for statement in statements {
+                    self.statement(statement, errors);
+                }
+            }
+            StatementKind::Case { selector, arms } => {
+                let selector_type = match self.expr(selector, errors) {
+                    Some(t)
+                        if t.is_integer() || matches!(t, TypeEnum::Char | TypeEnum::Enum(_)) =>
+                    {
+                        Some(t)
+                    }
+                    Some(t) => {
+                        errors.error(
+                            selector.span,
+                            Code::InvalidCaseSelector,
+                            format!(
+                                "Expected integer, Char or enumeration case selector, found {}",
+                                t
+                            ),
+                        );
+                        None
+                    }
+                    None => None,
+                };
+                let mut seen = BTreeMap::new();
+                for arm in arms {
+                    for label in &arm.labels {
+                        self.case_label(label, selector_type.as_ref(), &mut seen, errors);
+                    }
+                    self.statement(&arm.body, errors);
+                }
+            }
+            StatementKind::Read { targets } => {
+                for target in targets {
+                    if self.constant_target(target, "read into", errors) {
+                        continue;
+                    }
+                    match self.variable(target, errors) {
+                        Some(t) if !t.is_numeric() && t != TypeEnum::Char => errors.error(
+                            target.span,
+                            Code::InvalidReadWrite,
+                            format!("Cannot read into {} of type {}", target, t),
+                        ),
+                        _ => {}
+                    }
+                }
+            }
+            StatementKind::Write { values } => {
+                for value in values {
+                    match self.expr(value, errors) {
+                        Some(t)
+                            if !t.is_numeric() && !matches!(t, TypeEnum::Bool | TypeEnum::Char) =>
+                        {
+                            errors.error(
+                                value.span,
+                                Code::InvalidReadWrite,
+                                format!("Cannot write {} of type {}", value, t),
+                            )
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            StatementKind::Call { procedure, args } => {
+                match self.callables.get(procedure) {
+                    Some(callable @ Callable { result: None, .. }) => {
+                        self.args(procedure, callable, args, statement.span, errors);
+                        return;
+                    }
+                    Some(_) => {
+                        errors.error(
+                            statement.span,
+                            Code::InvalidCall,
+                            format!("{} is a function, not a procedure", procedure),
+                        );
+                        errors.help("use its result in an expression");
+                    }
+                    None => {
+                        errors.error(
+                            statement.span,
+                            Code::UndeclaredProcedure,
+                            format!("Undeclared procedure: {}", procedure),
+                        );
+                        errors.help("declare the procedure before the program block");
+                    }
+                }
+                for arg in args {
+                    self.expr(arg, errors);
+                }
+            }
+            StatementKind::Empty => {}
+        }
+    }
+    fn condition(&self, condition: &Expr, keyword: &str, errors: &mut ErrorRecorder) {
+        match self.expr(condition, errors) {
+            Some(TypeEnum::Bool) | None => {}
+            Some(t) => errors.error(
+                condition.span,
+                Code::ConditionNotBool,
+                format!("Expected Bool condition of `{}`, found {}", keyword, t),
+            ),
+        }
+    }
+    /// Check that a case label is a constant of the selector type, not handled before.
+    fn case_label(
+        &self,
+        label: &Expr,
+        selector: Option<&TypeEnum>,
+        seen: &mut BTreeMap<i64, Span>,
+        errors: &mut ErrorRecorder,
+    ) {
+        let Some(t) = self.expr(label, errors) else {
+            return;
+        };
+        let Some(value) = self.constant(label) else {
+            errors.error(
+                label.span,
+                Code::CaseLabelNotConstant,
+                "Case label must be a constant",
+            );
+            errors.help("use an integer, a constant or an enumerator");
+            return;
+        };
+        let Some(selector) = selector else {
+            return;
+        };
+        if !(selector.is_integer() && t.is_integer() || t == *selector) {
+            errors.error(
+                label.span,
+                Code::CaseLabelMismatch,
+                format!("Expected case label of type {}, found {}", selector, t),
+            );
+        } else if let Some(&first) = seen.get(&value) {
+            errors.error(
+                label.span,
+                Code::DuplicateCaseLabel,
+                format!("Duplicate case label: {}", label),
+            );
+            errors.label(first, "first handled here");
+            errors.note("each value may be handled by only one arm");
+        } else {
+            seen.insert(value, label.span);
+        }
+    }
+    /// Value of a constant expression: an integer, a constant or an enumerator,
+    /// optionally negated, or the code point of a character.
+    fn constant(&self, expr: &Expr) -> Option<i64> {
+        match &expr.kind {
+            ExprKind::Number { value } => Some(*value),
+            ExprKind::Char { value } => Some(*value as i64),
+            ExprKind::Unary {
+                op: UnaryOp::Neg,
+                operand,
+            } => self.constant(operand).map(|v| -v),
+            ExprKind::Variable { variable }
+                if variable.selectors.is_empty() && !self.vars.contains_key(&variable.name) =>
+            {
+                match self.consts.get(&variable.name) {
+                    Some(value) => Some(*value),
+                    None => self
+                        .enumerators
+                        .get(&variable.name)
+                        .map(|(_, ordinal)| *ordinal as i64),
+                }
+            }
+            _ => None,
+        }
+    }
+    /// Report a constant or an enumerator not shadowed by a variable as the target
+    /// of an assignment or a read. Returns whether the target is one.
+    fn constant_target(&self, target: &Variable, verb: &str, errors: &mut ErrorRecorder) -> bool {
+        let name = &target.name;
+        if self.vars.contains_key(name) {
+            return false;
+        }
+        let declared_as = match (self.consts.get(name), self.enumerators.get(name)) {
+            (Some(value), _) => format!("a constant of value {}", value),
+            (None, Some((t, _))) => format!("an enumerator of {}", t),
+            (None, None) => return false,
+        };
+        errors.error(
+            target.name_span(),
+            Code::AssignToConstant,
+            format!("Cannot {} constant {}", verb, name),
+        );
+        if let Some(&span) = self.declarations.get(name) {
+            errors.label(span, format!("{} declared here", name));
+        }
+        errors.note(format!("{} is declared as {}", name, declared_as));
+        true
+    }
+    /// Check a constant assigned to a subrange against its bounds.
+    fn check_range(
+        &self,
+        target: &Variable,
+        value: &Expr,
+        low: i64,
+        high: i64,
+        errors: &mut ErrorRecorder,
+    ) {
+        match self.constant(value) {
+            Some(v) if v < low || v > high => {
+                errors.error(
+                    value.span,
+                    Code::ConstantOutOfRange,
+                    format!("Constant {} is out of range for {}", value, target),
+                );
+                errors.note(format!("{} is of type {}..{}", target, low, high));
+            }
+            _ => {}
+        }
+    }
+    /// Type of the variable, field or element, checking that the variable is declared.
+    /// The fields are checked against the record types, the indices against the
+    /// dimensions of the array types, in number and, for constant ones, in bounds.
+    fn variable(&self, variable: &Variable, errors: &mut ErrorRecorder) -> Option<TypeEnum> {
+        let name = &variable.name;
+        let mut type_enum = match self.vars.get(name) {
+            Some(t) => Some(t.clone()),
+            None if self.consts.contains_key(name) => Some(TypeEnum::Integer),
+            None => match self.enumerators.get(name) {
+                Some((t, _)) => Some(t.clone()),
+                None => {
+                    if !self.callables.contains_key(name) {
+                        self.undeclared(name, variable.name_span(), errors);
+                    }
+                    None
+                }
+            },
+        };
+        let mut path = variable.name.clone();
+        let mut selectors = variable.selectors.iter().peekable();
+        while let Some(selector) = selectors.next() {
+            // `a[i][j]` indexes the remaining dimensions of `a[i]`
+            let partial = matches!(selectors.peek(), Some(Selector::Index { .. }));
+            type_enum = match (selector, type_enum) {
+                (Selector::Field { name, span }, Some(TypeEnum::Record(fields))) => {
+                    let found = fields.iter().find(|(f, _)| f == name);
+                    if found.is_none() {
+                        errors.error(
+                            *span,
+                            Code::UnknownField,
+                            format!("Unknown field: {} of {}", name, path),
+                        );
+                        let names: Vec<_> = fields.iter().map(|(f, _)| f.as_str()).collect();
+                        errors.note(format!("{} has fields {}", path, names.join(", ")));
+                    }
+                    found.map(|(_, t)| t.clone())
+                }
+                (Selector::Field { span, .. }, Some(t)) => {
+                    errors.error(
+                        *span,
+                        Code::InvalidSelector,
+                        format!("{} is not a record", path),
+                    );
+                    errors.note(format!("{} is of type {}", path, t));
+                    None
+                }
+                (Selector::Index { indices, span }, Some(TypeEnum::Array(bounds, element))) => {
+                    for (index, (dimension, bound)) in indices.iter().zip(bounds.iter().enumerate())
+                    {
+                        match self.expr(index, errors) {
+                            Some(t) if !t.is_integer() => errors.error(
+                                index.span,
+                                Code::InvalidSelector,
+                                format!("Expected integer index, found {}", t),
+                            ),
+                            Some(_) => self.check_index(&path, index, dimension, *bound, errors),
+                            None => {}
+                        }
+                    }
+                    // Indices beyond the dimensions are still checked for their variables
+                    for index in indices.iter().skip(bounds.len()) {
+                        self.expr(index, errors);
+                    }
+                    match indices.len().cmp(&bounds.len()) {
+                        Ordering::Equal => Some(*element),
+                        Ordering::Less if partial => {
+                            Some(TypeEnum::Array(bounds[indices.len()..].to_vec(), element))
+                        }
+                        _ => {
+                            let (expected, found) = (bounds.len(), indices.len());
+                            let plural = |n| if n == 1 { "index" } else { "indices" };
+                            errors.error(
+                                *span,
+                                Code::IndexOutOfBounds,
+                                format!(
+                                    "Expected {} {} for {}, found {}",
+                                    expected,
+                                    plural(expected),
+                                    path,
+                                    found
+                                ),
+                            );
+                            let t = TypeEnum::Array(bounds, element);
+                            errors.note(format!("{} is of type {}", path, t));
+                            None
+                        }
+                    }
+                }
+                (Selector::Index { indices, span }, t) => {
+                    if let Some(t) = t {
+                        errors.error(
+                            *span,
+                            Code::InvalidSelector,
+                            format!("{} is not an array", path),
+                        );
+                        errors.note(format!("{} is of type {}", path, t));
+                    }
+                    for index in indices {
+                        self.expr(index, errors);
+                    }
+                    None
+                }
+                // Selectors after an error are not checked
+                (Selector::Field { .. }, None) => None,
+            };
+            path.push_str(&selector.to_string());
+        }
+        type_enum
+    }
+    /// Check a constant index against the bounds of its dimension.
+    fn check_index(
+        &self,
+        path: &str,
+        index: &Expr,
+        dimension: usize,
+        (low, high): (i64, i64),
+        errors: &mut ErrorRecorder,
+    ) {
+        match self.constant(index) {
+            Some(v) if v < low || v > high => {
+                errors.error(
+                    index.span,
+                    Code::IndexOutOfBounds,
+                    format!("Index {} is out of bounds for {}", index, path),
+                );
+                errors.note(format!(
+                    "index {} of {} ranges over {}..{}",
+                    dimension + 1,
+                    path,
+                    low,
+                    high
+                ));
+            }
+            _ => {}
+        }
+    }
+    fn undeclared(&self, name: &str, span: Span, errors: &mut ErrorRecorder) {
+        errors.error(
+            span,
+            Code::UndeclaredIdentifier,
+            format!("Undeclared identifier: {}", name),
+        );
+        errors.help("declare the variable in the var block");
+    }
+    /// Check the arguments of a call against the parameters.
+    fn args(
+        &self,
+        name: &str,
+        callable: &Callable,
+        args: &[Expr],
+        span: Span,
+        errors: &mut ErrorRecorder,
+    ) {
+        if args.len() != callable.params.len() {
+            let expected = callable.params.len();
+            let plural = if expected == 1 { "" } else { "s" };
+            errors.error(
+                span,
+                Code::ArgumentCount,
+                format!(
+                    "Expected {} argument{} for {}, found {}",
+                    expected,
+                    plural,
+                    name,
+                    args.len()
+                ),
+            );
+            errors.note(format!("{} is declared as {}{}", name, name, callable));
+        }
+        for (i, arg) in args.iter().enumerate() {
+            let (value_type, param) = (self.expr(arg, errors), callable.params.get(i));
+            if let (Some((param, t)), Some(v)) = (param, value_type) {
+                if !assignable(t, &v, arg) {
+                    errors.error(
+                        arg.span,
+                        Code::ArgumentMismatch,
+                        format!("Cannot pass {} as {} of {}", v, param, name),
+                    );
+                    errors.note(format!("{} is of type {}", param, t));
+                }
+            }
+        }
+    }
+    /// Result type of a function call, None after reporting a procedure used as a value.
+    fn call(
+        &self,
+        name: &str,
+        args: &[Expr],
+        span: Span,
+        errors: &mut ErrorRecorder,
+    ) -> Option<TypeEnum> {
+        let callable = self.callables.get(name)?;
+        if callable.result.is_none() {
+            errors.error(
+                span,
+                Code::InvalidCall,
+                format!("Procedure {} has no value", name),
+            );
+            errors.help(format!("call it with `call {}`", name));
+            return None;
+        }
+        self.args(name, callable, args, span, errors);
+        callable.result.clone()
+    }
+    fn expr(&self, expr: &Expr, errors: &mut ErrorRecorder) -> Option<TypeEnum> {
+        match &expr.kind {
+            ExprKind::Number { .. } => Some(TypeEnum::Integer),
+            ExprKind::Real { .. } => Some(TypeEnum::Real),
+            ExprKind::Bool { .. } => Some(TypeEnum::Bool),
+            ExprKind::Char { .. } => Some(TypeEnum::Char),
+            // A function without parameters is called by its name alone
+            ExprKind::Variable { variable }
+                if variable.selectors.is_empty()
+                    && !self.vars.contains_key(&variable.name)
+                    && self.callables.contains_key(&variable.name) =>
+            {
+                self.call(&variable.name, &[], expr.span, errors)
+            }
+            ExprKind::Variable { variable } => self.variable(variable, errors),
+            ExprKind::Call { function, args } => {
+                if self.vars.contains_key(function) {
+                    errors.error(
+                        expr.span,
+                        Code::InvalidCall,
+                        format!("{} is not a function", function),
+                    );
+                    return None;
+                }
+                if !self.callables.contains_key(function) {
+                    let span = Span::new(expr.span.start, expr.span.start + function.len());
+                    self.undeclared(function, span, errors);
+                    for arg in args {
+                        self.expr(arg, errors);
+                    }
+                    return None;
+                }
+                self.call(function, args, expr.span, errors)
+            }
+            ExprKind::Unary { op, operand } => {
+                let t = self.expr(operand, errors)?;
+                let (ok, expected) = match op {
+                    UnaryOp::Neg => (t.is_numeric(), "numeric"),
+                    UnaryOp::Odd => (t.is_integer(), "integer"),
+                    UnaryOp::Not => (t == TypeEnum::Bool, "Bool"),
+                };
+                if !ok {
+                    errors.error(
+                        operand.span,
+                        Code::OperandMismatch,
+                        format!("Expected {} operand of `{}`, found {}", expected, op, t),
+                    );
+                    return None;
+                }
+                Some(match op {
+                    UnaryOp::Neg => arithmetic(&t, &t),
+                    UnaryOp::Odd | UnaryOp::Not => TypeEnum::Bool,
+                })
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let (l, r) = (self.expr(lhs, errors), self.expr(rhs, errors));
+                let (l, r) = (l?, r?);
+                if op.is_relational() {
+                    if (l.is_numeric() && r.is_numeric()) || l == r {
+                        return Some(TypeEnum::Bool);
+                    }
+                    errors.error(
+                        rhs.span,
+                        Code::OperandMismatch,
+                        format!("Cannot compare {} with {}", l, r),
+                    );
+                    return None;
+                }
+                let logical = matches!(op, BinaryOp::And | BinaryOp::Or);
+                for (operand, t) in [(lhs, &l), (rhs, &r)] {
+                    let (ok, expected) = if logical {
+                        (*t == TypeEnum::Bool, "Bool")
+                    } else {
+                        (t.is_numeric(), "numeric")
+                    };
+                    if !ok {
+                        errors.error(
+                            operand.span,
+                            Code::OperandMismatch,
+                            format!("Expected {} operand of `{}`, found {}", expected, op, t),
+                        );
+                        return None;
+                    }
+                }
+                Some(if logical {
+                    TypeEnum::Bool
+                } else {
+                    arithmetic(&l, &r)
+                })
+            }
+        }
+    }
+}
+/// Result type of an arithmetic operation on numeric operands.
+fn arithmetic(l: &TypeEnum, r: &TypeEnum) -> TypeEnum {
+    if *l == TypeEnum::Real || *r == TypeEnum::Real {
+        TypeEnum::Real
+    } else if *l == TypeEnum::Longint || *r == TypeEnum::Longint {
+        TypeEnum::Longint
+    } else {
+        TypeEnum::Integer
+    }
+}
+/// Whether a value of type `value` can be assigned to a variable of type `target`.
+/// Integers widen to longint and real, but a longint does not narrow to an integer
+/// or a subrange. Bool also accepts the constants 0 and 1 as false and true,
+/// as the reference program of the lab assignment (`data/0-correct.in`) does.
+fn assignable(target: &TypeEnum, value: &TypeEnum, expr: &Expr) -> bool {
+    match target {
+        TypeEnum::Real => value.is_numeric(),
+        TypeEnum::Longint => value.is_integer(),
+        TypeEnum::Integer | TypeEnum::Subrange(..) => {
+            value.is_integer() && *value != TypeEnum::Longint
+        }
+        TypeEnum::Bool => {
+            *value == TypeEnum::Bool || matches!(expr.kind, ExprKind::Number { value: 0 | 1 })
+        }
+        t => t == value,
+    }
+}
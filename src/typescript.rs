@@ -0,0 +1,22 @@
+use ts_rs::{Config, TS};
+
+use crate::{
+    lex::{Token, TokenEnum},
+    session::TokenOutput,
+};
+
+fn declaration<T: TS + ?Sized>(cfg: &Config) -> String {
+    let docs = T::docs().unwrap_or_default();
+    format!("{}export {}\n", docs, T::decl(cfg))
+}
+
+/// TypeScript declarations (`.d.ts`) of the JSON token output.
+pub fn typescript() -> String {
+    let cfg = Config::new();
+    [
+        declaration::<TokenOutput>(&cfg),
+        declaration::<Token>(&cfg),
+        declaration::<TokenEnum>(&cfg),
+    ]
+    .join("\n")
+}
@@ -0,0 +1,187 @@
+use compilation_exp1::{
+    ast::{Expr, ExprKind, Program, Statement, StatementKind},
+    parse, LexOptions, SourceMap,
+};
+
+fn ast(content: &str) -> Option<Program> {
+    let mut sources = SourceMap::new();
+    let file = sources.add("a.in", content);
+    parse(&sources, file, &LexOptions::default()).ast
+}
+
+/// Statement kinds, nested ones in brackets.
+fn shape(statements: &[Statement]) -> String {
+    let kinds: Vec<_> = statements
+        .iter()
+        .map(|s| match &s.kind {
+            StatementKind::Assign { target, .. } => format!("assign {}", target),
+            StatementKind::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let mut branches = vec![then_branch.as_ref().clone()];
+                branches.extend(else_branch.as_deref().cloned());
+                format!("if [{}]", shape(&branches))
+            }
+            StatementKind::While { body, .. } => {
+                format!("while [{}]", shape(std::slice::from_ref(body)))
+            }
+            StatementKind::Block { statements } => format!("block [{}]", shape(statements)),
+            StatementKind::Case { arms, .. } => format!("case {}", arms.len()),
+            StatementKind::Call { procedure, .. } => format!("call {}", procedure),
+            StatementKind::Read { .. } => "read".to_string(),
+            StatementKind::Write { .. } => "write".to_string(),
+            StatementKind::Empty => "empty".to_string(),
+        })
+        .collect();
+    kinds.join(", ")
+}
+
+#[test]
+fn program_block_is_parsed_into_statements() {
+    let program = "\
+var i: integer; a: array [1..2] of integer;
+procedure p; begin i := 0 end;
+begin
+    I := 1;
+    if i < 2 then begin a[i] := i; call p end else ;
+    while i > 0 do i := i - 1;
+    write(i)
+end.
+";
+    let ast = ast(program).unwrap();
+    assert_eq!(
+        shape(&ast.statements),
+        "assign i, if [block [assign a[i], call p], empty], while [assign i], write"
+    );
+    assert_eq!(ast.procedures.len(), 1);
+    assert_eq!(ast.procedures[0].name, "p");
+    assert_eq!(shape(&ast.procedures[0].statements), "assign i");
+    assert_eq!(ast.statement_count(), 9);
+}
+
+#[test]
+fn nodes_span_their_source() {
+    let program = "var i: integer;\nbegin i := i + 1; write(i) end\n";
+    let ast = ast(program).unwrap();
+    let source = |span: compilation_exp1::source_map::Span| &program[span.start..span.end];
+    assert_eq!(source(ast.span), "begin i := i + 1; write(i) end");
+    assert_eq!(source(ast.statements[0].span), "i := i + 1");
+    assert_eq!(source(ast.statements[1].span), "write(i)");
+    let StatementKind::Assign { value, .. } = &ast.statements[0].kind else {
+        panic!("not an assignment");
+    };
+    assert_eq!(source(value.span), "i + 1");
+}
+
+#[test]
+fn a_syntax_error_skips_only_its_statement() {
+    let program = "var i: integer;\nbegin i := ; i := 2; if then i := 3 end\n";
+    let ast = ast(program).unwrap();
+    assert_eq!(shape(&ast.statements), "assign i");
+}
+
+/// Every operator application in parentheses, whatever its precedence.
+fn parenthesized(expr: &Expr) -> String {
+    match &expr.kind {
+        ExprKind::Unary { op, operand } => format!("({} {})", op, parenthesized(operand)),
+        ExprKind::Binary { op, lhs, rhs } => {
+            format!("({} {} {})", parenthesized(lhs), op, parenthesized(rhs))
+        }
+        _ => expr.to_string(),
+    }
+}
+
+/// Trees of the arguments of a `write` of the expressions.
+fn expressions(expressions: &str) -> Vec<String> {
+    let program = format!(
+        "var a, b, c: integer; p, q: bool;\nbegin write({}) end\n",
+        expressions
+    );
+    let ast = ast(&program).unwrap();
+    let StatementKind::Write { values } = &ast.statements[0].kind else {
+        panic!("not a write");
+    };
+    values.iter().map(parenthesized).collect()
+}
+
+#[test]
+fn operators_bind_by_precedence() {
+    assert_eq!(
+        expressions("a + b * c, a * b + c, a - b - c, a / b / c, (a + b) * c"),
+        [
+            "(a + (b * c))",
+            "((a * b) + c)",
+            "((a - b) - c)",
+            "((a / b) / c)",
+            "((a + b) * c)",
+        ]
+    );
+    assert_eq!(
+        expressions("a + 1 < b * 2, p or q and a == b, p and q or p, -a * b"),
+        [
+            "((a + 1) < (b * 2))",
+            "(p or (q and (a == b)))",
+            "((p and q) or p)",
+            "((- a) * b)",
+        ]
+    );
+    assert_eq!(
+        expressions("not a < b and p, odd a + 1"),
+        ["((not (a < b)) and p)", "(odd (a + 1))"]
+    );
+}
+
+#[test]
+fn display_keeps_the_required_parentheses() {
+    let program = "var a, b, c: integer;\nbegin write((a + b) * c, a - (b - c), a * b + c) end\n";
+    let ast = ast(program).unwrap();
+    let StatementKind::Write { values } = &ast.statements[0].kind else {
+        panic!("not a write");
+    };
+    let shown: Vec<_> = values.iter().map(|e| e.to_string()).collect();
+    assert_eq!(shown, ["(a + b) * c", "a - (b - c)", "a * b + c"]);
+}
+
+#[test]
+fn malformed_expressions_are_rejected() {
+    let mut sources = SourceMap::new();
+    let program = "var a, b, c: integer;\nbegin write(a < b < c); write((a + b); write(a +) end\n";
+    let file = sources.add("a.in", program);
+    let result = parse(&sources, file, &LexOptions::default());
+    let codes: Vec<_> = result
+        .errors
+        .sorted()
+        .into_iter()
+        .map(|(_, e)| e.code.to_string())
+        .collect();
+    assert_eq!(codes, ["E024", "E048", "E020", "E020"]);
+}
+
+#[test]
+fn program_header_names_the_program() {
+    let name = |content: &str| {
+        let mut sources = SourceMap::new();
+        let file = sources.add("a.in", content);
+        let result = parse(&sources, file, &LexOptions::default());
+        let codes: Vec<_> = result
+            .errors
+            .sorted()
+            .into_iter()
+            .map(|(_, e)| e.code.to_string())
+            .collect();
+        (result.program, codes)
+    };
+    assert_eq!(
+        name("program sum;\nvar s: integer;\nbegin s := 0 end.\n"),
+        (Some("sum".to_string()), vec![])
+    );
+    assert_eq!(
+        name("Program Sum;\nbegin end.\n"),
+        (Some("sum".to_string()), vec![])
+    );
+    assert_eq!(name("begin end.\n"), (None, vec![]));
+    assert_eq!(name("program;\nbegin end.\n").1, ["E020"]);
+    assert_eq!(name("program sum\nbegin end.\n").1, ["E021"]);
+}
@@ -0,0 +1,50 @@
+use std::{path::PathBuf, process::Command};
+
+const BIN: &str = env!("CARGO_BIN_EXE_compilation-exp1");
+
+/// Run the binary on a program with a leading zero warning, returns (success, stdout, stderr).
+fn run(args: &[&str]) -> (bool, String, String) {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cli.in");
+    std::fs::write(&path, "var i: integer;\nbegin i := 01 end\n").unwrap();
+    let output = Command::new(BIN)
+        .args(args)
+        .arg(&path)
+        .env("RUST_BACKTRACE", "0")
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn global_flags_go_before_or_after_the_subcommand() {
+    for args in [
+        &["--color", "never", "lex"][..],
+        &["lex", "--color", "never"],
+        &["--diagnostics-format", "short", "lex"],
+    ] {
+        let (success, stdout, stderr) = run(args);
+        assert!(success, "{:?}: {}", args, stderr);
+        assert!(stdout.contains("\"version\": 1"), "{:?}: {}", args, stdout);
+    }
+    let (success, _, stderr) = run(&["-D", "W001", "--diagnostics-format", "short", "check"]);
+    assert!(!success);
+    assert!(stderr.contains("error[W001]"), "{}", stderr);
+    let (success, _, stderr) = run(&["check", "-D", "W001", "--diagnostics-format", "short"]);
+    assert!(!success);
+    assert!(stderr.contains("error[W001]"), "{}", stderr);
+}
+
+#[test]
+fn flags_of_the_default_mode_are_rejected_with_a_subcommand() {
+    let (success, _, stderr) = run(&["-f", "yaml", "lex"]);
+    assert!(!success);
+    assert!(
+        stderr.contains("the argument '--format <FORMAT>' cannot be used with 'lex'"),
+        "{}",
+        stderr
+    );
+}
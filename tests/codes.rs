@@ -0,0 +1,151 @@
+use std::{collections::BTreeSet, process::Command};
+
+use compilation_exp1::{
+    codes::{Code, Lint, LintLevel, WarningLevels},
+    error::DisplayOptions,
+    parse, LexOptions, SourceMap,
+};
+
+const BIN: &str = env!("CARGO_BIN_EXE_compilation-exp1");
+
+#[test]
+fn codes_are_unique_and_parse_back() {
+    let strs: BTreeSet<_> = Code::ALL.iter().map(|code| code.as_str()).collect();
+    assert_eq!(strs.len(), Code::ALL.len());
+    for code in Code::ALL {
+        let s = code.as_str();
+        assert!(
+            s.len() == 4 && s[1..].bytes().all(|b| b.is_ascii_digit()),
+            "{}",
+            s
+        );
+        assert_eq!(code.is_warning(), s.starts_with('W'), "{}", s);
+        assert!(s.starts_with(['E', 'W']), "{}", s);
+        assert_eq!(s.parse::<Code>().unwrap(), code);
+        assert_eq!(s.to_lowercase().parse::<Code>().unwrap(), code);
+    }
+    assert_eq!(
+        "E999".parse::<Code>().unwrap_err().to_string(),
+        "Unknown diagnostic code: E999"
+    );
+}
+
+#[test]
+fn every_code_is_explained() {
+    for code in Code::ALL {
+        assert!(!code.summary().is_empty(), "{}", code);
+        let explanation = code.explanation();
+        assert!(explanation.ends_with('\n'), "{}", code);
+        assert!(!explanation.starts_with(char::is_whitespace), "{}", code);
+    }
+}
+
+#[test]
+fn explain_prints_the_summary_and_the_explanation() {
+    let output = Command::new(BIN)
+        .args(["--explain", "e002"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let expected = format!(
+        "E002: {}\n\n{}",
+        Code::UnexpectedEq.summary(),
+        Code::UnexpectedEq.explanation()
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+
+    let output = Command::new(BIN)
+        .args(["--explain", "E999"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Unknown diagnostic code: E999"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn only_warnings_are_lints() {
+    assert_eq!("Warnings".parse::<Lint>().unwrap(), Lint::Warnings);
+    assert_eq!(
+        "w002".parse::<Lint>().unwrap(),
+        Lint::Code(Code::DiscardedComparison)
+    );
+    assert_eq!(
+        "E002".parse::<Lint>().unwrap_err().to_string(),
+        "E002 is an error, only warnings can be selected"
+    );
+    assert!("X001".parse::<Lint>().is_err());
+}
+
+#[test]
+fn a_code_overrides_warnings_and_the_strictest_level_wins() {
+    let code = Code::DiscardedComparison;
+    let mut warnings = WarningLevels::default();
+    assert_eq!(warnings.level(code), LintLevel::Warn);
+    warnings.set(Lint::Warnings, LintLevel::Deny);
+    assert_eq!(warnings.level(code), LintLevel::Deny);
+    warnings.set(Lint::Code(code), LintLevel::Allow);
+    assert_eq!(warnings.level(code), LintLevel::Allow);
+    assert_eq!(warnings.level(Code::LeadingZeros), LintLevel::Deny);
+    warnings.set(Lint::Code(code), LintLevel::Warn);
+    assert_eq!(warnings.level(code), LintLevel::Warn);
+    warnings.set(Lint::Warnings, LintLevel::Allow);
+    assert_eq!(warnings.level(Code::LeadingZeros), LintLevel::Deny);
+}
+
+/// Error and warning counts of the program with the lint at the level.
+fn counts(lint: &str, level: LintLevel) -> (usize, usize) {
+    let mut sources = SourceMap::new();
+    let file = sources.add("a.in", "var i: integer;\nbegin i == 01 end\n");
+    let mut result = parse(&sources, file, &LexOptions::default());
+    let mut warnings = WarningLevels::default();
+    warnings.set(lint.parse().unwrap(), level);
+    result.errors.set_options(DisplayOptions {
+        warnings,
+        ..DisplayOptions::default()
+    });
+    (result.errors.error_count(), result.errors.warning_count())
+}
+
+#[test]
+fn lint_levels_apply_to_recorded_warnings() {
+    assert_eq!(counts("warnings", LintLevel::Warn), (0, 2));
+    assert_eq!(counts("warnings", LintLevel::Allow), (0, 0));
+    assert_eq!(counts("warnings", LintLevel::Deny), (2, 0));
+    assert_eq!(counts("W002", LintLevel::Allow), (0, 1));
+    assert_eq!(counts("W002", LintLevel::Deny), (1, 1));
+}
+
+#[test]
+fn deny_fails_the_check() {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("deny.in");
+    std::fs::write(&path, "var i: integer;\nbegin i == 1 end\n").unwrap();
+    let check = |args: &[&str]| {
+        let output = Command::new(BIN)
+            .arg("check")
+            .arg(&path)
+            .args(args)
+            .args(["--diagnostics-format", "short"])
+            .env("RUST_BACKTRACE", "0")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        (
+            output.status.success(),
+            stderr.lines().next().unwrap_or_default().to_string(),
+        )
+    };
+    let (success, first) = check(&[]);
+    assert!(success);
+    assert!(first.ends_with("warning[W002]: Comparison result is discarded. Did you mean `:=`?"));
+    let (success, first) = check(&["-D", "W002"]);
+    assert!(!success);
+    assert!(first.ends_with("error[W002]: Comparison result is discarded. Did you mean `:=`?"));
+    let (success, first) = check(&["-A", "warnings"]);
+    assert!(success);
+    assert_eq!(first, "");
+}
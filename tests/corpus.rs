@@ -0,0 +1,42 @@
+//! Diagnostics of every `data/*.in` file, in the short format, against
+//! `tests/corpus/<name>.stderr`. `UPDATE_EXPECT=1 cargo test --test corpus` rewrites them.
+use std::{path::Path, process::Command};
+
+const BIN: &str = env!("CARGO_BIN_EXE_compilation-exp1");
+
+#[test]
+fn corpus_diagnostics_match_the_expected_ones() {
+    let mut paths: Vec<_> = std::fs::read_dir("data")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "in"))
+        .collect();
+    paths.sort();
+    let update = std::env::var_os("UPDATE_EXPECT").is_some();
+    let mut mismatches = Vec::new();
+    for path in paths {
+        let output = Command::new(BIN)
+            .args(["check", "--diagnostics-format", "short"])
+            .arg(&path)
+            .env("RUST_BACKTRACE", "0")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let expected_path = Path::new("tests/corpus").join(format!("{}.stderr", name));
+        if update {
+            std::fs::write(&expected_path, &stderr).unwrap();
+            continue;
+        }
+        let expected = std::fs::read_to_string(&expected_path).unwrap_or_default();
+        if stderr != expected {
+            mismatches.push(format!(
+                "{}:\n--- expected\n{}--- found\n{}",
+                path.display(),
+                expected,
+                stderr
+            ));
+        }
+    }
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
@@ -0,0 +1,74 @@
+use compilation_exp1::{
+    derivation::{is_nonterminal, to_derivation, Production, START_SYMBOL},
+    session::Session,
+};
+
+fn trace(content: &str) -> Vec<Production> {
+    let mut session = Session::new();
+    let file = session.add_source("a.in", content.to_string());
+    session.check();
+    session.trace(file).to_vec()
+}
+
+/// Sentential form reached by applying the productions to the leftmost nonterminal.
+fn derive(trace: &[Production]) -> Vec<&'static str> {
+    let mut form = vec![START_SYMBOL];
+    for production in trace {
+        let leftmost = form.iter().position(|s| is_nonterminal(s)).unwrap();
+        assert_eq!(form[leftmost], production.lhs(), "{:?}", trace);
+        form.splice(leftmost..=leftmost, production.rhs().iter().copied());
+    }
+    form
+}
+
+#[test]
+fn derivation_yields_the_var_block() {
+    let program = "var i, j: integer;\n    a: array [1..2] of bool;\nbegin end.\n";
+    assert_eq!(
+        derive(&trace(program)),
+        ["Var", "i", ",", "i", ":", "t", ";", "i", ":", "t", ";"]
+    );
+    assert_eq!(derive(&trace("begin end.\n")), Vec::<&str>::new());
+}
+
+#[test]
+fn derivation_steps_are_numbered() {
+    assert_eq!(
+        to_derivation(&trace("var i: integer;\nbegin end.\n")),
+        "      S
+   1  => Var D                S -> Var D
+   2  => Var D1 D'            D -> D1 D'
+   3  => Var I : t ; D'       D1 -> I : t ;
+   4  => Var i I' : t ; D'    I -> i I'
+   5  => Var i : t ; D'       I' -> ε
+   6  => Var i : t ;          D' -> ε
+"
+    );
+    assert_eq!(
+        to_derivation(&trace("begin end.\n")),
+        "      S\n   1  => ε    S -> ε\n"
+    );
+}
+
+#[test]
+fn hard_error_falls_back_to_the_most_plausible_derivation() {
+    for (var_block, code, form) in [
+        ("var i, : integer;", "E020", "Var i , i : t ;"),
+        ("var i j: integer;", "E022", "Var i , i : t ;"),
+        ("var i: integer k: bool;", "E021", "Var i : t ; i : t ;"),
+        ("var : integer; j: bool;", "E020", "Var i : t ; i : t ;"),
+        ("var i: ; j: bool;", "E020", "Var i : t ; i : t ;"),
+    ] {
+        let mut session = Session::new();
+        let file = session.add_source("a.in", format!("{}\nbegin end.\n", var_block));
+        session.check();
+        let codes: Vec<_> = session
+            .errors(file)
+            .sorted()
+            .into_iter()
+            .map(|(_, e)| e.code.to_string())
+            .collect();
+        assert_eq!(codes, [code], "{}", var_block);
+        assert_eq!(derive(session.trace(file)).join(" "), form, "{}", var_block);
+    }
+}
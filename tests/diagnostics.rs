@@ -0,0 +1,120 @@
+use compilation_exp1::{
+    diagnostics::{self, DiagnosticsFormat},
+    parse, LexOptions, SourceMap,
+};
+use serde_json::Value;
+
+/// Diagnostics of the source rendered in the format.
+fn render(content: &str, format: DiagnosticsFormat) -> String {
+    let mut sources = SourceMap::new();
+    let file = sources.add("a.in", content);
+    let result = parse(&sources, file, &LexOptions::default());
+    diagnostics::render(format, &sources, &result.errors.sorted()).unwrap()
+}
+
+#[test]
+fn json_clamps_eof_offsets_to_the_end_of_input() {
+    let json: Value = serde_json::from_str(&render("var", DiagnosticsFormat::Json)).unwrap();
+    let diagnostic = &json[0];
+    assert_eq!(diagnostic["code"], "E020");
+    assert_eq!(diagnostic["offset"], 3);
+    assert_eq!(diagnostic["line"], 1);
+    assert_eq!(diagnostic["col"], 4);
+    assert_eq!(diagnostic["length"], 0);
+}
+
+#[test]
+fn sarif_clamps_eof_offsets_to_the_end_of_input() {
+    let sarif: Value = serde_json::from_str(&render("var", DiagnosticsFormat::Sarif)).unwrap();
+    let result = &sarif["runs"][0]["results"][0];
+    assert_eq!(result["ruleId"], "E020");
+    let region = &result["locations"][0]["physicalLocation"]["region"];
+    assert_eq!(region["charOffset"], 3);
+    assert_eq!(region["charLength"], 0);
+    assert_eq!(region["startLine"], 1);
+    assert_eq!(region["startColumn"], 4);
+}
+
+#[test]
+fn duplicate_of_glued_var_points_after_the_prefix() {
+    let content = "vari: integer;\n    i: integer;\nbegin\nend\n";
+    let json: Value = serde_json::from_str(&render(content, DiagnosticsFormat::Json)).unwrap();
+    let duplicate = &json[1];
+    assert_eq!(duplicate["code"], "E010");
+    let label = &duplicate["labels"][0];
+    assert_eq!(label["message"], "i first declared here");
+    assert_eq!((&label["line"], &label["col"]), (&1.into(), &4.into()));
+    assert_eq!(label["length"], 1);
+}
+
+#[test]
+fn duplicate_case_label_points_at_the_original() {
+    let content = "var n: integer;\nbegin\n    case n of\n        1, 2: n := 0;\n        2: n := 1\n    end\nend\n";
+    let json: Value = serde_json::from_str(&render(content, DiagnosticsFormat::Json)).unwrap();
+    let duplicate = &json[0];
+    assert_eq!(duplicate["code"], "E045");
+    assert_eq!(
+        (&duplicate["line"], &duplicate["col"]),
+        (&5.into(), &9.into())
+    );
+    let label = &duplicate["labels"][0];
+    assert_eq!(label["message"], "first handled here");
+    assert_eq!((&label["line"], &label["col"]), (&4.into(), &12.into()));
+}
+
+#[test]
+fn assignment_to_constant_points_at_its_declaration() {
+    let content = "const\n    size = 3;\nbegin\n    size := 4\nend\n";
+    let json: Value = serde_json::from_str(&render(content, DiagnosticsFormat::Json)).unwrap();
+    let error = &json[0];
+    assert_eq!(error["code"], "E036");
+    let label = &error["labels"][0];
+    assert_eq!(label["message"], "size declared here");
+    assert_eq!((&label["line"], &label["col"]), (&2.into(), &5.into()));
+    assert_eq!(label["length"], 4);
+}
+
+/// Every format reports an error at EOF on the last character of a one-line file.
+#[test]
+fn line_formats_clamp_eof_positions_to_the_end_of_input() {
+    let cases = [
+        (
+            DiagnosticsFormat::Short,
+            "a.in:1:4: error[E020]: Expected begin\n",
+        ),
+        (
+            DiagnosticsFormat::Github,
+            "::error file=a.in,line=1,col=4,title=E020::Expected begin\n",
+        ),
+        (
+            DiagnosticsFormat::Quickfix,
+            "a.in:1:4:E:E020: Expected begin\n",
+        ),
+    ];
+    for (format, expected) in cases {
+        assert_eq!(render("var", format), expected, "{:?}", format);
+    }
+}
+
+#[test]
+fn junit_clamps_eof_positions_to_the_end_of_input() {
+    let mut sources = SourceMap::new();
+    let file = sources.add("a.in", "var");
+    let result = parse(&sources, file, &LexOptions::default());
+    let junit = diagnostics::to_junit(&sources, &[("a.in", &result.errors)]);
+    assert!(junit.contains("<testsuites tests=\"1\" failures=\"1\">"));
+    assert!(junit.contains(
+        "<failure message=\"1 error(s), 0 warning(s)\" type=\"error\">\
+         a.in:1:4: error[E020]: Expected begin\n</failure>"
+    ));
+}
+
+#[test]
+fn missing_semicolon_in_the_var_block_is_reported_once() {
+    let content = "var a: integer\n b: real;\n e, : real;\nbegin end\n";
+    assert_eq!(
+        render(content, DiagnosticsFormat::Short),
+        "a.in:1:15: error[E021]: Missing semicolon\n\
+         a.in:3:5: error[E020]: Expected Identifier, found Colon\n"
+    );
+}
@@ -0,0 +1,41 @@
+use compilation_exp1::{
+    output::{self, OutputWriter},
+    parse,
+    session::Session,
+    LexOptions, SourceMap,
+};
+use serde_json::Value;
+
+/// JSON token dump of the program, with the content of the char literal replaced.
+fn dump_with_char(content: &str) -> Vec<u8> {
+    let mut sources = SourceMap::new();
+    let file = sources.add("a.in", "var c: char;\nbegin c := 'x' end\n");
+    let result = parse(&sources, file, &LexOptions::default());
+    let mut dump: Value = serde_json::from_slice(&output::Json.write(&result).unwrap()).unwrap();
+    let tokens = dump["tokens"].as_array_mut().unwrap();
+    let literal = tokens
+        .iter_mut()
+        .find(|t| t["token"] == "CharLiteral")
+        .unwrap();
+    literal["content"] = content.into();
+    serde_json::to_vec(&dump).unwrap()
+}
+
+/// Codes of the diagnostics of the checked dump.
+fn codes(dump: &[u8]) -> Vec<String> {
+    let mut session = Session::new();
+    let file = session.add_token_dump("a.json", dump).unwrap();
+    session.check();
+    let errors = session.errors(file).sorted();
+    errors.iter().map(|(_, e)| e.code.to_string()).collect()
+}
+
+#[test]
+fn malformed_char_literals_of_a_dump_are_reported() {
+    assert_eq!(codes(&dump_with_char("'x'")), Vec::<String>::new());
+    assert_eq!(codes(&dump_with_char("''''")), Vec::<String>::new());
+    assert_eq!(codes(&dump_with_char("'é'")), Vec::<String>::new());
+    for content in ["", "'", "é", "'é", "''", "'xy'", "'''"] {
+        assert_eq!(codes(&dump_with_char(content)), ["E006"], "{}", content);
+    }
+}
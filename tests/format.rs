@@ -0,0 +1,141 @@
+use compilation_exp1::{
+    pre_grammar::lex_only,
+    session::{Artifact, Session},
+    FileId, LexOptions, SourceMap, TokenEnum,
+};
+
+/// Session with a single source checked, returns it with the file.
+fn checked(content: &str, options: LexOptions) -> (Session, FileId) {
+    let mut session = Session::new().lex_options(options);
+    let file = session.add_source("a.in", content.to_string());
+    session.check();
+    (session, file)
+}
+
+fn emit(content: &str, artifact: Artifact) -> String {
+    let (session, file) = checked(content, LexOptions::default());
+    assert_eq!(session.errors(file).error_count(), 0, "{}", content);
+    String::from_utf8(session.emit(file, artifact).unwrap()).unwrap()
+}
+
+/// Kinds and contents of the tokens of the source.
+fn tokens(content: &str, options: LexOptions) -> Vec<(TokenEnum, String)> {
+    let mut sources = SourceMap::new();
+    let file = sources.add("a.in", content);
+    let result = lex_only(&sources, file, &options);
+    assert_eq!(result.errors.error_count(), 0, "{}", content);
+    result
+        .tokens
+        .into_iter()
+        .map(|t| (t.token, t.content))
+        .collect()
+}
+
+/// The data files free of diagnostics.
+fn correct_programs() -> Vec<String> {
+    let mut programs = Vec::new();
+    for entry in std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/data")).unwrap() {
+        let path = entry.unwrap().path();
+        if path.to_string_lossy().contains("correct") {
+            programs.push(std::fs::read_to_string(path).unwrap());
+        }
+    }
+    assert!(!programs.is_empty());
+    programs
+}
+
+const NESTED: &str = "\
+var i,j:integer; { counters }
+begin
+  i:=0;
+    while i<10 do begin i:=i+1; (* step *)
+  if odd i then j:=j*2 else begin j:=-j end end;
+  case i of 1,2: ; 3: write(i) end
+end.
+";
+
+#[test]
+fn formatting_is_idempotent_and_keeps_the_tokens() {
+    for program in correct_programs()
+        .iter()
+        .map(String::as_str)
+        .chain([NESTED])
+    {
+        let formatted = emit(program, Artifact::Formatted);
+        assert_eq!(
+            emit(&formatted, Artifact::Formatted),
+            formatted,
+            "{}",
+            program
+        );
+        assert_eq!(
+            tokens(&formatted, LexOptions::default()),
+            tokens(program, LexOptions::default()),
+            "{}",
+            formatted
+        );
+    }
+}
+
+#[test]
+fn formatting_indents_blocks_and_keeps_comments() {
+    assert_eq!(
+        emit(NESTED, Artifact::Formatted),
+        "\
+var
+    i, j: integer; { counters }
+begin
+    i := 0;
+    while i < 10 do
+    begin
+        i := i + 1; (* step *)
+        if odd i then
+            j := j * 2
+        else
+        begin
+            j := -j
+        end
+    end;
+    case i of
+        1, 2: ;
+        3: write(i)
+    end
+end.
+"
+    );
+}
+
+#[test]
+fn minified_output_lexes_to_the_same_tokens() {
+    for program in correct_programs()
+        .iter()
+        .map(String::as_str)
+        .chain([NESTED])
+    {
+        let minified = emit(program, Artifact::Minified);
+        assert_eq!(minified.lines().count(), 1, "{}", minified);
+        assert_eq!(
+            tokens(&minified, LexOptions::default()),
+            tokens(program, LexOptions::default()),
+            "{}",
+            minified
+        );
+    }
+    assert_eq!(
+        emit(NESTED, Artifact::Minified),
+        "var i,j:integer;begin i:=0;while i<10 do begin i:=i+1;if odd i then j:=j*2 else begin j:=-j end end;case i of 1,2:;3:write(i)end end.\n"
+    );
+
+    // Joined where the lexer still tells the tokens apart
+    let program =
+        "var a: array [1 .. 2] of integer; r: real;\nbegin a[1] := - - 1; r := 1.5 - - 2.0 end.\n";
+    let minified = emit(program, Artifact::Minified);
+    assert_eq!(
+        minified,
+        "var a:array[1..2]of integer;r:real;begin a[1]:=--1;r:=1.5--2.0 end.\n"
+    );
+    assert_eq!(
+        tokens(&minified, LexOptions::default()),
+        tokens(program, LexOptions::default())
+    );
+}
@@ -0,0 +1,26 @@
+use std::{fs, path::PathBuf};
+
+use compilation_exp1::glob;
+
+#[test]
+fn literal_components_after_a_wildcard_must_exist() {
+    let root = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("glob");
+    let _ = fs::remove_dir_all(&root);
+    for dir in ["a", "b", "c"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    fs::write(root.join("a/x.in"), "").unwrap();
+    fs::write(root.join("c/x.in"), "").unwrap();
+    fs::write(root.join("b/y.in"), "").unwrap();
+
+    let pattern = root.join("*/x.in");
+    let paths = glob::expand(pattern.to_str().unwrap()).unwrap();
+    assert_eq!(paths, [root.join("a/x.in"), root.join("c/x.in")]);
+
+    let pattern = root.join("*/z.in");
+    let error = glob::expand(pattern.to_str().unwrap()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!("No file matches {}", pattern.display())
+    );
+}
@@ -0,0 +1,41 @@
+use compilation_exp1::{grammar, ErrorRecorder, FileId};
+
+fn analyze(content: &str) -> String {
+    let mut errors = ErrorRecorder::new(FileId::default());
+    let grammar = grammar::Grammar::parse(content, &mut errors).unwrap();
+    assert!(errors.no_error());
+    grammar::analyze(&grammar)
+}
+
+#[test]
+fn left_recursive_grammar_is_not_ll1() {
+    for content in ["A -> A | a\n", "S -> A b\nA -> A c\n"] {
+        let report = analyze(content);
+        assert!(report.contains("Direct left recursion on A"), "{}", report);
+        assert!(
+            report.ends_with("The grammar is not LL(1).\n"),
+            "{}",
+            report
+        );
+    }
+}
+
+#[test]
+fn left_factoring_suggestion_is_aligned() {
+    let report = analyze("S -> a b | a c\n");
+    let expected = "\
+    Common prefix `a` in S:
+            S -> a b
+            S -> a c
+        Suggested:
+            S -> a S'
+            S' -> b | c
+";
+    assert!(report.contains(expected), "{}", report);
+}
+
+#[test]
+fn ll1_grammar() {
+    let report = analyze("S -> a S | b\n");
+    assert!(report.ends_with("The grammar is LL(1).\n"), "{}", report);
+}
@@ -0,0 +1,41 @@
+use compilation_exp1::session::{Artifact, Session};
+
+/// Session with a single source checked, returns it with the file.
+fn checked(content: &str) -> (Session, compilation_exp1::FileId) {
+    let mut session = Session::new();
+    let file = session.add_source("a.in", content.to_string());
+    session.check();
+    (session, file)
+}
+
+#[test]
+fn html_marks_eof_diagnostics_at_the_end_of_input() {
+    for content in ["", "var", "procedure p; begin end"] {
+        let (session, file) = checked(content);
+        let html = String::from_utf8(session.emit(file, Artifact::Html).unwrap()).unwrap();
+        assert!(html.contains("<tr class=\"error\">"), "{}", html);
+        assert!(
+            html.contains("<span class=\"mark-error\"> </span>"),
+            "{}",
+            html
+        );
+    }
+}
+
+#[test]
+fn report_lists_eof_diagnostics() {
+    for content in ["", "var", "procedure p; begin end"] {
+        let (session, file) = checked(content);
+        let report = session.html_report();
+        let summary = format!(
+            "<p>Files: 1, Errors: {}, Warnings: 0</p>",
+            session.errors(file).error_count()
+        );
+        assert!(report.contains(&summary), "{}", report);
+        assert!(
+            report.contains("<p id=\"d-0-0\" class=\"error\">"),
+            "{}",
+            report
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use compilation_exp1::{
+    lex::{self, keyword, Dialect, Limits, KEYWORDS},
+    ErrorRecorder, FileId, LexOptions, SourceMap, TokenEnum,
+};
+
+/// The lexer reads `=` the same everywhere, the parser tells the contexts apart.
+#[test]
+fn single_eq_is_lexed_without_context() {
+    let program = "const n = 3;\nvar i: integer;\nbegin i = n; if i = 1 then i := 2 end\n";
+    let mut errors = ErrorRecorder::new(FileId::default());
+    let (tokens, _) = lex::lex(program, &LexOptions::default(), &mut errors);
+    assert_eq!(errors.error_count(), 0);
+    let eqs: Vec<_> = tokens
+        .iter()
+        .filter(|t| t.token == TokenEnum::Eq)
+        .map(|t| t.content.as_str())
+        .collect();
+    assert_eq!(eqs, ["=", "=", "="]);
+
+    let mut sources = SourceMap::new();
+    let file = sources.add("a.in", program);
+    let result = compilation_exp1::parse(&sources, file, &LexOptions::default());
+    let messages: Vec<_> = result
+        .errors
+        .sorted()
+        .into_iter()
+        .map(|(_, e)| (e.code.to_string(), e.msg.clone()))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (
+                "E002".to_string(),
+                "Unexpected operator `=`. Did you mean `:=`?".to_string()
+            ),
+            (
+                "E002".to_string(),
+                "Unexpected operator `=`. Did you mean `==` or `:=`?".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn keywords_are_looked_up_ignoring_case() {
+    for (k, token) in KEYWORDS {
+        assert_eq!(keyword(k), Some(*token));
+        assert_eq!(keyword(&k.to_uppercase()), Some(*token));
+    }
+    for ident in ["", "i", "begins", "procedures", "ifé", "bégin"] {
+        assert_eq!(keyword(ident), None);
+    }
+}
+
+/// Every token kind, read from the JSON schema of `TokenEnum`.
+fn token_kinds() -> Vec<TokenEnum> {
+    fn collect(schema: &serde_json::Value, names: &mut Vec<String>) {
+        match schema {
+            serde_json::Value::Object(map) => {
+                if let Some(name) = map.get("const").and_then(|v| v.as_str()) {
+                    names.push(name.to_string());
+                }
+                if let Some(values) = map.get("enum").and_then(|v| v.as_array()) {
+                    names.extend(values.iter().filter_map(|v| v.as_str().map(String::from)));
+                }
+                map.values().for_each(|v| collect(v, names));
+            }
+            serde_json::Value::Array(values) => values.iter().for_each(|v| collect(v, names)),
+            _ => {}
+        }
+    }
+    let schema = serde_json::to_value(schemars::schema_for!(TokenEnum)).unwrap();
+    let mut names = Vec::new();
+    collect(&schema, &mut names);
+    names
+        .into_iter()
+        .map(|name| serde_json::from_value(serde_json::Value::String(name)).unwrap())
+        .collect()
+}
+
+/// `keyword` matches its own arms, so each token kind spelled as a keyword
+/// must be listed in `KEYWORDS` and the other way round.
+#[test]
+fn keyword_lookup_and_table_agree() {
+    let kinds = token_kinds();
+    assert!(kinds.contains(&TokenEnum::Identifier), "{:?}", kinds);
+    for kind in kinds {
+        let spelling = format!("{:?}", kind).to_lowercase();
+        let listed = KEYWORDS.iter().any(|(k, t)| *k == spelling && *t == kind);
+        assert_eq!(keyword(&spelling) == Some(kind), listed, "{:?}", kind);
+    }
+    for (k, token) in KEYWORDS {
+        assert_eq!(*k, format!("{:?}", token).to_lowercase());
+    }
+}
+
+/// Kinds and contents of the tokens, and the code, source and fix of each diagnostic.
+#[allow(clippy::type_complexity)]
+fn lexed(
+    program: &str,
+    options: &LexOptions,
+) -> (
+    Vec<(TokenEnum, String)>,
+    Vec<(String, String, Option<String>)>,
+) {
+    let mut errors = ErrorRecorder::new(FileId::default());
+    let (tokens, _) = lex::lex(program, options, &mut errors);
+    let chars: Vec<char> = program.chars().collect();
+    let tokens = tokens.into_iter().map(|t| (t.token, t.content)).collect();
+    let diagnostics = errors
+        .sorted()
+        .into_iter()
+        .map(|(_, e)| {
+            let source = chars[e.span.start..e.span.end].iter().collect();
+            let fix = e.fix.as_ref().map(|f| f.replacement.clone());
+            (e.code.to_string(), source, fix)
+        })
+        .collect();
+    (tokens, diagnostics)
+}
+
+#[test]
+fn real_literals_are_lexed_and_normalized() {
+    let (tokens, diagnostics) = lexed("1.5 0.25 1..2 12.50", &LexOptions::default());
+    assert_eq!(diagnostics, []);
+    let tokens: Vec<_> = tokens.iter().map(|(t, c)| (*t, c.as_str())).collect();
+    assert_eq!(
+        tokens,
+        [
+            (TokenEnum::RealLiteral, "1.5"),
+            (TokenEnum::RealLiteral, "0.25"),
+            (TokenEnum::IntLiteral, "1"),
+            (TokenEnum::DotDot, ".."),
+            (TokenEnum::IntLiteral, "2"),
+            (TokenEnum::RealLiteral, "12.50"),
+        ]
+    );
+
+    // Leading zeros and separators are dropped, the fraction is kept as written
+    let extended = LexOptions {
+        dialect: Dialect::Extended,
+        ..LexOptions::default()
+    };
+    let (tokens, diagnostics) = lexed("007.50 1_000.2_5", &extended);
+    assert_eq!(
+        diagnostics,
+        [("W001".to_string(), "007.50".to_string(), None)]
+    );
+    let contents: Vec<_> = tokens.iter().map(|(_, c)| c.as_str()).collect();
+    assert_eq!(contents, ["7.50", "1000.25"]);
+}
+
+#[test]
+fn malformed_real_literals_are_reported() {
+    let (_, diagnostics) = lexed("1. 1.2.3 007.5", &LexOptions::default());
+    assert_eq!(
+        diagnostics,
+        [
+            (
+                "E003".to_string(),
+                "1.".to_string(),
+                Some("1.0".to_string())
+            ),
+            ("E003".to_string(), "1.2.3".to_string(), None),
+            ("W001".to_string(), "007.5".to_string(), None),
+        ]
+    );
+}
+
+#[test]
+fn char_literals_hold_exactly_one_character() {
+    let (tokens, diagnostics) = lexed("'a' '''' 'é' ' '", &LexOptions::default());
+    assert_eq!(diagnostics, []);
+    let tokens: Vec<_> = tokens.iter().map(|(t, c)| (*t, c.as_str())).collect();
+    assert_eq!(
+        tokens,
+        [
+            (TokenEnum::CharLiteral, "'a'"),
+            (TokenEnum::CharLiteral, "''''"),
+            (TokenEnum::CharLiteral, "'é'"),
+            (TokenEnum::CharLiteral, "' '"),
+        ]
+    );
+
+    for (program, source) in [("'ab'", "'ab'"), ("x := 'a\n", "'a"), ("''", "''")] {
+        let (_, diagnostics) = lexed(program, &LexOptions::default());
+        let diagnostics: Vec<_> = diagnostics
+            .iter()
+            .map(|(code, source, _)| (code.as_str(), source.as_str()))
+            .collect();
+        assert_eq!(diagnostics, [("E006", source)], "{:?}", program);
+    }
+}
+
+#[test]
+fn block_comments_are_skipped() {
+    let (tokens, diagnostics) = lexed(
+        "x (* c *) y { c\n} z (* a { b } *) w (**) v {}",
+        &LexOptions::default(),
+    );
+    assert_eq!(diagnostics, []);
+    let contents: Vec<_> = tokens.iter().map(|(_, c)| c.as_str()).collect();
+    assert_eq!(contents, ["x", "y", "z", "w", "v"]);
+}
+
+#[test]
+fn unterminated_block_comment_points_at_its_opening() {
+    for (program, opening) in [
+        ("(* a *) x\n  (* open\n", 12),
+        ("{ a } x\n  { open *)\n", 10),
+    ] {
+        let mut errors = ErrorRecorder::new(FileId::default());
+        let (tokens, _) = lex::lex(program, &LexOptions::default(), &mut errors);
+        let contents: Vec<_> = tokens.iter().map(|t| t.content.as_str()).collect();
+        assert_eq!(contents, ["x"]);
+        let errors: Vec<_> = errors
+            .sorted()
+            .into_iter()
+            .map(|(_, e)| (e.code.to_string(), e.span.start))
+            .collect();
+        assert_eq!(errors, [("E007".to_string(), opening)], "{:?}", program);
+    }
+}
+
+#[test]
+fn misplaced_digit_separators_are_reported() {
+    let extended = LexOptions {
+        dialect: Dialect::Extended,
+        ..LexOptions::default()
+    };
+    let (tokens, diagnostics) = lexed("1_000 12_3.4_5", &extended);
+    assert_eq!(diagnostics, []);
+    let contents: Vec<_> = tokens.iter().map(|(_, c)| c.as_str()).collect();
+    assert_eq!(contents, ["1000", "123.45"]);
+
+    for (program, expected) in [
+        ("_1", vec![("E005", "_")]),
+        ("1_", vec![("E005", "_")]),
+        ("1__0", vec![("E005", "__")]),
+        ("1_.5", vec![("E005", "_")]),
+        ("1._5", vec![("E003", "1."), ("E005", "_")]),
+    ] {
+        let (_, diagnostics) = lexed(program, &extended);
+        let diagnostics: Vec<_> = diagnostics
+            .iter()
+            .map(|(code, source, _)| (code.as_str(), source.as_str()))
+            .collect();
+        assert_eq!(diagnostics, expected, "{}", program);
+    }
+
+    // Separators are an extension
+    let (_, diagnostics) = lexed("1_000", &LexOptions::default());
+    let codes: Vec<_> = diagnostics.iter().map(|(c, _, _)| c.as_str()).collect();
+    assert_eq!(codes, ["E008"]);
+}
+
+#[test]
+fn limits_are_reported_where_exceeded() {
+    let program = "var abcdef, x: integer;\nbegin x := 123456; x := 12345.5; x := 1 end.\n";
+    let limited = |limits: Limits| LexOptions {
+        limits,
+        ..LexOptions::default()
+    };
+    let messages = |limits: Limits| {
+        let (tokens, diagnostics) = lexed(program, &limited(limits));
+        let diagnostics: Vec<_> = diagnostics
+            .into_iter()
+            .map(|(code, source, _)| format!("{} {}", code, source))
+            .collect();
+        (tokens.len(), diagnostics)
+    };
+    let (count, diagnostics) = messages(Limits::default());
+    assert_eq!((count, diagnostics.len()), (21, 0));
+
+    let (count, diagnostics) = messages(Limits {
+        max_file_size: Some(10),
+        ..Limits::default()
+    });
+    assert_eq!(count, 0);
+    assert_eq!(diagnostics, ["E009 "]);
+
+    let (_, diagnostics) = messages(Limits {
+        max_line_length: Some(20),
+        ..Limits::default()
+    });
+    assert_eq!(diagnostics, ["E009 er;", "E009  := 12345.5; x := 1 end."]);
+
+    let (_, diagnostics) = messages(Limits {
+        max_identifier_length: Some(5),
+        max_number_length: Some(5),
+        ..Limits::default()
+    });
+    assert_eq!(diagnostics, ["E009 abcdef", "E009 123456", "E009 12345.5"]);
+
+    let (count, diagnostics) = messages(Limits {
+        max_tokens: Some(6),
+        ..Limits::default()
+    });
+    assert_eq!(count, 6);
+    assert_eq!(diagnostics.len(), 1);
+}
@@ -0,0 +1,76 @@
+use compilation_exp1::{
+    codes::{Lint, LintLevel, WarningLevels},
+    lsp, LexOptions,
+};
+use serde_json::{json, Value};
+
+/// Replies of the server to the message bodies, each framed by a `Content-Length` header.
+fn serve(bodies: &[String], warnings: &WarningLevels) -> Vec<Value> {
+    let input: String = bodies
+        .iter()
+        .map(|body| format!("Content-Length: {}\r\n\r\n{}", body.len(), body))
+        .collect();
+    let mut output = Vec::new();
+    lsp::serve(
+        input.as_bytes(),
+        &mut output,
+        &LexOptions::default(),
+        warnings,
+    )
+    .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    output
+        .split("Content-Length: ")
+        .skip(1)
+        .map(|message| serde_json::from_str(message.split_once("\r\n\r\n").unwrap().1).unwrap())
+        .collect()
+}
+fn did_open(text: &str) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": { "textDocument": { "uri": "file:///a.pl0", "text": text } },
+    })
+    .to_string()
+}
+/// Diagnostics published for a document opened with the text.
+fn published(text: &str, warnings: &WarningLevels) -> Vec<Value> {
+    let replies = serve(&[did_open(text)], warnings);
+    replies[0]["params"]["diagnostics"]
+        .as_array()
+        .unwrap()
+        .clone()
+}
+
+#[test]
+fn eof_diagnostics_are_clamped_to_the_end_of_the_document() {
+    let diagnostics = published("var", &WarningLevels::default());
+    let end = json!({ "line": 0, "character": 3 });
+    assert_eq!(diagnostics[0]["range"], json!({ "start": end, "end": end }));
+}
+
+#[test]
+fn warnings_follow_the_lint_levels() {
+    let program = "var i: integer;\nbegin i == 1 end\n";
+    let severities = |lint: &str, level| {
+        let mut warnings = WarningLevels::default();
+        warnings.set(lint.parse::<Lint>().unwrap(), level);
+        published(program, &warnings)
+            .iter()
+            .map(|d| d["severity"].as_u64().unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(severities("W002", LintLevel::Warn), [2]);
+    assert_eq!(severities("W002", LintLevel::Deny), [1]);
+    assert_eq!(severities("warnings", LintLevel::Allow), Vec::<u64>::new());
+}
+
+#[test]
+fn malformed_messages_get_a_parse_error() {
+    let bodies = ["{bad}".to_string(), did_open("var")];
+    let replies = serve(&bodies, &WarningLevels::default());
+    assert_eq!(replies.len(), 2);
+    assert_eq!(replies[0]["id"], Value::Null);
+    assert_eq!(replies[0]["error"]["code"], -32700);
+    assert_eq!(replies[1]["method"], "textDocument/publishDiagnostics");
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use compilation_exp1::{
+    lex::{self, Dialect},
+    msgpack,
+    output::{self, OutputWriter},
+    pre_grammar::unchecked,
+    session::TokenDump,
+    table::Tables,
+    ErrorRecorder, FileId, LexOptions, ParseResult,
+};
+
+/// Every token kind, identifiers and strings of each MessagePack length form,
+/// and offsets past 2^16.
+fn every_token_kind() -> ParseResult {
+    let program = format!(
+        "program all;
+const n = 3;
+type r = record x: integer end;
+var a: array [1..2] of real; b: bool; c: char; l: longint; {}: integer; {}: integer;
+procedure p; begin end;
+function f(x: integer): integer; begin end;
+begin
+    if odd l and not b or true then n := 1 else n := 2;
+    while false do call p;
+    case n of 1: ?n end;
+    read(n); write(f(n) + 1, 2 * 3 / 4 - 5);
+    b := (n < 1) or (n > 1) or (n <> 1) or (n >= 1) or (n <= 1) or (n == 1);
+    a[1] := 1.5; c := 'x'; r.x := 0;{}
+    !n
+end.
+",
+        "i".repeat(40),
+        "j".repeat(300),
+        " ".repeat(70_000),
+    );
+    let options = LexOptions {
+        dialect: Dialect::Extended,
+        ..LexOptions::default()
+    };
+    let mut errors = ErrorRecorder::new(FileId::default());
+    let (tokens, tables) = lex::lex(&program, &options, &mut errors);
+    assert_eq!(errors.error_count(), 0);
+    unchecked(tokens, tables, errors)
+}
+
+/// Entries of the tables, without the lookup index.
+fn tables(tables: &Tables) -> [&[String]; 2] {
+    [tables.identifiers.entries(), tables.constants.entries()]
+}
+
+#[test]
+fn token_output_round_trips() {
+    let result = every_token_kind();
+    let kinds: BTreeSet<_> = result.tokens.iter().map(|t| t.token.code()).collect();
+    assert_eq!(kinds, (1..=57).collect());
+    assert!(result.tokens.iter().any(|t| t.index.is_none()));
+    assert!(result.tokens.iter().any(|t| t.index.is_some()));
+    assert!(result.tokens.last().unwrap().offset > 1 << 16);
+
+    let msgpack = output::MessagePack.write(&result).unwrap();
+    let dump = output::read_msgpack(&msgpack).unwrap();
+    let json = output::Json.write(&result).unwrap();
+    let expected: TokenDump = serde_json::from_slice(&json).unwrap();
+    assert_eq!(dump.version, expected.version);
+    assert_eq!(format!("{:?}", dump.tokens), format!("{:?}", result.tokens));
+    assert_eq!(tables(&dump.tables), tables(&result.tables));
+    assert_eq!(
+        format!("{:?}", dump.tokens),
+        format!("{:?}", expected.tokens)
+    );
+    assert_eq!(tables(&dump.tables), tables(&expected.tables));
+}
+
+#[test]
+fn large_token_output_round_trips() {
+    // More than 2^16 tokens, for the 32-bit array length
+    let mut sources = compilation_exp1::SourceMap::new();
+    let content = compilation_exp1::testcase::gen_testcase(100_000, false, 0);
+    let file = sources.add("a.in", content);
+    let result = compilation_exp1::parse(&sources, file, &LexOptions::default());
+    assert!(result.tokens.len() > 1 << 16);
+    let dump = output::read_msgpack(&output::MessagePack.write(&result).unwrap()).unwrap();
+    assert_eq!(format!("{:?}", dump.tokens), format!("{:?}", result.tokens));
+    assert_eq!(tables(&dump.tables), tables(&result.tables));
+}
+
+fn read_error(bytes: &[u8]) -> String {
+    output::read_msgpack(bytes).unwrap_err().to_string()
+}
+
+#[test]
+fn malformed_token_output_is_rejected() {
+    let msgpack = output::MessagePack.write(&every_token_kind()).unwrap();
+    let truncated = &msgpack[..msgpack.len() - 1];
+    assert_eq!(read_error(truncated), "Unexpected end of MessagePack data");
+    assert_eq!(read_error(&[]), "Unexpected end of MessagePack data");
+
+    let trailing = [msgpack.as_slice(), &[0xc0]].concat();
+    assert_eq!(
+        read_error(&trailing),
+        "Trailing bytes after MessagePack value"
+    );
+
+    assert_eq!(read_error(&[0xc1]), "Unsupported MessagePack marker 0xc1");
+    // {"\xff": nil}
+    assert!(read_error(&[0x81, 0xa1, 0xff, 0xc0]).contains("utf-8"));
+    // {}
+    assert_eq!(read_error(&[0x80]), "missing field `tokens`");
+    // {"tokens": 5}
+    let wrong_type = [&[0x81, 0xa6][..], b"tokens", &[0x05]].concat();
+    assert!(read_error(&wrong_type).starts_with("invalid type: integer `5`"));
+}
+
+/// Round trip the value, asserting that every proper prefix of the encoding is rejected
+/// as truncated. Returns the encoding.
+fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: &T) -> Vec<u8> {
+    let bytes = msgpack::to_vec(value).unwrap();
+    assert_eq!(&msgpack::from_slice::<T>(&bytes).unwrap(), value);
+    // The header and the last byte, the rest of a long value reads the same way
+    let cuts = (0..bytes.len().min(6)).chain([bytes.len() - 1]);
+    for cut in cuts {
+        let error = msgpack::from_slice::<T>(&bytes[..cut]).unwrap_err();
+        assert_eq!(error.to_string(), "Unexpected end of MessagePack data");
+    }
+    bytes
+}
+
+#[test]
+fn strings_round_trip_in_every_length_form() {
+    let forms = [
+        (0, 0xa0),
+        (31, 0xbf),
+        (32, 0xd9),
+        (255, 0xd9),
+        (256, 0xda),
+        (65_535, 0xda),
+        (65_536, 0xdb),
+    ];
+    for (len, marker) in forms {
+        assert_eq!(round_trip(&"s".repeat(len))[0], marker, "{}", len);
+    }
+    assert_eq!(round_trip(&"é".to_string()), [0xa2, 0xc3, 0xa9]);
+}
+
+#[test]
+fn arrays_round_trip_in_every_length_form() {
+    let forms = [
+        (0, 0x90),
+        (15, 0x9f),
+        (16, 0xdc),
+        (65_535, 0xdc),
+        (65_536, 0xdd),
+    ];
+    for (len, marker) in forms {
+        assert_eq!(round_trip(&vec![1u8; len])[0], marker, "{}", len);
+    }
+}
+
+#[test]
+fn maps_round_trip_in_every_length_form() {
+    let forms = [
+        (0, 0x80),
+        (15, 0x8f),
+        (16, 0xde),
+        (65_535, 0xde),
+        (65_536, 0xdf),
+    ];
+    for (len, marker) in forms {
+        let map: BTreeMap<u32, bool> = (0..len).map(|i| (i, i % 2 == 0)).collect();
+        assert_eq!(round_trip(&map)[0], marker, "{}", len);
+    }
+}
+
+#[test]
+fn integers_round_trip_in_their_smallest_form() {
+    let unsigned = [
+        (0, 0x00),
+        (127, 0x7f),
+        (128, 0xcc),
+        (255, 0xcc),
+        (256, 0xcd),
+        (65_535, 0xcd),
+        (65_536, 0xce),
+        (u32::MAX.into(), 0xce),
+        (u64::from(u32::MAX) + 1, 0xcf),
+        (u64::MAX, 0xcf),
+    ];
+    for (value, marker) in unsigned {
+        assert_eq!(round_trip(&value)[0], marker, "{}", value);
+    }
+    let signed = [
+        (-1, 0xff),
+        (-32, 0xe0),
+        (-33, 0xd0),
+        (-128, 0xd0),
+        (-129, 0xd1),
+        (-32_768, 0xd1),
+        (-32_769, 0xd2),
+        (i32::MIN.into(), 0xd2),
+        (i64::from(i32::MIN) - 1, 0xd3),
+        (i64::MIN, 0xd3),
+    ];
+    for (value, marker) in signed {
+        assert_eq!(round_trip(&value)[0], marker, "{}", value);
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Shape {
+    Empty,
+    Circle(f64),
+    Rect { w: f32, h: f32 },
+}
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Scene {
+    name: String,
+    visible: bool,
+    parent: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    skipped: Option<u32>,
+    shapes: Vec<Shape>,
+    pair: (i8, char),
+}
+
+#[test]
+fn structs_and_enums_round_trip() {
+    let scene = Scene {
+        name: "scene".to_string(),
+        visible: true,
+        parent: None,
+        skipped: None,
+        shapes: vec![
+            Shape::Empty,
+            Shape::Circle(1.5),
+            Shape::Rect { w: 2.0, h: -0.5 },
+        ],
+        pair: (-7, 'x'),
+    };
+    // The skipped field shrinks the map of the struct
+    assert_eq!(round_trip(&scene)[0], 0x85);
+}
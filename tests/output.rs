@@ -0,0 +1,289 @@
+use compilation_exp1::{
+    output::{self, OutputWriter},
+    pre_grammar::{lex_only, unchecked},
+    table::Tables,
+    ErrorRecorder, FileId, LexOptions, ParseResult, SourceMap, Token, TokenEnum,
+};
+use serde_json::{Map, Value};
+
+fn yaml(content: &str) -> String {
+    let mut sources = SourceMap::new();
+    let file = sources.add("a.in", content);
+    let result = lex_only(&sources, file, &LexOptions::default());
+    String::from_utf8(output::Yaml.write(&result).unwrap()).unwrap()
+}
+
+/// Lines of the YAML giving the value of the key, without indentation.
+fn values<'a>(yaml: &'a str, key: &str) -> Vec<&'a str> {
+    let prefix = format!("{}: ", key);
+    yaml.lines()
+        .filter_map(|line| line.trim_start_matches([' ', '-']).strip_prefix(&prefix))
+        .collect()
+}
+
+#[test]
+fn yaml_has_the_structure_of_the_json() {
+    let expected = r#"constants:
+  - "1.5"
+identifiers:
+  - "y"
+tokens:
+  - col: 1
+    content: "y"
+    end: 1
+    index: 0
+    line: 1
+    offset: 0
+    token: Identifier
+  - col: 3
+    content: ":="
+    end: 4
+    line: 1
+    offset: 2
+    token: Assign
+  - col: 6
+    content: "1.5"
+    end: 8
+    index: 0
+    line: 1
+    offset: 5
+    token: RealLiteral
+version: 1
+"#;
+    assert_eq!(yaml("y := 1.5\n"), expected);
+}
+
+#[test]
+fn yaml_quotes_strings_read_as_other_values() {
+    let output = yaml("x Yes N null on onoff 007 'a' <> end\n");
+    assert_eq!(
+        values(&output, "content"),
+        [
+            "x", "\"Yes\"", "\"N\"", "\"null\"", "\"on\"", "onoff", "\"7\"", "\"'a'\"", "\"<>\"",
+            "end",
+        ]
+    );
+    let identifiers: Vec<_> = output
+        .lines()
+        .skip_while(|line| *line != "identifiers:")
+        .skip(1)
+        .take_while(|line| line.starts_with("  - "))
+        .collect();
+    assert_eq!(
+        identifiers,
+        [
+            "  - x",
+            "  - \"yes\"",
+            "  - \"n\"",
+            "  - \"null\"",
+            "  - \"on\"",
+            "  - onoff"
+        ]
+    );
+    // Empty tables are flow collections on the line of their key
+    assert!(yaml("begin end\n").contains("constants: []\n"));
+}
+
+/// Reader of the block YAML subset written by `Yaml`, resolving plain scalars as YAML 1.1
+/// does, so that a string written plain but read as anything else fails the comparison.
+struct YamlReader<'a> {
+    lines: Vec<(usize, &'a str)>,
+    pos: usize,
+}
+impl<'a> YamlReader<'a> {
+    fn read(yaml: &'a str) -> Value {
+        let lines = yaml
+            .lines()
+            .map(|line| {
+                let text = line.trim_start_matches(' ');
+                (line.len() - text.len(), text)
+            })
+            .collect();
+        let mut reader = Self { lines, pos: 0 };
+        let value = reader.block(0);
+        assert_eq!(reader.pos, reader.lines.len(), "unread lines");
+        value
+    }
+    /// Mapping or sequence whose lines are indented by `indent`.
+    fn block(&mut self, indent: usize) -> Value {
+        let (_, text) = self.lines[self.pos];
+        if text.starts_with("- ") {
+            let mut items = Vec::new();
+            while let Some(&(i, text)) = self.lines.get(self.pos) {
+                let Some(item) = text.strip_prefix("- ").filter(|_| i == indent) else {
+                    break;
+                };
+                if item.starts_with('"') || !item.contains(':') {
+                    items.push(scalar(item));
+                    self.pos += 1;
+                } else {
+                    // A mapping starting on the line of the dash
+                    self.lines[self.pos] = (indent + 2, item);
+                    items.push(self.block(indent + 2));
+                }
+            }
+            Value::Array(items)
+        } else {
+            let mut map = Map::new();
+            while let Some(&(i, text)) = self.lines.get(self.pos) {
+                if i != indent || text.starts_with("- ") {
+                    break;
+                }
+                let (key, rest) = key(text);
+                self.pos += 1;
+                let value = if rest.is_empty() {
+                    let nested = self.lines[self.pos].0;
+                    assert!(nested > indent, "empty value of {}", key);
+                    self.block(nested)
+                } else {
+                    scalar(rest.strip_prefix(' ').expect("space after the key"))
+                };
+                map.insert(key, value);
+            }
+            Value::Object(map)
+        }
+    }
+}
+/// Key of a mapping line and the rest after the `:`.
+fn key(text: &str) -> (String, &str) {
+    let (key, rest) = if text.starts_with('"') {
+        let mut stream = serde_json::Deserializer::from_str(text).into_iter::<String>();
+        let key = stream.next().unwrap().unwrap();
+        (key, &text[stream.byte_offset()..])
+    } else {
+        let end = text.find(':').expect("key");
+        let key = &text[..end];
+        assert!(matches!(scalar(key), Value::String(_)), "key {}", key);
+        (key.to_string(), &text[end..])
+    };
+    (key, rest.strip_prefix(':').expect("`:` after the key"))
+}
+fn scalar(text: &str) -> Value {
+    match text {
+        "[]" => return Value::Array(Vec::new()),
+        "{}" => return Value::Object(Map::new()),
+        _ if text.starts_with('"') => return Value::String(serde_json::from_str(text).unwrap()),
+        _ => {}
+    }
+    let words = [
+        ("y|yes|on|true", Value::Bool(true)),
+        ("n|no|off|false", Value::Bool(false)),
+        ("~|null", Value::Null),
+    ];
+    for (spellings, value) in words {
+        if spellings.split('|').any(|w| w.eq_ignore_ascii_case(text)) {
+            return value;
+        }
+    }
+    if let Ok(number) = serde_json::from_str::<serde_json::Number>(text) {
+        return Value::Number(number);
+    }
+    // Other numbers (`1_000`, `0x1f`, `.5`, `1:30`, `.inf`) and indicators are never plain
+    let indicators = "-?:,[]{}#&*!|>'\"%@`+.0123456789";
+    assert!(
+        !text.is_empty()
+            && !text.starts_with(|c| indicators.contains(c))
+            && !text.contains(": ")
+            && !text.contains(" #")
+            && !text.ends_with(' ')
+            && !text.contains(['\t', '\n']),
+        "ambiguous plain scalar {:?}",
+        text
+    );
+    Value::String(text.to_string())
+}
+
+fn json_and_yaml(result: &ParseResult) -> (Value, Value) {
+    let json = serde_json::from_slice(&output::Json.write(result).unwrap()).unwrap();
+    let yaml = String::from_utf8(output::Yaml.write(result).unwrap()).unwrap();
+    (json, YamlReader::read(&yaml))
+}
+
+#[test]
+fn yaml_reads_back_as_the_json() {
+    let mut paths: Vec<_> = std::fs::read_dir("data")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    for path in paths {
+        let mut sources = SourceMap::new();
+        let content = std::fs::read_to_string(&path).unwrap();
+        let file = sources.add(path.to_str().unwrap(), content);
+        let result = lex_only(&sources, file, &LexOptions::default());
+        let (json, yaml) = json_and_yaml(&result);
+        assert_eq!(yaml, json, "{}", path.display());
+    }
+}
+
+#[test]
+fn yaml_quotes_every_string_read_as_something_else() {
+    let contents = [
+        "yes",
+        "No",
+        "ON",
+        "y",
+        "~",
+        "null",
+        "",
+        "1e3",
+        "0x1f",
+        "0o17",
+        ".5",
+        "-1",
+        "+1",
+        "1_000",
+        "12:30",
+        ".inf",
+        ".NaN",
+        "a: b",
+        "a:b",
+        "a #b",
+        "#c",
+        "- x",
+        "-x",
+        "[x]",
+        "{x}",
+        "&a",
+        "*a",
+        "!t",
+        "|",
+        ">x",
+        "'q'",
+        "\"q\"",
+        "%x",
+        "@x",
+        "`x",
+        "?x",
+        "tab\tx",
+        "line\nbreak",
+        "trailing ",
+        " leading",
+        "é",
+        "x.y",
+        "a-b",
+        "plain",
+        "a_b",
+    ];
+    let mut tables = Tables::default();
+    let tokens = contents
+        .iter()
+        .enumerate()
+        .map(|(i, content)| {
+            let mut token = Token {
+                offset: i,
+                end: i + 1,
+                line: 1,
+                col: i + 1,
+                content: content.to_string(),
+                token: TokenEnum::Identifier,
+                index: None,
+            };
+            token.index = tables.intern(&token);
+            token
+        })
+        .collect();
+    let result = unchecked(tokens, tables, ErrorRecorder::new(FileId::default()));
+    let (json, yaml) = json_and_yaml(&result);
+    assert_eq!(yaml, json);
+}
@@ -0,0 +1,93 @@
+use compilation_exp1::{
+    session::{Artifact, OutputFormat, Session},
+    TokenEnum,
+};
+
+/// The codes of `--format pairs` are published, they must never change.
+#[test]
+fn token_codes_are_pinned() {
+    use TokenEnum::*;
+    let codes = [
+        (Var, 1),
+        (If, 2),
+        (Then, 3),
+        (Else, 4),
+        (While, 5),
+        (Do, 6),
+        (Begin, 7),
+        (End, 8),
+        (And, 9),
+        (Or, 10),
+        (Integer, 11),
+        (Longint, 12),
+        (Bool, 13),
+        (Real, 14),
+        (Add, 15),
+        (Sub, 16),
+        (Mul, 17),
+        (Div, 18),
+        (Assign, 19),
+        (Lt, 20),
+        (Gt, 21),
+        (Ne, 22),
+        (Ge, 23),
+        (Le, 24),
+        (Eq, 25),
+        (Colon, 26),
+        (LParen, 27),
+        (RParen, 28),
+        (Comma, 29),
+        (SemiColon, 30),
+        (Identifier, 31),
+        (IntLiteral, 32),
+        (Program, 33),
+        (Odd, 34),
+        (Call, 35),
+        (Question, 36),
+        (Exclamation, 37),
+        (Type, 38),
+        (Record, 39),
+        (Dot, 40),
+        (DotDot, 41),
+        (Of, 42),
+        (Array, 43),
+        (LBracket, 44),
+        (RBracket, 45),
+        (Procedure, 46),
+        (Function, 47),
+        (Const, 48),
+        (Case, 49),
+        (Read, 50),
+        (Write, 51),
+        (True, 52),
+        (False, 53),
+        (Not, 54),
+        (RealLiteral, 55),
+        (Char, 56),
+        (CharLiteral, 57),
+    ];
+    for (token, code) in codes {
+        assert_eq!(token.code(), code, "{:?}", token);
+    }
+}
+
+#[test]
+fn pairs_carry_the_classic_codes() {
+    let mut session = Session::new().format(OutputFormat::Pairs);
+    let file = session.add_source("a.in", "var i: integer; begin i := 50 end".to_string());
+    assert!(session.check());
+    let pairs = String::from_utf8(session.emit(file, Artifact::Tokens).unwrap()).unwrap();
+    let expected = "\
+(1, -)
+(31, 0)
+(26, -)
+(11, -)
+(30, -)
+(7, -)
+(31, 0)
+(19, -)
+(32, 0)
+(8, -)
+";
+    assert!(pairs.starts_with(expected), "{}", pairs);
+}
@@ -0,0 +1,52 @@
+use compilation_exp1::session::{Artifact, Session};
+
+fn emit(content: &str, artifact: Artifact) -> String {
+    let mut session = Session::new();
+    let file = session.add_source("a.in", content.to_string());
+    session.check();
+    String::from_utf8(session.emit(file, artifact).unwrap()).unwrap()
+}
+
+#[test]
+fn parse_tree_labels_nodes_with_productions() {
+    assert_eq!(
+        emit("var i, j: integer;\nbegin end.\n", Artifact::ParseTree),
+        "\
+S -> Var D
+├── Var
+└── D -> D1 D'
+    ├── D1 -> I : t ;
+    │   ├── I -> i I'
+    │   │   ├── i
+    │   │   └── I' -> , i I'
+    │   │       ├── ,
+    │   │       ├── i
+    │   │       └── I' -> ε
+    │   ├── :
+    │   ├── t
+    │   └── ;
+    └── D' -> ε
+"
+    );
+    assert_eq!(emit("begin end.\n", Artifact::ParseTree), "S -> ε\n");
+}
+
+#[test]
+fn parse_tree_dot_has_an_edge_to_every_node_but_the_root() {
+    let dot = emit(
+        "var i, j: integer; k: bool;\nbegin end.\n",
+        Artifact::ParseTreeDot,
+    );
+    assert!(dot.starts_with("digraph ParseTree {\n"), "{}", dot);
+    assert!(dot.ends_with("}\n"), "{}", dot);
+    let nodes = dot.lines().filter(|l| l.contains("[label=")).count();
+    let edges = dot.lines().filter(|l| l.contains(" -> n")).count();
+    assert_eq!(edges, nodes - 1, "{}", dot);
+    // Productions are boxes, terminals are plain text
+    assert!(
+        dot.contains("[label=\"I' -> , i I'\", shape=box];"),
+        "{}",
+        dot
+    );
+    assert!(dot.contains("[label=\",\", shape=plaintext];"), "{}", dot);
+}
@@ -0,0 +1,331 @@
+use compilation_exp1::{error::apply_fixes, parse, LexOptions, SourceMap};
+
+/// Codes of the diagnostics of the program, in source order.
+fn codes(content: &str) -> Vec<String> {
+    let mut sources = SourceMap::new();
+    let file = sources.add("a.in", content);
+    let result = parse(&sources, file, &LexOptions::default());
+    result
+        .errors
+        .sorted()
+        .into_iter()
+        .map(|(_, e)| e.code.to_string())
+        .collect()
+}
+
+#[test]
+fn bool_accepts_zero_and_one() {
+    let program = "var flag: bool;\nbegin flag := 0; flag := 1; flag := true end\n";
+    assert_eq!(codes(program), Vec::<String>::new());
+    assert_eq!(codes("var flag: bool;\nbegin flag := 2 end\n"), ["E040"]);
+}
+
+#[test]
+fn longint_does_not_narrow() {
+    let declarations = "var i: integer; l: longint; s: 1..10;\n";
+    assert_eq!(
+        codes(&format!("{}begin l := i; l := s end\n", declarations)),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        codes(&format!(
+            "{}begin i := l; s := l; i := i + l end\n",
+            declarations
+        )),
+        ["E040", "E040", "E040"]
+    );
+}
+
+#[test]
+fn index_checks_are_reported_once() {
+    let declarations = "var a: array [1..3, 0..1] of integer;\n";
+    assert_eq!(
+        codes(&format!("{}begin a[4, 0] := 1 end\n", declarations)),
+        ["E034"]
+    );
+    assert_eq!(
+        codes(&format!("{}begin a[1] := 1 end\n", declarations)),
+        ["E034"]
+    );
+    assert_eq!(
+        codes(&format!("{}begin a[1][0] := 1 end\n", declarations)),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn constant_out_of_range_is_reported_once() {
+    assert_eq!(
+        codes("var s: 1..10;\nbegin s := 11; s := -1; s := 10 end\n"),
+        ["E035", "E035"]
+    );
+}
+
+#[test]
+fn statement_errors_are_reported_once() {
+    let declarations = "const size = 3;\nvar r: record x: integer end; i: integer;\n";
+    let program = format!(
+        "{}begin y := 1; r.z := 1; i.x := 1; i[1] := 1; size := 1; read(size); call p(y) end\n",
+        declarations
+    );
+    assert_eq!(
+        codes(&program),
+        ["E030", "E032", "E033", "E033", "E036", "E036", "E031", "E030"]
+    );
+}
+
+#[test]
+fn discarded_comparison_is_a_warning() {
+    assert_eq!(codes("var i: integer;\nbegin i == 1 end\n"), ["W002"]);
+}
+
+#[test]
+fn case_labels_are_constant_unique_and_of_the_selector_type() {
+    let declarations = "const two = 2; var i: integer; c: char;\n";
+    assert_eq!(
+        codes(&format!(
+            "{}begin case i of 1, two: i := 0; 2: i := 1; 'a': i := 2; i: i := 3 end end\n",
+            declarations
+        )),
+        ["E045", "E044", "E043"]
+    );
+    assert_eq!(
+        codes(&format!(
+            "{}begin case c of 'a': i := 0; 'b', 'a': i := 1 end end\n",
+            declarations
+        )),
+        ["E045"]
+    );
+    assert_eq!(
+        codes(&format!(
+            "{}begin case i of 1: ; two, 3: i := 1; end end\n",
+            declarations
+        )),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn read_targets_must_be_declared_variables() {
+    let declarations = "const k = 3; var i: integer; a: array [1..2] of integer;\n";
+    assert_eq!(
+        codes(&format!(
+            "{}begin read(i, a[1]); write(i, i + 1, 'x') end\n",
+            declarations
+        )),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        codes(&format!("{}begin read(i, k, j) end\n", declarations)),
+        ["E036", "E030"]
+    );
+    assert_eq!(
+        codes(&format!("{}begin read(i + 1); write() end\n", declarations)),
+        ["E020", "E020"]
+    );
+}
+
+#[test]
+fn not_requires_a_bool_operand() {
+    let declarations = "var p, q: bool; i: integer;\n";
+    assert_eq!(
+        codes(&format!(
+            "{}begin p := true; q := not p; if not p and q then p := false end\n",
+            declarations
+        )),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        codes(&format!(
+            "{}begin p := not i; p := not 1; i := true end\n",
+            declarations
+        )),
+        ["E048", "E048", "E040"]
+    );
+}
+
+#[test]
+fn char_is_distinct_from_integer() {
+    assert_eq!(
+        codes("var c: char; i: integer;\nbegin c := 'a'; c := i; i := c; if c < 'b' then c := '''' end\n"),
+        ["E040", "E040"]
+    );
+}
+
+#[test]
+fn eq_confusion_fixes_apply_cleanly() {
+    let program =
+        "const n = 3;\nvar i: integer;\nbegin i = n; i == 2; if i = 1 then i := 2; i=1 end.\n";
+    let mut sources = SourceMap::new();
+    let file = sources.add("a.in", program);
+    let result = parse(&sources, file, &LexOptions::default());
+    assert_eq!(codes(program), ["E002", "W002", "E002", "E002"]);
+    // A condition may mean either operator, so it has no fix
+    let fixed = apply_fixes(program, &result.errors.fixes());
+    assert_eq!(
+        fixed,
+        "const n = 3;\nvar i: integer;\nbegin i := n; i := 2; if i = 1 then i := 2; i:=1 end.\n"
+    );
+    assert_eq!(codes(&fixed), ["E002"]);
+}
+
+#[test]
+fn type_aliases_are_their_underlying_type() {
+    assert_eq!(
+        codes(
+            "type index = integer; flag = bool; other = index;\n\
+             var i: index; o: other; f: flag; j: integer;\n\
+             begin i := j; j := i; o := i; f := true; f := i end.\n"
+        ),
+        ["E040"]
+    );
+    assert_eq!(
+        codes("type t = undefinedtype;\nvar i: t;\nbegin end.\n"),
+        ["E015"]
+    );
+    assert_eq!(
+        codes("type index = integer; index = bool;\nbegin end.\n"),
+        ["E011"]
+    );
+}
+
+#[test]
+fn record_fields_are_typed() {
+    let declarations = "var r: record x, y: integer; f: bool end; i: integer;\n";
+    assert_eq!(
+        codes(&format!(
+            "{}begin r.x := 1; r.f := true; i := r.y + r.x end.\n",
+            declarations
+        )),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        codes(&format!(
+            "{}begin r.z := 1; r.f := 2; r.x.y := 2; i := r end.\n",
+            declarations
+        )),
+        ["E032", "E040", "E033", "E040"]
+    );
+    assert_eq!(
+        codes("var r: record x: integer; x: bool end;\nbegin end.\n"),
+        ["E013"]
+    );
+}
+
+#[test]
+fn enumerators_are_ordered_constants_of_their_type() {
+    let program = "type color = (red, green, blue);\nvar c: color; i: integer;\n\
+                   begin c := red; if c < blue then c := green;\n\
+                   case c of red: i := 1; green, blue: i := 2 end end.\n";
+    assert_eq!(codes(program), Vec::<String>::new());
+    let mut sources = SourceMap::new();
+    let file = sources.add("a.in", program);
+    let result = parse(&sources, file, &LexOptions::default());
+    let ordinals: Vec<_> = ["red", "green", "blue"]
+        .iter()
+        .map(|e| result.enumerators[*e].1)
+        .collect();
+    assert_eq!(ordinals, [0, 1, 2]);
+
+    let declarations =
+        "type color = (red, green); fruit = (apple, pear);\nvar c: color; i: integer;\n";
+    assert_eq!(
+        codes(&format!(
+            "{}begin case c of red: ; 1: end; c := 1; i := red; c := apple end.\n",
+            declarations
+        )),
+        ["E044", "E040", "E040", "E040"]
+    );
+    // Enumerators share the scope of the other identifiers
+    assert_eq!(
+        codes("type color = (red, green); fruit = (apple, red);\nbegin end.\n"),
+        ["E012"]
+    );
+    assert_eq!(
+        codes("type color = (red, green);\nvar red: integer;\nbegin end.\n"),
+        ["E010"]
+    );
+}
+
+#[test]
+fn subrange_types_bound_constant_assignments() {
+    assert_eq!(
+        codes(
+            "const lo = 1; hi = 3;\ntype digit = 0..9; neg = -5..-1; r = lo..hi;\n\
+             var d: digit; n: neg; x: r; i: integer;\n\
+             begin d := 9; d := 10; n := -5; n := 0; x := 3; x := 4; d := i; i := d end.\n"
+        ),
+        ["E035", "E035", "E035"]
+    );
+    assert_eq!(codes("type bad = 9..0;\nbegin end.\n"), ["E016"]);
+}
+
+#[test]
+fn multi_dimensional_indices_are_checked_per_dimension() {
+    let declarations = "type row = 1..3;\n\
+                        var a: array [row, 0..1] of integer; b: array [1..2] of array [1..2] of bool;\n\
+                        i: integer;\n";
+    assert_eq!(
+        codes(&format!(
+            "{}begin a[3, 1] := a[1, 0] + 1; b[1][2] := true; a[i, 1] := 0; b[i][1] := b[2][i] end.\n",
+            declarations
+        )),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        codes(&format!(
+            "{}begin a[0, 0] := 1; a[1, 2] := 1; b[1, 2] := false; b[1][3] := true; a[true, 0] := 1 end.\n",
+            declarations
+        )),
+        ["E034", "E034", "E034", "E034", "E033"]
+    );
+}
+
+#[test]
+fn calls_are_checked_against_the_declarations() {
+    let declarations = "var g: integer; b: bool;\n\
+                        procedure p(x: integer; y: bool); var l: integer; begin l := x; g := l end;\n\
+                        function f(n: integer): integer; begin f := n + 1 end;\n\
+                        function h: bool; begin h := true end;\n";
+    assert_eq!(
+        codes(&format!(
+            "{}begin call p(1, true); g := f(2); b := h; b := not h end.\n",
+            declarations
+        )),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        codes(&format!(
+            "{}begin call p(1); call p(true, 1); call f(1); g := p; g := f(1, 2); g := l; b := f(1) end.\n",
+            declarations
+        )),
+        ["E046", "E047", "E037", "E037", "E046", "E030", "E040"]
+    );
+    assert_eq!(
+        codes("procedure p; begin end; procedure p; begin end;\nbegin end.\n"),
+        ["E010"]
+    );
+    assert_eq!(
+        codes("function f(n: integer): integer; var n: integer; begin f := n end;\nbegin end.\n"),
+        ["E010"]
+    );
+}
+
+#[test]
+fn constants_have_values_and_cannot_be_redeclared() {
+    let program =
+        "const a = 1; b = -2; c = a; d = 007;\nvar i: integer;\nbegin i := a + b + c end.\n";
+    assert_eq!(codes(program), ["W001"]);
+    let mut sources = SourceMap::new();
+    let file = sources.add("a.in", program);
+    let consts = parse(&sources, file, &LexOptions::default()).consts;
+    let values: Vec<_> = consts.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+    assert_eq!(values, [("a", 1), ("b", -2), ("c", 1), ("d", 7)]);
+
+    assert_eq!(codes("const a = 1; a = 2;\nbegin end.\n"), ["E010"]);
+    assert_eq!(
+        codes("const a = 1;\nvar a: integer;\nbegin end.\n"),
+        ["E010"]
+    );
+    assert_eq!(codes("const a = x;\nbegin end.\n"), ["E020"]);
+}